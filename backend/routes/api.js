@@ -216,6 +216,7 @@ router.post("/generate-market", async (req, res, next) => {
 
     const result = await generateMarket({
       sport, country, league, teamA, teamB, matchDate, marketType,
+      teamAId: req.body.teamAId, teamBId: req.body.teamBId,
       espnEventId: req.body.espnEventId, lang: lang || "ru",
     });
     res.json(result);
@@ -334,6 +335,7 @@ router.post("/tls-proof/:id", async (req, res, next) => {
 
     // Запрашиваем proof у TLS Oracle backend
     const { default: config } = await import("../config.js");
+    const { TLS_ESPN_FIELDS } = await import("../services/tls-relayer.js");
     const url = `${config.tlsOracle.backendUrl}/api/prove-espn`;
     const headers = { "Content-Type": "application/json" };
     if (config.tlsOracle.apiKey) headers["X-API-Key"] = config.tlsOracle.apiKey;
@@ -341,7 +343,7 @@ router.post("/tls-proof/:id", async (req, res, next) => {
     const proveResp = await fetch(url, {
       method: "POST",
       headers,
-      body: JSON.stringify({ espnEventId: espnId, sport, league }),
+      body: JSON.stringify({ espnEventId: espnId, sport, league, fields: TLS_ESPN_FIELDS }),
       signal: AbortSignal.timeout(120_000),
     });
 
@@ -90,7 +90,7 @@ export const SPORTS_CONFIG = {
   football: {
     label: "Футбол", labelEn: "Football",
     search: "Football Soccer",
-    marketTypes: ["winner", "over-under"],
+    marketTypes: ["winner", "over-under", "asian-handicap", "correct-score", "first-half-winner"],
     countries: {
       england: {
         label: "Англия", labelEn: "England",
@@ -201,7 +201,7 @@ export const SPORTS_CONFIG = {
   basketball: {
     label: "Баскетбол", labelEn: "Basketball",
     search: "Basketball",
-    marketTypes: ["winner", "over-under"],
+    marketTypes: ["winner", "over-under", "spread", "range", "first-half-winner"],
     countries: {
       usa: {
         label: "США", labelEn: "USA",
@@ -215,7 +215,7 @@ export const SPORTS_CONFIG = {
   hockey: {
     label: "Хоккей", labelEn: "Hockey",
     search: "Ice Hockey",
-    marketTypes: ["winner", "over-under"],
+    marketTypes: ["winner", "over-under", "spread", "correct-score"],
     countries: {
       "north-america": {
         label: "США/Канада", labelEn: "USA/Canada",
@@ -228,7 +228,7 @@ export const SPORTS_CONFIG = {
   "american-football": {
     label: "Американский футбол", labelEn: "American Football",
     search: "American Football",
-    marketTypes: ["winner", "over-under"],
+    marketTypes: ["winner", "over-under", "spread", "range"],
     countries: {
       usa: {
         label: "США", labelEn: "USA",
@@ -297,6 +297,11 @@ export const SPORTS_CONFIG = {
 export const MARKET_TYPES = {
   "winner": { ru: "Кто победит", en: "Winner" },
   "over-under": { ru: "Тотал (больше/меньше)", en: "Over/Under" },
+  "spread": { ru: "Фора", en: "Spread" },
+  "asian-handicap": { ru: "Азиатская фора", en: "Asian Handicap" },
+  "correct-score": { ru: "Точный счёт", en: "Correct Score" },
+  "range": { ru: "Диапазон тотала", en: "Total Range" },
+  "first-half-winner": { ru: "Победитель первого тайма", en: "First Half Winner" },
 };
 
 // ── AI: получить ближайшие матчи ─────────────────────────────
@@ -423,6 +428,13 @@ function getSportLinesHint(sport, lang) {
   return parts.length > 0 ? "\n\nSport-specific lines:\n" + parts.join("\n") : "";
 }
 
+// Фора из названия исхода — последнее слово со знаком ("1. FC Köln -0.25" → -0.25).
+// Цифры в названии команды ("Schalke 04") линией не считаются
+function signedLine(label) {
+  const m = /\s([+-]\d+(?:\.\d+)?)\s*$/.exec(String(label));
+  return m ? Number(m[1]) : null;
+}
+
 // ── AI: сгенерировать рынок для выбранного матча ─────────────
 
 export async function generateMarket({
@@ -431,6 +443,8 @@ export async function generateMarket({
   league,
   teamA,
   teamB,
+  teamAId,
+  teamBId,
   matchDate,
   marketType,
   espnEventId,
@@ -475,7 +489,12 @@ RESPOND STRICTLY IN JSON:
 
 Outcome rules by type:
 - winner: ["${teamA}", "Draw", "${teamB}"] (remove "Draw" if impossible, e.g. tennis/MMA)
-- over-under: ${ouExample} (pick the best line for this sport and matchup)`
+- over-under: ${ouExample} (pick the best line for this sport and matchup)
+- spread: ["${teamA} -5.5", "${teamB} +5.5"] (home team first; signed handicap for ${teamA}, pick a realistic line)
+- asian-handicap: ["${teamA} -0.25", "${teamB} +0.25"] (home team first; line step 0.25, quarter lines allowed)
+- correct-score: ["1-0", "2-1", "1-1", "0-2", "Any other"] (home-away scorelines, 4-9 likely scores + "Any other")
+- range: ["0-199", "200-219", "220-239", "240+"] (non-overlapping total buckets, the last one open-ended with "+")
+- first-half-winner: ["${teamA}", "Draw", "${teamB}"] (always keep "Draw" — a half can end level)`
     : `Сгенерируй предсказательный рынок для матча.
 
 Матч: ${teamA} vs ${teamB}
@@ -501,7 +520,12 @@ ${linesHint}
 
 Правила исходов по типу:
 - winner: ["${teamA}", "Ничья", "${teamB}"] (убери "Ничья" если ничья невозможна, напр. теннис/MMA)
-- over-under: ${ouExample} (выбери лучшую линию для данного спорта и матча)`;
+- over-under: ${ouExample} (выбери лучшую линию для данного спорта и матча)
+- spread: ["${teamA} -5.5", "${teamB} +5.5"] (хозяева первыми; фора со знаком для ${teamA}, выбери реалистичную линию)
+- asian-handicap: ["${teamA} -0.25", "${teamB} +0.25"] (хозяева первыми; шаг линии 0.25, допустимы четвертные линии)
+- correct-score: ["1-0", "2-1", "1-1", "0-2", "Any other"] (счёт хозяева-гости, 4-9 вероятных счетов + "Any other")
+- range: ["0-199", "200-219", "220-239", "240+"] (корзины тотала без пересечений, последняя открытая с "+")
+- first-half-winner: ["${teamA}", "Ничья", "${teamB}"] (всегда оставляй "Ничья" — тайм может закончиться вничью)`;
 
   checkBudget();
 
@@ -518,5 +542,40 @@ ${linesHint}
   result.league = infoForEspn.espnPath ? infoForEspn.espnPath.split("/")[1] : "";
   result.marketType = marketType;
 
+  // Первый тайм по ESPN linescores: в футболе период — тайм, в баскетболе — четверть
+  if (marketType === "first-half-winner") {
+    result.kind = { type: "period_winner", fromPeriod: 1, toPeriod: sport === "basketball" ? 2 : 1, draw: true };
+  }
+
+  // Фора: исход 0 — хозяева (teamA), команды привязываются по позиции
+  if ((marketType === "spread" || marketType === "asian-handicap") && teamAId && teamBId && Array.isArray(result.outcomes) && result.outcomes.length === 2) {
+    result.outcomeTeamIds = [String(teamAId), String(teamBId)];
+  }
+
+  // Явный тип с линией — контракт не угадывает фору по названию
+  if (marketType === "spread" && Array.isArray(result.outcomes)) {
+    const line = signedLine(result.outcomes[0]);
+    if (line === null) throw new Error(`Фора не найдена в исходе "${result.outcomes[0]}"`);
+    result.kind = { type: "spread", handicapX10: Math.round(line * 10) };
+  }
+  if (marketType === "asian-handicap" && Array.isArray(result.outcomes)) {
+    const line = signedLine(result.outcomes[0]);
+    if (line === null) throw new Error(`Фора не найдена в исходе "${result.outcomes[0]}"`);
+    result.kind = { type: "asian_handicap", handicapX100: Math.round(line * 100) };
+  }
+
+  // Привязка исходов winner-рынка к ESPN team id ("" — ничья)
+  if ((marketType === "winner" || marketType === "first-half-winner") && teamAId && teamBId && Array.isArray(result.outcomes)) {
+    const ids = result.outcomes.map((o) =>
+      o === teamA ? String(teamAId) : o === teamB ? String(teamBId) : "",
+    );
+    if (ids.filter(Boolean).length === 2) result.outcomeTeamIds = ids;
+  }
+
+  // Бой / теннисный матч: ESPN без home/away — итог по athlete id победителя
+  if (marketType === "winner" && (sport === "mma" || sport === "tennis") && result.outcomeTeamIds) {
+    result.kind = { type: "head_to_head", draw: result.outcomes.length === 3 };
+  }
+
   return result;
 }
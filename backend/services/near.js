@@ -106,6 +106,11 @@ export async function getMarket(marketId) {
   return viewContract("get_market", { market_id: marketId });
 }
 
+// Голоса источников счёта (OutLayer / TLS Oracle / oracle) по рынку
+export async function getScoreVotes(marketId) {
+  return (await viewContract("get_score_votes", { market_id: marketId })) || [];
+}
+
 export async function getOdds(marketId) {
   return viewContract("get_odds", { market_id: marketId });
 }
@@ -139,8 +144,12 @@ export async function createMarket({
   sport,
   league,
   marketType,
+  kind,
+  outcomeTeamIds,
 }) {
   const account = await initOracleAccount();
+  // Залог за создание рынка — возвращается после разрешения
+  const bond = (await viewContract("get_creation_bond")) || "0";
 
   const result = await account.functionCall({
     contractId: config.near.contractId,
@@ -156,9 +165,11 @@ export async function createMarket({
       sport: sport || null,
       league: league || null,
       market_type: marketType || "winner",
+      kind: kind || null,
+      outcome_team_ids: outcomeTeamIds || null,
     },
     gas: "30000000000000", // 30 TGas
-    attachedDeposit: "0",
+    attachedDeposit: bond,
   });
 
   const txHash = result.transaction?.hash || result.transaction_outcome?.id;
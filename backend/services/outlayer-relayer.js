@@ -9,7 +9,7 @@
  */
 
 import config from "../config.js";
-import { getMarkets, requestResolution } from "./near.js";
+import { getMarkets, getScoreVotes, requestResolution } from "./near.js";
 
 let relayerIntervalId = null;
 
@@ -30,7 +30,12 @@ async function resolveViaOutLayer(market) {
 // Проверка и разрешение спортивных рынков
 async function checkSportsMarkets() {
   try {
-    const markets = await getMarkets({ status: "closed", limit: 50 });
+    // Статус "closed" хранится on-chain только после close_market —
+    // рынки с истёкшим дедлайном ставок могут ещё числиться "active"
+    const markets = [
+      ...(await getMarkets({ status: "active", limit: 50 })),
+      ...(await getMarkets({ status: "closed", limit: 50 })),
+    ];
     if (!markets || markets.length === 0) return;
 
     const now = BigInt(Date.now()) * BigInt(1_000_000);
@@ -41,6 +46,10 @@ async function checkSportsMarkets() {
       if (!espnId) continue;
       if (now < BigInt(market.resolution_date || market.resolutionDate)) continue;
 
+      // Голос OutLayer уже учтён — рынок ждёт остальные источники кворума
+      const votes = await getScoreVotes(market.id);
+      if (votes.some((v) => v.source === "outlayer")) continue;
+
       await resolveViaOutLayer(market);
       // Пауза между вызовами — OutLayer + NEAR gas
       await new Promise((r) => setTimeout(r, 5000));
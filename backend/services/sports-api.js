@@ -51,11 +51,15 @@ function parseEvent(event) {
   const away = competitors.find((c) => c.homeAway === "away") || competitors[1];
 
   let teamA, teamB;
+  // ESPN team id (athlete id у бойцов и теннисистов) — контракт сопоставляет по ним исходы
+  let teamAId = "", teamBId = "";
 
   if (home && away) {
     // Стандартный матч: 2 участника (команды или бойцы)
     teamA = home.team?.displayName || home.athlete?.displayName || "TBD";
     teamB = away.team?.displayName || away.athlete?.displayName || "TBD";
+    teamAId = home.team?.id || home.athlete?.id || "";
+    teamBId = away.team?.id || away.athlete?.id || "";
   } else if (competitors.length === 1) {
     // Один участник (например, F1 — гонка)
     teamA = event.name || "TBD";
@@ -76,6 +80,8 @@ function parseEvent(event) {
     id: event.id, // ESPN Event ID для OutLayer permissionless resolution
     teamA,
     teamB,
+    teamAId,
+    teamBId,
     date: event.date,
     round,
   };
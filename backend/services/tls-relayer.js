@@ -12,12 +12,18 @@
 import config from "../config.js";
 import {
   getMarkets,
+  getScoreVotes,
   submitTlsAttestation,
   requestTlsResolution,
 } from "./near.js";
 
 let relayerIntervalId = null;
 
+// Поля компактного ответа сверх счёта: team id, периоды, овертайм / серия
+// и счёт основного времени (hid/aid, hp/ap, ot/so, rhs/ras, hss/ass).
+// Без них голос TLS не учитывается на рынках по основному времени (футбол)
+export const TLS_ESPN_FIELDS = ["ids", "linescores", "extraTime"];
+
 /**
  * Запросить ESPN proof через TLS Oracle backend
  * @param {string} espnEventId
@@ -35,7 +41,7 @@ async function requestEspnProof(espnEventId, sport, league) {
   const resp = await fetch(url, {
     method: "POST",
     headers,
-    body: JSON.stringify({ espnEventId, sport, league }),
+    body: JSON.stringify({ espnEventId, sport, league, fields: TLS_ESPN_FIELDS }),
     signal: AbortSignal.timeout(120_000), // 2 мин (MPC-TLS + ZK)
   });
 
@@ -66,7 +72,8 @@ async function resolveViaTls(market) {
   // Парсим компактные ESPN данные
   const espnData = JSON.parse(proofData.responseData);
   console.log(
-    `[tls-relayer] ESPN данные: ${espnData.ht} ${espnData.hs}:${espnData.as} ${espnData.at} (${espnData.st})`,
+    `[tls-relayer] ESPN данные: ${espnData.ht} ${espnData.hs}:${espnData.as} ${espnData.at} (${espnData.st})` +
+      (espnData.ot != null ? `, осн. время ${espnData.rhs}:${espnData.ras}, OT ${espnData.ot}, SO ${espnData.so}` : ""),
   );
 
   // Если матч не завершён — пропускаем
@@ -125,7 +132,12 @@ async function resolveViaTls(market) {
  */
 async function checkSportsMarketsTls() {
   try {
-    const markets = await getMarkets({ status: "closed", limit: 50 });
+    // Статус "closed" хранится on-chain только после close_market —
+    // рынки с истёкшим дедлайном ставок могут ещё числиться "active"
+    const markets = [
+      ...(await getMarkets({ status: "active", limit: 50 })),
+      ...(await getMarkets({ status: "closed", limit: 50 })),
+    ];
     if (!markets || markets.length === 0) return;
 
     const now = BigInt(Date.now()) * BigInt(1_000_000);
@@ -136,6 +148,10 @@ async function checkSportsMarketsTls() {
       if (now < BigInt(market.resolution_date || market.resolutionDate))
         continue;
 
+      // Голос TLS Oracle уже учтён — рынок ждёт остальные источники кворума
+      const votes = await getScoreVotes(market.id);
+      if (votes.some((v) => v.source === "tls_oracle")) continue;
+
       try {
         await resolveViaTls(market);
       } catch (err) {
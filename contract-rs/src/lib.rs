@@ -1,15 +1,21 @@
+// Публичные методы NEAR принимают аргументы JSON-полями, а не структурой
+#![allow(clippy::too_many_arguments)]
+
 /// NearCast — Prediction Market на NEAR с Permissionless ESPN Oracle
 ///
 /// Контракт поддерживает:
 /// - Создание рынков с опциональными ESPN метаданными
 /// - Ставки с внутреннего баланса (pari-mutuel)
 /// - Permissionless разрешение через OutLayer TEE (ESPN Oracle)
-/// - Логика определения победителя ON-CHAIN (fuzzy match по именам команд)
+/// - Логика определения победителя ON-CHAIN (исходы сопоставляются с
+///   командами по ESPN team id; старые рынки без id — по именам)
 /// - Аннулирование (void) с возвратом ставок
-
 use near_sdk::json_types::U128;
-use near_sdk::store::{IterableMap, LookupMap};
-use near_sdk::{env, log, near, AccountId, BorshStorageKey, Gas, NearToken, Promise};
+use near_sdk::store::{IterableMap, IterableSet, LookupMap};
+use near_sdk::{
+    env, log, near, AccountId, BorshStorageKey, Gas, NearToken, Promise, PromiseError,
+    PromiseOrValue,
+};
 use serde::{Deserialize, Serialize};
 
 // ── Константы ────────────────────────────────────────────────────
@@ -18,6 +24,23 @@ const ONE_NEAR: u128 = 1_000_000_000_000_000_000_000_000;
 const MIN_BET: u128 = ONE_NEAR / 10; // 0.1 NEAR
 const MAX_OUTCOMES: usize = 10;
 const MIN_OUTCOMES: usize = 2;
+/// Срок разрешения по умолчанию: resolution_date + 7 дней
+const DEFAULT_RESOLUTION_GRACE: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+/// После переноса матча разрешение возможно через 3 часа от нового начала
+const RESCHEDULE_RESOLUTION_DELAY: u64 = 3 * 60 * 60 * 1_000_000_000;
+
+/// Статусы ESPN, после которых матч не будет доигран — рынок аннулируется
+const VOID_EVENT_STATUSES: [&str; 2] = ["canceled", "abandoned"];
+/// Матч перенесён или приостановлен — рынок ждёт новую дату
+const DELAYED_EVENT_STATUSES: [&str; 2] = ["postponed", "suspended"];
+
+/// Байты, резервируемые за регистрацию в storage (NEP-145)
+const STORAGE_REGISTRATION_BYTES: u64 = 160;
+
+/// Базисные пункты: 10_000 = 100%
+const BPS_DENOMINATOR: u128 = 10_000;
+/// Потолок суммарной комиссии (протокол + создатель) — 10%
+const MAX_TOTAL_FEE_BPS: u16 = 1_000;
 
 /// Gas для вызова OutLayer request_execution
 const GAS_FOR_OUTLAYER: Gas = Gas::from_tgas(200);
@@ -25,12 +48,30 @@ const GAS_FOR_OUTLAYER: Gas = Gas::from_tgas(200);
 const GAS_FOR_CALLBACK: Gas = Gas::from_tgas(30);
 /// Минимальный депозит для OutLayer (0.1 NEAR)
 const OUTLAYER_MIN_DEPOSIT: u128 = ONE_NEAR / 10;
+/// Через сколько блоков без callback блокировка разрешения считается просроченной
+const RESOLUTION_LOCK_BLOCKS: u64 = 600;
+/// Сколько последних запросов разрешения хранится по рынку
+const MAX_RESOLUTION_REQUESTS: usize = 10;
+
+/// Залог за предложение исхода по умолчанию (оптимистичное разрешение)
+const DEFAULT_PROPOSAL_BOND: u128 = ONE_NEAR;
+/// Окно для оспаривания предложения по умолчанию — 24 часа
+const DEFAULT_DISPUTE_WINDOW: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Период оспаривания результата OutLayer / TLS Oracle по умолчанию — 6 часов
+const DEFAULT_CHALLENGE_WINDOW: u64 = 6 * 60 * 60 * 1_000_000_000;
 
 /// Gas для view-call к TLS Oracle (get_attestation)
 const GAS_FOR_TLS_VIEW: Gas = Gas::from_tgas(10);
 /// Gas для callback on_tls_attestation_result
 const GAS_FOR_TLS_CALLBACK: Gas = Gas::from_tgas(20);
 
+/// Gas для callback on_withdraw_complete / on_treasury_withdraw_complete
+const GAS_FOR_WITHDRAW_CALLBACK: Gas = Gas::from_tgas(10);
+/// Gas для ft_transfer на контракт NEP-141 токена
+const GAS_FOR_FT_TRANSFER: Gas = Gas::from_tgas(10);
+/// ft_transfer требует ровно 1 yoctoNEAR (NEP-141)
+const ONE_YOCTO: NearToken = NearToken::from_yoctonear(1);
+
 // ── Ключи хранилища ─────────────────────────────────────────────
 
 #[derive(BorshStorageKey)]
@@ -40,10 +81,103 @@ enum StorageKey {
     MarketBets,
     UserBets,
     Balances,
+    PendingWithdrawals,
+    CollateralTokens,
+    FtBalances,
+    FtTreasury,
+    Positions,
+    BetLog,
+    UserMarkets,
+    ClaimedStakes,
+    StorageAccounts,
+    Moderators,
+    LockedBonds,
+    Proposals,
+    ScoreVotes,
+    ResolutionRequests,
+    /// Рынки в новом layout; под Markets остаются рынки до миграции
+    MarketsV2,
 }
 
 // ── Модели данных ────────────────────────────────────────────────
 
+/// Жизненный цикл рынка:
+/// Active → Closed → ResolutionPending → Resolved | Voided → Finalized
+///
+/// ResolutionPending → Closed — oracle не вернул финальный результат,
+/// разрешение можно запросить повторно.
+/// Active | Closed → Voided — отмена рынка владельцем или модератором
+/// либо истечение resolution_deadline (оно же аннулирует зависшие
+/// ResolutionPending, Proposed и Disputed).
+/// Closed → Proposed → (Disputed →) Resolved | Voided — оптимистичное
+/// разрешение рынков без ESPN.
+/// ResolutionPending → Proposed — результат oracle ждёт окончания
+/// периода оспаривания; Disputed → Closed — повторный запрос к oracle.
+/// ResolutionPending | Closed → Disputed — источники разошлись в счёте.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "snake_case")]
+pub enum MarketStatus {
+    /// Приём ставок
+    Active,
+    /// Ставки закрыты, ждём resolution_date
+    Closed,
+    /// Запрос к oracle отправлен, ждём callback
+    ResolutionPending,
+    /// Исход предложен с залогом, идёт окно для оспаривания
+    Proposed,
+    /// Предложение оспорено, ждём арбитража oracle
+    Disputed,
+    /// Победитель определён, выплаты открыты
+    Resolved,
+    /// Аннулирован, ставки возвращаются
+    Voided,
+    /// Все выплаты получены
+    Finalized,
+}
+
+impl MarketStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MarketStatus::Active => "active",
+            MarketStatus::Closed => "closed",
+            MarketStatus::ResolutionPending => "resolution_pending",
+            MarketStatus::Proposed => "proposed",
+            MarketStatus::Disputed => "disputed",
+            MarketStatus::Resolved => "resolved",
+            MarketStatus::Voided => "voided",
+            MarketStatus::Finalized => "finalized",
+        }
+    }
+
+    /// Допустимые переходы машины состояний
+    fn can_transition_to(self, next: MarketStatus) -> bool {
+        use MarketStatus::*;
+        matches!(
+            (self, next),
+            (Active, Closed)
+                | (Closed, ResolutionPending)
+                | (ResolutionPending, Closed)
+                | (ResolutionPending, Resolved)
+                | (ResolutionPending, Voided)
+                | (Active, Voided)
+                | (Closed, Voided)
+                | (Closed, Proposed)
+                | (ResolutionPending, Proposed)
+                | (ResolutionPending, Disputed)
+                | (Closed, Disputed)
+                | (Disputed, Closed)
+                | (Proposed, Disputed)
+                | (Proposed, Resolved)
+                | (Proposed, Voided)
+                | (Disputed, Resolved)
+                | (Disputed, Voided)
+                | (Resolved, Finalized)
+                | (Voided, Finalized)
+        )
+    }
+}
+
 #[derive(Clone)]
 #[near(serializers = [borsh, json])]
 #[serde(rename_all = "camelCase")]
@@ -56,17 +190,827 @@ pub struct Market {
     pub outcome_pools: Vec<U128>,
     pub total_pool: U128,
     pub category: String,
-    pub created_at: u64,       // наносекунды
-    pub bets_end_date: u64,    // наносекунды — дедлайн ставок
-    pub resolution_date: u64,  // наносекунды — когда можно разрешить
-    pub resolved_outcome: i32, // -1 = не разрешён, -2 = void
-    pub status: String,        // "active" | "closed" | "resolved" | "voided"
+    pub created_at: u64,               // наносекунды
+    pub bets_end_date: u64,            // наносекунды — дедлайн ставок
+    pub resolution_date: u64,          // наносекунды — когда можно разрешить
+    pub resolved_outcome: Option<u32>, // None — не разрешён, аннулирован или push
+    pub status: MarketStatus,
     pub total_bets: u32,
     // ESPN метаданные для OutLayer permissionless resolution
-    pub espn_event_id: String,
+    pub espn_event_id: String, // id матча; у outright по таблице — сезон ("2025")
     pub sport: String,
     pub league: String,
-    pub market_type: String, // "winner" | "over-under" | "both-score"
+    pub market_type: String, // "winner" | "over-under" | "both-score" | "spread" | ... — метка для UI (MarketKind::as_str)
+    // Комиссии (снимок на момент создания), берутся с проигравших пулов
+    pub protocol_fee_bps: u16,
+    pub creator_fee_bps: u16,
+    /// Сколько удержано комиссий при разрешении
+    pub fees_collected: U128,
+    /// NEP-141 токен залога; None — нативный NEAR
+    pub collateral_token: Option<AccountId>,
+    /// Залог создателя (NEAR) — возвращается после разрешения,
+    /// конфискуется в казну при отмене рынка модератором
+    pub creator_bond: U128,
+    /// Какие источники счёта принимаются и сколько должно совпасть
+    pub resolution_policy: ResolutionPolicy,
+    /// Если к этому моменту (нс) рынок не разрешён — его можно аннулировать
+    pub resolution_deadline: u64,
+    /// ESPN id для каждого исхода: team id или athlete id в индивидуальных
+    /// видах ("" — ничья / не команда);
+    /// пусто — рынок без привязки, сопоставление по именам
+    pub outcome_team_ids: Vec<String>,
+    /// Тип спортивного рынка с параметрами; None — рынок без ESPN
+    pub kind: Option<MarketKind>,
+    /// Итог по исходам после разрешения (пусто, пока не Resolved)
+    pub settlement: Vec<OutcomeSettlement>,
+    /// Какой счёт учитывается: основное время, с овертаймом или с серией
+    pub settlement_rule: SettlementRule,
+}
+
+/// Участник индивидуального события (теннис, MMA, гольф, гонки)
+#[derive(Clone, PartialEq, Eq)]
+#[near(serializers = [borsh, json])]
+pub struct CompetitorResult {
+    /// ESPN athlete id
+    pub id: String,
+    pub winner: bool,
+    /// Занятое место, 0 — неизвестно
+    pub position: u32,
+}
+
+/// Показатель игрока из ESPN boxscore
+#[derive(Clone, PartialEq, Eq)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerStat {
+    /// Worker отдаёт поля в snake_case
+    #[serde(alias = "athlete_id")]
+    pub athlete_id: String,
+    pub stat: String,
+    /// Значение ×10; None — игрок не играл или показателя нет
+    #[serde(alias = "value_x10")]
+    pub value_x10: Option<i64>,
+}
+
+/// Какой счёт учитывается при расчёте спортивного рынка
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "snake_case")]
+pub enum SettlementRule {
+    /// Только основное время (футбольные 1X2 — 90 минут)
+    Regulation,
+    /// С овертаймом / дополнительным временем, без серии
+    IncludingOvertime,
+    /// Итоговый результат с буллитами / пенальти (moneyline NBA, NHL)
+    IncludingShootout,
+}
+
+impl SettlementRule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SettlementRule::Regulation => "regulation",
+            SettlementRule::IncludingOvertime => "including_overtime",
+            SettlementRule::IncludingShootout => "including_shootout",
+        }
+    }
+
+    /// По умолчанию: футбол — основное время, остальное — итоговый результат
+    fn default_for(sport: &str) -> Self {
+        match sport {
+            "soccer" => SettlementRule::Regulation,
+            _ => SettlementRule::IncludingShootout,
+        }
+    }
+}
+
+/// Овертайм и серия по данным источника, счёт основного времени и серии
+#[derive(Clone, PartialEq, Eq)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "camelCase")]
+pub struct ExtraTime {
+    pub overtime: bool,
+    pub shootout: bool,
+    pub regulation_home_score: Option<i32>,
+    pub regulation_away_score: Option<i32>,
+    pub home_shootout_score: Option<i32>,
+    pub away_shootout_score: Option<i32>,
+}
+
+/// Итог исхода при разрешении, bps от ставки: выигрыш и возврат
+/// (остаток проигран). Победитель — win 100%, push — возврат 100%
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "camelCase")]
+pub struct OutcomeSettlement {
+    pub win_bps: u16,
+    pub push_bps: u16,
+}
+
+impl OutcomeSettlement {
+    const WIN: Self = Self {
+        win_bps: BPS_DENOMINATOR as u16,
+        push_bps: 0,
+    };
+    const PUSH: Self = Self {
+        win_bps: 0,
+        push_bps: BPS_DENOMINATOR as u16,
+    };
+    const LOSE: Self = Self {
+        win_bps: 0,
+        push_bps: 0,
+    };
+}
+
+/// Итог рынка по данным oracle
+#[derive(Debug, PartialEq)]
+enum Resolution {
+    /// Один выигравший исход
+    Winner(u32),
+    /// Push — ставки возвращаются всем, никто не проигрывает
+    Push,
+    /// Доли выигрыша и возврата по каждому исходу (четвертные линии)
+    Split(Vec<OutcomeSettlement>),
+    /// Результат не определён — рынок аннулируется
+    Void,
+}
+
+impl Resolution {
+    /// Итог по исходам; None — аннулирование
+    fn settlement(&self, outcomes: usize) -> Option<Vec<OutcomeSettlement>> {
+        match self {
+            Resolution::Winner(w) if (*w as usize) < outcomes => Some(
+                (0..outcomes as u32)
+                    .map(|o| {
+                        if o == *w {
+                            OutcomeSettlement::WIN
+                        } else {
+                            OutcomeSettlement::LOSE
+                        }
+                    })
+                    .collect(),
+            ),
+            Resolution::Push => Some(vec![OutcomeSettlement::PUSH; outcomes]),
+            Resolution::Split(s) if s.len() == outcomes => Some(s.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// Единственный победитель: исход с win 100% при проигрыше остальных
+fn single_winner(settlement: &[OutcomeSettlement]) -> Option<u32> {
+    let winner = settlement
+        .iter()
+        .position(|s| *s == OutcomeSettlement::WIN)?;
+    settlement
+        .iter()
+        .enumerate()
+        .all(|(i, s)| i == winner || *s == OutcomeSettlement::LOSE)
+        .then_some(winner as u32)
+}
+
+/// Тип спортивного рынка — по нему контракт определяет исход из счёта
+#[derive(Clone, PartialEq)]
+#[near(serializers = [borsh, json])]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum MarketKind {
+    /// Победитель: исходы — команды (+ ничья, если draw)
+    Winner { draw: bool },
+    /// Тотал: исход 0 — больше линии, 1 — меньше; линия ×10 (25 = 2.5)
+    OverUnder { line_x10: u32 },
+    /// Обе забьют: исход 0 — да, 1 — нет
+    BothScore,
+    /// Фора хозяев ×10 (-55 = -5.5): исход 0 — хозяева с форой, 1 — гости.
+    /// Целая фора — push при равенстве
+    Spread { handicap_x10: i32 },
+    /// Азиатская фора хозяев ×100 (-25 = -0.25): исход 0 — хозяева, 1 — гости.
+    /// Четвертная линия делит ставку пополам между соседними линиями
+    AsianHandicap { handicap_x100: i32 },
+    /// Точный счёт: исходы "2-1" (хозяева-гости) и необязательный "Any other"
+    CorrectScore,
+    /// Диапазон тотала: исходы-корзины "0-1", "2-3", "4+" без пересечений
+    Range,
+    /// Победитель по сумме периодов from..=to (1 — первый тайм/четверть/период);
+    /// исходы — как у Winner
+    PeriodWinner {
+        from_period: u8,
+        to_period: u8,
+        draw: bool,
+    },
+    /// Тотал по сумме периодов from..=to: исход 0 — больше, 1 — меньше
+    PeriodOverUnder {
+        from_period: u8,
+        to_period: u8,
+        line_x10: u32,
+    },
+    /// Самый результативный период: исход i — период i + 1
+    /// (овертаймы не считаются); равенство лучших — push
+    HighestScoringPeriod,
+    /// Кто победит в бою / матче (теннис, MMA): исходы — спортсмены по ESPN
+    /// athlete id в outcome_team_ids (+ ничья с "", если draw)
+    HeadToHead { draw: bool },
+    /// Финиш в топ-N (гольф, гонки): исходы — спортсмены по athlete id,
+    /// выигрывают все, кто занял место не ниже N-го
+    TopN { n: u32 },
+    /// Показатель игрока из boxscore против линии ×10: исход 0 — больше,
+    /// 1 — меньше; stat — ключ ESPN ("points") или метка ("PTS")
+    PlayerProp {
+        athlete_id: String,
+        stat: String,
+        line_x10: u32,
+    },
+    /// Победитель турнира / сезона: исходы — команды по ESPN team id
+    /// ("" — любая другая команда), победитель — из режима worker
+    Outright { source: OutrightSource },
+}
+
+/// Откуда worker берёт победителя outright-рынка
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "snake_case")]
+pub enum OutrightSource {
+    /// Финальный матч / серия (espn_event_id): победитель по флагу ESPN
+    Championship,
+    /// Итоговая таблица сезона (espn_event_id — сезон): первое место.
+    /// Завершённость сезона worker не видит — resolution_date ставится
+    /// после последнего тура
+    Standings,
+}
+
+impl OutrightSource {
+    /// Режим WASM Worker
+    fn worker_mode(&self) -> &'static str {
+        match self {
+            OutrightSource::Championship => "championship",
+            OutrightSource::Standings => "standings",
+        }
+    }
+}
+
+impl MarketKind {
+    /// Метка для UI и старых клиентов (поле market_type)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MarketKind::Winner { .. } => "winner",
+            MarketKind::OverUnder { .. } => "over-under",
+            MarketKind::BothScore => "both-score",
+            MarketKind::Spread { .. } => "spread",
+            MarketKind::AsianHandicap { .. } => "asian-handicap",
+            MarketKind::CorrectScore => "correct-score",
+            MarketKind::Range => "range",
+            MarketKind::PeriodWinner { .. } => "period-winner",
+            MarketKind::PeriodOverUnder { .. } => "period-over-under",
+            MarketKind::HighestScoringPeriod => "highest-scoring-period",
+            MarketKind::HeadToHead { .. } => "head-to-head",
+            MarketKind::TopN { .. } => "top-n",
+            MarketKind::PlayerProp { .. } => "player-prop",
+            MarketKind::Outright { .. } => "outright",
+        }
+    }
+
+    /// Итог без счёта матча: по списку участников или id победителя
+    fn is_scoreless(&self) -> bool {
+        matches!(
+            self,
+            MarketKind::HeadToHead { .. } | MarketKind::TopN { .. } | MarketKind::Outright { .. }
+        )
+    }
+
+    /// Итог по счёту матча — к нему применяется правило расчёта рынка
+    fn settles_on_score(&self) -> bool {
+        matches!(
+            self,
+            MarketKind::Winner { .. }
+                | MarketKind::OverUnder { .. }
+                | MarketKind::BothScore
+                | MarketKind::Spread { .. }
+                | MarketKind::AsianHandicap { .. }
+                | MarketKind::CorrectScore
+                | MarketKind::Range
+        )
+    }
+
+    /// Тип из строкового market_type старых клиентов.
+    /// Линия тотала ("Over 2.5") и фора ("Lakers -5.5") берутся из названия
+    /// первого исхода — только при создании. Фора — только последнее слово
+    /// со знаком: цифры в названии команды ("Schalke 04") линией не считаются
+    fn from_legacy(market_type: &str, outcomes: &[String]) -> Self {
+        match market_type {
+            "winner" => MarketKind::Winner {
+                draw: outcomes.len() == 3,
+            },
+            "over-under" => {
+                let line = outcomes
+                    .first()
+                    .and_then(|o| {
+                        o.split_whitespace()
+                            .find_map(|word| word.parse::<f64>().ok())
+                    })
+                    .expect("Укажите линию тотала в kind или в названии исхода");
+                assert!(line > 0.0, "Линия тотала должна быть положительной");
+                MarketKind::OverUnder {
+                    line_x10: (line * 10.0).round() as u32,
+                }
+            }
+            "both-score" => MarketKind::BothScore,
+            "spread" => {
+                let handicap = outcomes.first().and_then(|o| parse_signed_line(o)).expect(
+                    "Укажите фору в kind или в конце названия исхода со знаком (\"Lakers -5.5\")",
+                );
+                MarketKind::Spread {
+                    handicap_x10: (handicap * 10.0).round() as i32,
+                }
+            }
+            "asian-handicap" => {
+                let handicap = outcomes.first().and_then(|o| parse_signed_line(o)).expect(
+                    "Укажите фору в kind или в конце названия исхода со знаком (\"Köln -0.25\")",
+                );
+                MarketKind::AsianHandicap {
+                    handicap_x100: (handicap * 100.0).round() as i32,
+                }
+            }
+            "correct-score" => MarketKind::CorrectScore,
+            "range" => MarketKind::Range,
+            _ => panic!("Неизвестный тип рынка: {}", market_type),
+        }
+    }
+
+    /// Тип рынка из состояния до миграции — так, как его разрешал старый
+    /// контракт: линия тотала из первого исхода (по умолчанию 2.5), тотал
+    /// "больше" строго выше линии, поэтому целая линия N становится N.5
+    fn from_baseline(market_type: &str, outcomes: &[String]) -> Option<Self> {
+        match market_type {
+            "winner" => Some(MarketKind::Winner {
+                draw: outcomes.len() == 3,
+            }),
+            "over-under" => {
+                let line = outcomes
+                    .first()
+                    .and_then(|o| {
+                        o.split_whitespace()
+                            .find_map(|word| word.parse::<f64>().ok())
+                    })
+                    .filter(|line| *line >= 0.0)
+                    .unwrap_or(2.5);
+                Some(MarketKind::OverUnder {
+                    line_x10: line.floor() as u32 * 10 + 5,
+                })
+            }
+            "both-score" => Some(MarketKind::BothScore),
+            _ => None,
+        }
+    }
+
+    /// Проверка параметров против количества и названий исходов
+    fn validate(&self, outcomes: &[String], team_ids: &[String]) {
+        match self {
+            MarketKind::Winner { draw } => {
+                let expected = if *draw { 3 } else { 2 };
+                assert!(
+                    outcomes.len() == expected,
+                    "Winner{}: нужно {} исхода",
+                    if *draw { " с ничьёй" } else { "" },
+                    expected
+                );
+                if *draw {
+                    assert!(
+                        draw_index(outcomes, team_ids).is_some(),
+                        "Winner с ничьёй: нужен исход \"Draw\" / \"Ничья\" (или исход без team id)"
+                    );
+                }
+            }
+            MarketKind::OverUnder { line_x10 } => {
+                assert!(
+                    outcomes.len() == 2,
+                    "Тотал: нужно 2 исхода — больше и меньше"
+                );
+                assert!(*line_x10 > 0, "Линия тотала должна быть положительной");
+                assert!(
+                    line_x10 % 10 == 0 || line_x10 % 10 == 5,
+                    "Линия тотала: целая (push при равенстве) или половинная, например 3 или 2.5"
+                );
+                assert!(
+                    label_starts_with(&outcomes[0], &["over", "больше"])
+                        && label_starts_with(&outcomes[1], &["under", "меньше"]),
+                    "Тотал: исход 0 — \"Over\"/\"Больше\", исход 1 — \"Under\"/\"Меньше\""
+                );
+            }
+            MarketKind::BothScore => {
+                assert!(outcomes.len() == 2, "Обе забьют: нужно 2 исхода — да и нет");
+                assert!(
+                    label_starts_with(&outcomes[0], &["yes", "да"])
+                        && label_starts_with(&outcomes[1], &["no", "нет"]),
+                    "Обе забьют: исход 0 — \"Yes\"/\"Да\", исход 1 — \"No\"/\"Нет\""
+                );
+            }
+            MarketKind::Spread { handicap_x10 } => {
+                assert!(
+                    outcomes.len() == 2,
+                    "Фора: нужно 2 исхода — хозяева и гости"
+                );
+                assert!(
+                    handicap_x10 % 5 == 0,
+                    "Фора: целая (push при равенстве) или половинная, например -5 или -5.5"
+                );
+                assert!(
+                    team_ids.is_empty() || team_ids.iter().all(|t| !t.is_empty()),
+                    "Фора: у обоих исходов должен быть team id"
+                );
+            }
+            MarketKind::AsianHandicap { handicap_x100 } => {
+                assert!(
+                    outcomes.len() == 2,
+                    "Азиатская фора: нужно 2 исхода — хозяева и гости"
+                );
+                assert!(
+                    handicap_x100 % 25 == 0,
+                    "Азиатская фора: шаг линии 0.25, например -0.25, -0.5 или -0.75"
+                );
+                assert!(
+                    team_ids.is_empty() || team_ids.iter().all(|t| !t.is_empty()),
+                    "Азиатская фора: у обоих исходов должен быть team id"
+                );
+            }
+            MarketKind::CorrectScore => {
+                let mut scores = Vec::new();
+                let mut others = 0;
+                for label in outcomes {
+                    if is_other_label(label) {
+                        others += 1;
+                        continue;
+                    }
+                    let score = parse_scoreline(label).unwrap_or_else(|| {
+                        panic!(
+                            "Точный счёт: исход \"{}\" — нужен счёт \"2-1\" или \"Any other\"",
+                            label
+                        )
+                    });
+                    assert!(
+                        !scores.contains(&score),
+                        "Точный счёт: счёт \"{}\" повторяется",
+                        label
+                    );
+                    scores.push(score);
+                }
+                assert!(others <= 1, "Точный счёт: исход \"Any other\" только один");
+                assert!(
+                    !scores.is_empty(),
+                    "Точный счёт: нужен хотя бы один счёт"
+                );
+            }
+            MarketKind::Range => {
+                let buckets: Vec<(u32, Option<u32>)> = outcomes
+                    .iter()
+                    .map(|label| {
+                        parse_bucket(label).unwrap_or_else(|| {
+                            panic!(
+                                "Диапазон: исход \"{}\" — нужна корзина \"0-1\", \"2\" или \"4+\"",
+                                label
+                            )
+                        })
+                    })
+                    .collect();
+                for (i, a) in buckets.iter().enumerate() {
+                    for b in &buckets[i + 1..] {
+                        let overlap = a.0 <= b.1.unwrap_or(u32::MAX)
+                            && b.0 <= a.1.unwrap_or(u32::MAX);
+                        assert!(!overlap, "Диапазон: корзины пересекаются");
+                    }
+                }
+            }
+            MarketKind::PeriodWinner {
+                from_period,
+                to_period,
+                draw,
+            } => {
+                validate_periods(*from_period, *to_period);
+                MarketKind::Winner { draw: *draw }.validate(outcomes, team_ids);
+            }
+            MarketKind::PeriodOverUnder {
+                from_period,
+                to_period,
+                line_x10,
+            } => {
+                validate_periods(*from_period, *to_period);
+                MarketKind::OverUnder {
+                    line_x10: *line_x10,
+                }
+                .validate(outcomes, team_ids);
+            }
+            MarketKind::HighestScoringPeriod => {
+                assert!(
+                    outcomes.len() >= 2,
+                    "Самый результативный период: нужен исход на каждый период"
+                );
+            }
+            MarketKind::HeadToHead { draw } => {
+                assert!(
+                    !team_ids.is_empty(),
+                    "Противостояние: нужны ESPN athlete id исходов (outcome_team_ids)"
+                );
+                MarketKind::Winner { draw: *draw }.validate(outcomes, team_ids);
+            }
+            MarketKind::TopN { n } => {
+                assert!(*n >= 1, "Топ-N: N от 1");
+                assert!(
+                    !team_ids.is_empty() && team_ids.iter().all(|t| !t.is_empty()),
+                    "Топ-N: у каждого исхода должен быть ESPN athlete id"
+                );
+            }
+            MarketKind::PlayerProp {
+                athlete_id,
+                stat,
+                line_x10,
+            } => {
+                assert!(
+                    !athlete_id.is_empty() && athlete_id.len() <= 32,
+                    "Показатель игрока: нужен ESPN athlete id"
+                );
+                assert!(
+                    !stat.is_empty() && stat.len() <= 32,
+                    "Показатель игрока: нужен ключ статистики, например \"points\""
+                );
+                MarketKind::OverUnder {
+                    line_x10: *line_x10,
+                }
+                .validate(outcomes, team_ids);
+            }
+            MarketKind::Outright { .. } => {
+                assert!(
+                    !team_ids.is_empty(),
+                    "Outright: нужны ESPN team id исходов (outcome_team_ids)"
+                );
+                assert!(
+                    team_ids.iter().filter(|t| t.is_empty()).count() <= 1,
+                    "Outright: исход \"любая другая команда\" (\"\") только один"
+                );
+            }
+        }
+    }
+}
+
+/// Источник счёта спортивного рынка
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "snake_case")]
+pub enum OracleSource {
+    /// WASM Worker в OutLayer TEE
+    Outlayer,
+    /// MPC-TLS аттестация ответа ESPN
+    TlsOracle,
+    /// Назначенный аккаунт oracle
+    DesignatedOracle,
+}
+
+impl OracleSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OracleSource::Outlayer => "outlayer",
+            OracleSource::TlsOracle => "tls_oracle",
+            OracleSource::DesignatedOracle => "designated_oracle",
+        }
+    }
+}
+
+/// Политика разрешения: `required` из `sources` должны сообщить одинаковый счёт
+#[derive(Clone)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "camelCase")]
+pub struct ResolutionPolicy {
+    pub sources: Vec<OracleSource>,
+    pub required: u8,
+}
+
+impl Default for ResolutionPolicy {
+    /// Любой источник, достаточно одного голоса
+    fn default() -> Self {
+        Self {
+            sources: vec![
+                OracleSource::Outlayer,
+                OracleSource::TlsOracle,
+                OracleSource::DesignatedOracle,
+            ],
+            required: 1,
+        }
+    }
+}
+
+/// Голос источника за итоговый счёт матча
+#[derive(Clone)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreVote {
+    pub source: OracleSource,
+    pub home_team: String,
+    pub away_team: String,
+    /// ESPN team id; пустая строка — источник не сообщил
+    pub home_team_id: String,
+    pub away_team_id: String,
+    pub home_score: i32,
+    pub away_score: i32,
+    pub event_status: String,
+    pub voted_at: u64, // наносекунды
+    /// Очки по периодам; пусто — источник не сообщил
+    pub home_periods: Vec<i32>,
+    pub away_periods: Vec<i32>,
+    /// Овертайм / серия; None — источник не сообщил
+    pub extra_time: Option<ExtraTime>,
+    /// Участники индивидуального события; пусто — не сообщил или командный
+    pub competitors: Vec<CompetitorResult>,
+    /// Показатели игроков для player-prop; пусто — не запрашивались
+    pub player_stats: Vec<PlayerStat>,
+    /// ESPN id победителя outright-рынка; пусто — не сообщил
+    pub winner_id: String,
+}
+
+/// Данные голоса, по которым рассчитывается рынок. Голоса совпадают,
+/// только если ключи равны целиком: отношение транзитивно, и голос
+/// без какого-то поля не склеивает противоречащие друг другу голоса
+#[derive(PartialEq)]
+struct VoteKey {
+    home_score: i32,
+    away_score: i32,
+    event_status: String,
+    team_ids: Option<(String, String)>,
+    home_periods: Vec<i32>,
+    away_periods: Vec<i32>,
+    /// Счёт по правилу расчёта рынка (с учётом доп. времени)
+    settled_score: Option<(i32, i32)>,
+    competitors: Vec<CompetitorResult>,
+    player_stat: Option<PlayerStat>,
+    winner_id: String,
+}
+
+impl ScoreVote {
+    /// Ключ голоса по полям, которые нужны рынку
+    fn settlement_key(&self, market: &Market) -> VoteKey {
+        let periods = market.periods_needed();
+        let mut competitors = match market.kind {
+            Some(MarketKind::HeadToHead { .. } | MarketKind::TopN { .. }) => {
+                self.competitors.clone()
+            }
+            _ => Vec::new(),
+        };
+        competitors.sort_by(|a, b| a.id.cmp(&b.id));
+        VoteKey {
+            home_score: self.home_score,
+            away_score: self.away_score,
+            event_status: self.event_status.clone(),
+            team_ids: market
+                .settles_by_team_id()
+                .then(|| (self.home_team_id.clone(), self.away_team_id.clone())),
+            home_periods: self.home_periods.iter().take(periods).copied().collect(),
+            away_periods: self.away_periods.iter().take(periods).copied().collect(),
+            settled_score: market
+                .kind
+                .as_ref()
+                .filter(|kind| kind.settles_on_score())
+                .and_then(|_| settled_score(market.settlement_rule, &self.to_espn_result()).ok())
+                .map(|(scored, _)| (scored.home_score, scored.away_score)),
+            competitors,
+            player_stat: match &market.kind {
+                Some(MarketKind::PlayerProp {
+                    athlete_id, stat, ..
+                }) => self
+                    .player_stats
+                    .iter()
+                    .find(|s| &s.athlete_id == athlete_id && s.stat.eq_ignore_ascii_case(stat))
+                    .cloned(),
+                _ => None,
+            },
+            winner_id: match market.kind {
+                Some(MarketKind::Outright { .. }) => self.winner_id.clone(),
+                _ => String::new(),
+            },
+        }
+    }
+
+    fn to_espn_result(&self) -> EspnResult {
+        let mut result = EspnResult {
+            home_team: self.home_team.clone(),
+            away_team: self.away_team.clone(),
+            home_team_id: self.home_team_id.clone(),
+            away_team_id: self.away_team_id.clone(),
+            home_score: self.home_score,
+            away_score: self.away_score,
+            event_status: self.event_status.clone(),
+            error: String::new(),
+            start_time: 0,
+            home_periods: self.home_periods.clone(),
+            away_periods: self.away_periods.clone(),
+            overtime: None,
+            shootout: None,
+            regulation_home_score: None,
+            regulation_away_score: None,
+            home_shootout_score: None,
+            away_shootout_score: None,
+            competitors: self.competitors.clone(),
+            player_stats: self.player_stats.clone(),
+            winner_id: self.winner_id.clone(),
+        };
+        if let Some(extra) = &self.extra_time {
+            result.set_extra_time(extra);
+        }
+        result
+    }
+}
+
+impl Market {
+    /// Исходы сопоставляются с командами по ESPN team id
+    fn settles_by_team_id(&self) -> bool {
+        !self.outcome_team_ids.is_empty()
+            && matches!(
+                self.kind,
+                Some(
+                    MarketKind::Winner { .. }
+                        | MarketKind::PeriodWinner { .. }
+                        | MarketKind::Spread { .. }
+                        | MarketKind::AsianHandicap { .. }
+                )
+            )
+    }
+
+    /// Сколько первых периодов нужно для расчёта рынка
+    fn periods_needed(&self) -> usize {
+        match &self.kind {
+            Some(MarketKind::PeriodWinner { to_period, .. })
+            | Some(MarketKind::PeriodOverUnder { to_period, .. }) => *to_period as usize,
+            Some(MarketKind::HighestScoringPeriod) => self.outcomes.len(),
+            _ => 0,
+        }
+    }
+
+    /// Переход статуса с проверкой по машине состояний
+    fn transition(&mut self, next: MarketStatus) {
+        assert!(
+            self.status.can_transition_to(next),
+            "Недопустимый переход статуса рынка #{}: {} → {}",
+            self.id,
+            self.status.as_str(),
+            next.as_str()
+        );
+        self.status = next;
+    }
+
+    /// Закрывает приём ставок, если дедлайн прошёл
+    fn close_if_expired(&mut self, now: u64) {
+        if self.status == MarketStatus::Active && now >= self.bets_end_date {
+            self.transition(MarketStatus::Closed);
+        }
+    }
+
+    /// Комиссии протокола и создателя с суммы проигравших пулов
+    fn fees_on(&self, losing_pool: u128) -> (u128, u128) {
+        (
+            safe_mul_div(losing_pool, self.protocol_fee_bps as u128, BPS_DENOMINATOR),
+            safe_mul_div(losing_pool, self.creator_fee_bps as u128, BPS_DENOMINATOR),
+        )
+    }
+
+    /// Выплата за ставку `stake` на исход с пулом `outcome_pool`
+    /// при общем пуле `total_pool` — за вычетом комиссий
+    fn net_payout(&self, stake: u128, outcome_pool: u128, total_pool: u128) -> u128 {
+        if outcome_pool == 0 {
+            return 0;
+        }
+        let (protocol_fee, creator_fee) = self.fees_on(total_pool - outcome_pool);
+        safe_mul_div(stake, total_pool - protocol_fee - creator_fee, outcome_pool)
+    }
+
+    /// Ставки на исход участвуют в выплате (выигрыш или возврат)
+    fn is_payable(&self, outcome: u32) -> bool {
+        match self.status {
+            MarketStatus::Voided => true,
+            MarketStatus::Resolved => self
+                .settlement
+                .get(outcome as usize)
+                .is_some_and(|s| s.win_bps > 0 || s.push_bps > 0),
+            _ => false,
+        }
+    }
+
+    /// Сумма ставок, подлежащих выплате: весь пул при void,
+    /// пулы выигравших и возвращаемых исходов иначе
+    fn payable_stake(&self) -> u128 {
+        (0..self.outcomes.len())
+            .filter(|&o| self.is_payable(o as u32))
+            .map(|o| u128::from(self.outcome_pools[o]))
+            .sum()
+    }
+
+    /// Пулы по итогу разрешения: (выигравшие доли, возвращаемые, проигранные)
+    fn settled_pools(&self) -> (u128, u128, u128) {
+        let (mut win, mut push) = (0u128, 0u128);
+        for (pool, s) in self.outcome_pools.iter().zip(&self.settlement) {
+            win += safe_mul_div(pool.0, s.win_bps as u128, BPS_DENOMINATOR);
+            push += safe_mul_div(pool.0, s.push_bps as u128, BPS_DENOMINATOR);
+        }
+        let total: u128 = self.total_pool.into();
+        (win, push, total - win - push)
+    }
 }
 
 #[derive(Clone)]
@@ -81,17 +1025,188 @@ pub struct Bet {
     pub claimed: bool,
 }
 
+/// Агрегированная позиция пользователя на исходе рынка.
+/// Ключ — (market_id, аккаунт, исход), история ставок — в bet_log.
+#[derive(Clone)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "camelCase")]
+pub struct Position {
+    pub stake: U128,
+    pub claimed: bool,
+    pub last_bet_at: u64, // наносекунды
+}
+
+/// Storage-депозит аккаунта (NEP-145): внесено и занято байт
+#[derive(Clone)]
+#[near(serializers = [borsh])]
+pub struct StorageAccount {
+    pub deposit: u128,
+    pub used_bytes: u64,
+}
+
+/// NEP-145 StorageBalance
+#[near(serializers = [json])]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+/// NEP-145 StorageBalanceBounds
+#[near(serializers = [json])]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+/// Настройки комиссий платформы
+#[derive(Clone)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "camelCase")]
+pub struct FeeConfig {
+    /// Комиссия протокола в казну, bps от проигравших пулов
+    pub protocol_fee_bps: u16,
+    /// Максимальная комиссия, которую создатель может назначить рынку
+    pub max_creator_fee_bps: u16,
+}
+
+/// Параметры оптимистичного разрешения
+#[derive(Clone)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "camelCase")]
+pub struct ResolutionConfig {
+    /// Залог за предложение исхода (и за спор), yoctoNEAR
+    pub proposal_bond: U128,
+    /// Окно для оспаривания, наносекунды
+    pub dispute_window: u64,
+    /// Период оспаривания результата OutLayer / TLS Oracle, наносекунды
+    pub challenge_window: u64,
+    /// Награда инициатору успешного разрешения, bps от комиссии протокола
+    pub resolver_reward_bps: u16,
+}
+
+/// Предварительный исход рынка: предложение с залогом (рынки без ESPN)
+/// или результат OutLayer / TLS Oracle на период оспаривания
+#[derive(Clone)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "camelCase")]
+pub struct Proposal {
+    /// Автор предложения или инициатор запроса к oracle
+    pub proposer: AccountId,
+    /// Единственный выигравший исход (для UI); None — push, частичный итог или void
+    pub outcome: Option<u32>,
+    /// Итог по исходам; None — предложено аннулировать рынок
+    pub settlement: Option<Vec<OutcomeSettlement>>,
+    pub reasoning: String,
+    pub bond: U128,
+    pub proposed_at: u64,      // наносекунды
+    pub dispute_deadline: u64, // наносекунды
+    pub disputer: Option<AccountId>,
+    pub dispute_bond: U128,
+}
+
+/// Платный запрос разрешения к OutLayer. Пока result не заполнен
+/// и не прошло RESOLUTION_LOCK_BLOCKS — блокирует повторные запросы
+#[derive(Clone)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "camelCase")]
+pub struct ResolutionRequest {
+    /// Порядковый номер запроса по рынку
+    pub seq: u32,
+    pub requester: AccountId,
+    pub source: OracleSource,
+    /// Сколько передано в OutLayer
+    pub deposit: U128,
+    /// Сколько зачислено обратно инициатору во внутренний баланс
+    pub refunded: U128,
+    pub block_height: u64,
+    pub requested_at: u64, // наносекунды
+    /// Итог callback; None — запрос в полёте
+    pub result: Option<String>,
+}
+
+/// Незавершённый вывод — блокирует повторный withdraw до callback
+#[derive(Clone)]
+#[near(serializers = [borsh, json])]
+#[serde(rename_all = "camelCase")]
+pub struct PendingWithdrawal {
+    pub amount: U128,
+    /// NEP-141 токен; None — нативный NEAR
+    pub token_id: Option<AccountId>,
+    pub requested_at: u64, // наносекунды
+}
+
 /// Сырые данные ESPN из TEE Worker (stdout)
 /// Worker возвращает только счёт и имена — без winning_outcome
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(crate = "serde")]
 struct EspnResult {
     home_team: String,
     away_team: String,
+    /// ESPN team id — по ним исходы сопоставляются с командами
+    #[serde(default)]
+    home_team_id: String,
+    #[serde(default)]
+    away_team_id: String,
     home_score: i32,
     away_score: i32,
-    event_status: String, // "final" | "pre" | "in" | "error"
+    // "final" | "pre" | "in" | "postponed" | "canceled" | "abandoned" | "suspended" | "error"
+    event_status: String,
     error: String,
+    /// Начало матча (unix, секунды) — новая дата для перенесённых; 0 — неизвестно
+    #[serde(default)]
+    start_time: u64,
+    /// Очки по периодам из ESPN linescores; пусто — источник не сообщил
+    #[serde(default)]
+    home_periods: Vec<i32>,
+    #[serde(default)]
+    away_periods: Vec<i32>,
+    /// Овертайм / серия; null — источник не сообщает (TLS)
+    #[serde(default)]
+    overtime: Option<bool>,
+    #[serde(default)]
+    shootout: Option<bool>,
+    /// Счёт основного времени; null — неизвестен
+    #[serde(default)]
+    regulation_home_score: Option<i32>,
+    #[serde(default)]
+    regulation_away_score: Option<i32>,
+    /// Счёт серии пенальти (футбол)
+    #[serde(default)]
+    home_shootout_score: Option<i32>,
+    #[serde(default)]
+    away_shootout_score: Option<i32>,
+    /// Участники индивидуального события (без home/away)
+    #[serde(default)]
+    competitors: Vec<CompetitorResult>,
+    /// Запрошенные показатели игроков
+    #[serde(default)]
+    player_stats: Vec<PlayerStat>,
+    /// Outright-режимы: ESPN id победителя финала / чемпиона таблицы
+    #[serde(default)]
+    winner_id: String,
+}
+
+impl EspnResult {
+    /// Данные о доп. времени; None — источник их не сообщил
+    fn extra_time(&self) -> Option<ExtraTime> {
+        Some(ExtraTime {
+            overtime: self.overtime?,
+            shootout: self.shootout.unwrap_or(false),
+            regulation_home_score: self.regulation_home_score,
+            regulation_away_score: self.regulation_away_score,
+            home_shootout_score: self.home_shootout_score,
+            away_shootout_score: self.away_shootout_score,
+        })
+    }
+
+    fn set_extra_time(&mut self, extra: &ExtraTime) {
+        self.overtime = Some(extra.overtime);
+        self.shootout = Some(extra.shootout);
+        self.regulation_home_score = extra.regulation_home_score;
+        self.regulation_away_score = extra.regulation_away_score;
+        self.home_shootout_score = extra.home_shootout_score;
+        self.away_shootout_score = extra.away_shootout_score;
+    }
 }
 
 /// Компактные данные ESPN из TLS Oracle response_data
@@ -111,6 +1226,32 @@ struct TlsEspnData {
     st: String,
     /// ESPN event ID
     eid: String,
+    /// Home team ESPN id (если relayer его передаёт)
+    #[serde(default)]
+    hid: String,
+    /// Away team ESPN id
+    #[serde(default)]
+    aid: String,
+    /// Очки по периодам (linescores)
+    #[serde(default)]
+    hp: Vec<i32>,
+    #[serde(default)]
+    ap: Vec<i32>,
+    /// Овертайм / серия; null — backend их не сообщил
+    #[serde(default)]
+    ot: Option<bool>,
+    #[serde(default)]
+    so: Option<bool>,
+    /// Счёт основного времени
+    #[serde(default)]
+    rhs: Option<i32>,
+    #[serde(default)]
+    ras: Option<i32>,
+    /// Счёт серии пенальти
+    #[serde(default)]
+    hss: Option<i32>,
+    #[serde(default)]
+    ass: Option<i32>,
 }
 
 /// Аттестация из TLS Oracle контракта (формат get_attestation)
@@ -155,6 +1296,23 @@ struct WorkerInput {
     espn_event_id: String,
     sport: String,
     league: String,
+    /// Показатели игроков для player-prop рынка
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    player_stats: Vec<PlayerStatRequest>,
+    /// Спортсмены исходов индивидуального события — в вывод worker
+    /// попадают только они, чтобы протокол влез в лимит stdout
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    athlete_ids: Vec<String>,
+    /// Режим outright-рынка: "championship" | "standings"; нет — матч
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "serde")]
+struct PlayerStatRequest {
+    athlete_id: String,
+    stat: String,
 }
 
 /// Аргументы для callback on_resolution_result
@@ -162,6 +1320,8 @@ struct WorkerInput {
 #[serde(crate = "serde")]
 struct ResolutionCallbackArgs {
     market_id: u64,
+    requester: AccountId,
+    request_seq: u32,
 }
 
 // ── Контракт ─────────────────────────────────────────────────────
@@ -171,11 +1331,26 @@ pub struct NearCast {
     owner: AccountId,
     oracle: AccountId,
     markets: IterableMap<u64, Market>,
-    market_bets: LookupMap<u64, Vec<Bet>>,
-    user_bets: LookupMap<AccountId, Vec<Bet>>,
+    /// Рынки в старом layout — переносятся migrate_markets
+    legacy_markets: IterableMap<u64, OldMarket>,
+    /// Ставки до перехода на позиции — переносятся migrate_market_bets
+    legacy_market_bets: LookupMap<u64, Vec<Bet>>,
+    legacy_user_bets: LookupMap<AccountId, Vec<Bet>>,
     balances: LookupMap<AccountId, u128>,
+    /// Выводы в процессе (transfer отправлен, callback ещё не пришёл)
+    pending_withdrawals: LookupMap<AccountId, PendingWithdrawal>,
     market_count: u64,
     total_volume: u128,
+    /// Комиссии протокола и рыночных создателей
+    fee_config: FeeConfig,
+    /// Казна протокола (накопленные комиссии), yoctoNEAR
+    treasury: u128,
+    /// Разрешённые NEP-141 токены залога → минимальная ставка
+    collateral_tokens: IterableMap<AccountId, U128>,
+    /// Балансы в NEP-141 токенах: (аккаунт, токен) → сумма
+    ft_balances: LookupMap<(AccountId, AccountId), u128>,
+    /// Казна протокола в NEP-141 токенах
+    ft_treasury: LookupMap<AccountId, u128>,
     /// GitHub репозиторий с WASM Worker для OutLayer
     outlayer_source_repo: String,
     /// Commit/branch для OutLayer source
@@ -186,6 +1361,142 @@ pub struct NearCast {
     reclaim_contract: AccountId,
     /// TLS Oracle контракт для альтернативного разрешения рынков
     tls_oracle_contract: AccountId,
+    /// Позиции: (market_id, аккаунт, исход) → агрегированная ставка
+    positions: LookupMap<(u64, AccountId, u32), Position>,
+    /// Журнал ставок рынка для истории: (market_id, порядковый номер) → ставка
+    bet_log: LookupMap<(u64, u32), Bet>,
+    /// Рынки, на которых у аккаунта есть позиции
+    user_markets: LookupMap<AccountId, Vec<u64>>,
+    /// Сумма ставок, по которым уже получены выплаты, по рынкам
+    claimed_stakes: LookupMap<u64, u128>,
+    /// NEP-145: storage-депозиты ставящих и создателей рынков
+    storage_accounts: LookupMap<AccountId, StorageAccount>,
+    /// Залог за создание рынка, yoctoNEAR
+    creation_bond: u128,
+    /// Модераторы — могут отменять рынки как невалидные
+    moderators: IterableSet<AccountId>,
+    /// Залоги создателей в ещё не разрешённых рынках
+    locked_bonds: LookupMap<AccountId, u128>,
+    /// Параметры оптимистичного разрешения
+    resolution_config: ResolutionConfig,
+    /// Предложенные исходы рынков без ESPN
+    proposals: LookupMap<u64, Proposal>,
+    /// Голоса источников за счёт спортивных рынков
+    score_votes: LookupMap<u64, Vec<ScoreVote>>,
+    /// Последние запросы разрешения по рынкам (блокировка + история)
+    resolution_requests: LookupMap<u64, Vec<ResolutionRequest>>,
+}
+
+/// Borsh-layout рынка до миграции: статус строкой,
+/// resolved_outcome: -1 — не разрешён, -2 — аннулирован
+#[near(serializers = [borsh])]
+struct OldMarket {
+    id: u64,
+    creator: AccountId,
+    question: String,
+    description: String,
+    outcomes: Vec<String>,
+    outcome_pools: Vec<U128>,
+    total_pool: U128,
+    category: String,
+    created_at: u64,
+    bets_end_date: u64,
+    resolution_date: u64,
+    resolved_outcome: i32,
+    status: String,
+    total_bets: u32,
+    espn_event_id: String,
+    sport: String,
+    league: String,
+    market_type: String,
+}
+
+impl OldMarket {
+    /// Рынок в новом layout: статус — enum, итог — settlement,
+    /// тип спортивного рынка восстанавливается из market_type.
+    /// Комиссий и залога создателя у старых рынков нет
+    fn into_market(self) -> Market {
+        let status = match self.status.as_str() {
+            "active" => MarketStatus::Active,
+            "closed" => MarketStatus::Closed,
+            "resolved" => MarketStatus::Resolved,
+            "voided" => MarketStatus::Voided,
+            other => panic!("Рынок #{}: неизвестный статус \"{}\"", self.id, other),
+        };
+        let resolved_outcome = u32::try_from(self.resolved_outcome)
+            .ok()
+            .filter(|o| (*o as usize) < self.outcomes.len());
+        let settlement = match (status, resolved_outcome) {
+            (MarketStatus::Resolved, Some(winner)) => Resolution::Winner(winner)
+                .settlement(self.outcomes.len())
+                .unwrap_or_default(),
+            (MarketStatus::Resolved, None) => {
+                panic!("Рынок #{}: разрешён без исхода", self.id)
+            }
+            _ => Vec::new(),
+        };
+        let kind = if self.espn_event_id.is_empty() {
+            None
+        } else {
+            MarketKind::from_baseline(&self.market_type, &self.outcomes)
+        };
+        Market {
+            id: self.id,
+            creator: self.creator,
+            question: self.question,
+            description: self.description,
+            outcome_pools: self.outcome_pools,
+            total_pool: self.total_pool,
+            category: self.category,
+            created_at: self.created_at,
+            bets_end_date: self.bets_end_date,
+            resolution_date: self.resolution_date,
+            resolved_outcome,
+            status,
+            total_bets: self.total_bets,
+            market_type: kind
+                .as_ref()
+                .map_or(self.market_type, |k| k.as_str().to_string()),
+            protocol_fee_bps: 0,
+            creator_fee_bps: 0,
+            fees_collected: U128(0),
+            collateral_token: None,
+            creator_bond: U128(0),
+            resolution_policy: ResolutionPolicy::default(),
+            resolution_deadline: self.resolution_date + DEFAULT_RESOLUTION_GRACE,
+            outcome_team_ids: Vec::new(),
+            kind,
+            settlement,
+            // Старый контракт считал по итоговому счёту ESPN:
+            // в футболе он включает дополнительное время, но не пенальти
+            settlement_rule: match self.sport.as_str() {
+                "soccer" => SettlementRule::IncludingOvertime,
+                _ => SettlementRule::IncludingShootout,
+            },
+            outcomes: self.outcomes,
+            espn_event_id: self.espn_event_id,
+            sport: self.sport,
+            league: self.league,
+        }
+    }
+}
+
+/// Borsh-layout состояния до миграции (читается в migrate)
+#[near(serializers = [borsh])]
+struct OldNearCast {
+    owner: AccountId,
+    oracle: AccountId,
+    markets: IterableMap<u64, OldMarket>,
+    market_bets: LookupMap<u64, Vec<Bet>>,
+    user_bets: LookupMap<AccountId, Vec<Bet>>,
+    balances: LookupMap<AccountId, u128>,
+    market_count: u64,
+    total_volume: u128,
+    outlayer_source_repo: String,
+    outlayer_source_commit: String,
+    outlayer_contract: AccountId,
+    reclaim_contract: AccountId,
+    tls_oracle_contract: AccountId,
 }
 
 impl Default for NearCast {
@@ -193,12 +1504,22 @@ impl Default for NearCast {
         Self {
             owner: env::predecessor_account_id(),
             oracle: env::predecessor_account_id(),
-            markets: IterableMap::new(StorageKey::Markets),
-            market_bets: LookupMap::new(StorageKey::MarketBets),
-            user_bets: LookupMap::new(StorageKey::UserBets),
+            markets: IterableMap::new(StorageKey::MarketsV2),
+            legacy_markets: IterableMap::new(StorageKey::Markets),
+            legacy_market_bets: LookupMap::new(StorageKey::MarketBets),
+            legacy_user_bets: LookupMap::new(StorageKey::UserBets),
             balances: LookupMap::new(StorageKey::Balances),
+            pending_withdrawals: LookupMap::new(StorageKey::PendingWithdrawals),
             market_count: 0,
             total_volume: 0,
+            fee_config: FeeConfig {
+                protocol_fee_bps: 0,
+                max_creator_fee_bps: 0,
+            },
+            treasury: 0,
+            collateral_tokens: IterableMap::new(StorageKey::CollateralTokens),
+            ft_balances: LookupMap::new(StorageKey::FtBalances),
+            ft_treasury: LookupMap::new(StorageKey::FtTreasury),
             outlayer_source_repo: String::new(),
             outlayer_source_commit: "main".to_string(),
             outlayer_contract: "outlayer.testnet".parse().unwrap(),
@@ -206,6 +1527,23 @@ impl Default for NearCast {
             tls_oracle_contract: "tls-oracle-v2.nearcast-oracle.testnet"
                 .parse()
                 .unwrap(),
+            positions: LookupMap::new(StorageKey::Positions),
+            bet_log: LookupMap::new(StorageKey::BetLog),
+            user_markets: LookupMap::new(StorageKey::UserMarkets),
+            claimed_stakes: LookupMap::new(StorageKey::ClaimedStakes),
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts),
+            creation_bond: 0,
+            moderators: IterableSet::new(StorageKey::Moderators),
+            locked_bonds: LookupMap::new(StorageKey::LockedBonds),
+            resolution_config: ResolutionConfig {
+                proposal_bond: U128(DEFAULT_PROPOSAL_BOND),
+                dispute_window: DEFAULT_DISPUTE_WINDOW,
+                challenge_window: DEFAULT_CHALLENGE_WINDOW,
+                resolver_reward_bps: 0,
+            },
+            proposals: LookupMap::new(StorageKey::Proposals),
+            score_votes: LookupMap::new(StorageKey::ScoreVotes),
+            resolution_requests: LookupMap::new(StorageKey::ResolutionRequests),
         }
     }
 }
@@ -277,6 +1615,119 @@ fn safe_mul_div(a: u128, b: u128, c: u128) -> u128 {
     }
 }
 
+/// Единица суммы для логов: yoctoNEAR или id NEP-141 токена
+fn token_label(token_id: &Option<AccountId>) -> String {
+    match token_id {
+        None => "yoctoNEAR".to_string(),
+        Some(token) => token.to_string(),
+    }
+}
+
+/// Перевод NEAR (transfer) или NEP-141 токена (ft_transfer) получателю
+fn transfer_promise(
+    receiver_id: &AccountId,
+    token_id: &Option<AccountId>,
+    amount: u128,
+) -> Promise {
+    match token_id {
+        None => Promise::new(receiver_id.clone()).transfer(NearToken::from_yoctonear(amount)),
+        Some(token) => Promise::new(token.clone()).function_call(
+            "ft_transfer".to_string(),
+            serde_json::to_vec(&serde_json::json!({
+                "receiver_id": receiver_id,
+                "amount": U128(amount),
+            }))
+            .unwrap(),
+            ONE_YOCTO,
+            GAS_FOR_FT_TRANSFER,
+        ),
+    }
+}
+
+/// Стоимость хранения `bytes` байт
+fn storage_cost(bytes: u64) -> u128 {
+    bytes as u128 * env::storage_byte_cost().as_yoctonear()
+}
+
+/// Минимальный storage-депозит для регистрации
+fn storage_min_balance() -> u128 {
+    storage_cost(STORAGE_REGISTRATION_BYTES)
+}
+
+/// Свободная часть storage-депозита
+fn storage_available(account: &StorageAccount) -> u128 {
+    account
+        .deposit
+        .saturating_sub(storage_cost(account.used_bytes))
+}
+
+/// Название исхода начинается с одного из префиксов (без учёта регистра)
+fn label_starts_with(label: &str, prefixes: &[&str]) -> bool {
+    let lower = label.to_lowercase();
+    prefixes.iter().any(|p| lower.starts_with(p))
+}
+
+/// Диапазон периодов рынка: с первого, from <= to
+fn validate_periods(from_period: u8, to_period: u8) {
+    assert!(
+        from_period >= 1 && from_period <= to_period,
+        "Периоды: 1 <= from_period <= to_period"
+    );
+}
+
+/// Исход "любой другой счёт" для точного счёта
+fn is_other_label(label: &str) -> bool {
+    label_starts_with(label, &["any other", "other", "любой другой", "другой"])
+}
+
+/// Счёт из названия исхода: "2-1", "2:1" (хозяева-гости), текст после — пояснение
+fn parse_scoreline(label: &str) -> Option<(u32, u32)> {
+    let token = label.split_whitespace().next()?;
+    let (home, away) = token.split_once(['-', ':'])?;
+    Some((home.parse().ok()?, away.parse().ok()?))
+}
+
+/// Линия форы — последнее слово названия исхода со знаком:
+/// "1. FC Köln -0.25" → -0.25, "Lakers +5" → 5; без знака — None
+fn parse_signed_line(label: &str) -> Option<f64> {
+    let token = label.split_whitespace().last()?;
+    if !token.starts_with(['+', '-']) {
+        return None;
+    }
+    token.parse().ok().filter(|line: &f64| line.is_finite())
+}
+
+/// Корзина тотала из названия исхода: "0-1" → (0, Some(1)), "4+" → (4, None),
+/// "2" → (2, Some(2)); текст после — единицы ("200-209 points")
+fn parse_bucket(label: &str) -> Option<(u32, Option<u32>)> {
+    let token = label.split_whitespace().next()?;
+    if let Some(min) = token.strip_suffix('+') {
+        return Some((min.parse().ok()?, None));
+    }
+    match token.split_once('-') {
+        Some((min, max)) => {
+            let (min, max): (u32, u32) = (min.parse().ok()?, max.parse().ok()?);
+            (min <= max).then_some((min, Some(max)))
+        }
+        None => {
+            let exact = token.parse().ok()?;
+            Some((exact, Some(exact)))
+        }
+    }
+}
+
+/// Исход-ничья: без team id, если рынок с привязкой, иначе по названию
+fn draw_index(outcomes: &[String], team_ids: &[String]) -> Option<usize> {
+    if team_ids.is_empty() {
+        outcomes.iter().position(|o| {
+            let lower = o.to_lowercase();
+            lower == "draw" || lower == "ничья"
+        })
+    } else {
+        team_ids.iter().position(|t| t.is_empty())
+    }
+}
+
 /// Нечёткое совпадение: проверяем что одна строка содержит другую
 fn fuzzy_match(outcome: &str, espn_name: &str) -> bool {
     if outcome.is_empty() || espn_name.is_empty() {
@@ -307,678 +1758,1792 @@ fn fuzzy_match(outcome: &str, espn_name: &str) -> bool {
     false
 }
 
+/// Индекс исхода для команды ESPN. Рынок с привязкой team id —
+/// только точное совпадение id; без привязки — по имени: точное
+/// совпадение, иначе единственное нечёткое. None — команда не найдена
+fn team_outcome(
+    outcomes: &[String],
+    team_ids: &[String],
+    draw_idx: Option<usize>,
+    name: &str,
+    id: &str,
+) -> Option<usize> {
+    if !team_ids.is_empty() {
+        if id.is_empty() {
+            return None;
+        }
+        return team_ids.iter().position(|t| t == id);
+    }
+
+    let candidates: Vec<usize> = (0..outcomes.len())
+        .filter(|&i| Some(i) != draw_idx)
+        .collect();
+    if let Some(&i) = candidates
+        .iter()
+        .find(|&&i| outcomes[i].to_lowercase() == name.to_lowercase())
+    {
+        return Some(i);
+    }
+    let fuzzy: Vec<usize> = candidates
+        .into_iter()
+        .filter(|&i| fuzzy_match(&outcomes[i], name))
+        .collect();
+    match fuzzy.as_slice() {
+        [i] => Some(*i),
+        _ => None,
+    }
+}
+
 /// Winner: 3-way (с ничьёй — футбол) или 2-way (баскетбол, теннис)
-/// Матчим outcomes по именам команд из ESPN, а не по позиции в массиве.
+/// Матчим outcomes по ESPN team id (или именам), а не по позиции в массиве.
+/// Если команды не сопоставились с исходами — рынок аннулируется.
 fn resolve_winner(
     outcomes: &[String],
-    home: i32,
-    away: i32,
-    home_name: &str,
-    away_name: &str,
-) -> (i32, String) {
-    if outcomes.len() == 3 {
-        // Находим индексы по именам ESPN команд
-        let draw_idx = outcomes.iter().position(|o| {
-            let lower = o.to_lowercase();
-            lower == "draw" || lower == "ничья"
-        });
-        let home_idx = outcomes.iter().position(|o| {
-            Some(outcomes.iter().position(|x| x == o).unwrap()) != draw_idx
-                && fuzzy_match(o, home_name)
-        });
-        let away_idx = outcomes.iter().position(|o| {
-            Some(outcomes.iter().position(|x| x == o).unwrap()) != draw_idx
-                && fuzzy_match(o, away_name)
-        });
+    team_ids: &[String],
+    draw: bool,
+    espn: &EspnResult,
+) -> (Resolution, String) {
+    let (home, away) = (espn.home_score, espn.away_score);
 
-        // Fallback: если не удалось найти по имени, берём оставшиеся по порядку
-        let non_draw: Vec<usize> = (0..3).filter(|&i| Some(i) != draw_idx).collect();
-        let home_idx = home_idx.unwrap_or(non_draw[0]);
-        let away_idx = away_idx.unwrap_or(non_draw[1]);
+    let draw_idx = if draw {
+        draw_index(outcomes, team_ids)
+    } else {
+        None
+    };
 
-        if home > away {
-            (
-                home_idx as i32,
-                format!("{} wins {}:{}", outcomes[home_idx], home, away),
-            )
-        } else if home < away {
-            (
-                away_idx as i32,
-                format!("{} wins {}:{}", outcomes[away_idx], away, home),
+    let home_idx = team_outcome(
+        outcomes,
+        team_ids,
+        draw_idx,
+        &espn.home_team,
+        &espn.home_team_id,
+    );
+    let away_idx = team_outcome(
+        outcomes,
+        team_ids,
+        draw_idx,
+        &espn.away_team,
+        &espn.away_team_id,
+    );
+    let (home_idx, away_idx) = match (home_idx, away_idx) {
+        (Some(h), Some(a)) if h != a => (h, a),
+        _ => {
+            return (
+                Resolution::Void,
+                format!(
+                    "Teams not matched to outcomes: {} ({}) vs {} ({})",
+                    espn.home_team, espn.home_team_id, espn.away_team, espn.away_team_id
+                ),
             )
-        } else if let Some(di) = draw_idx {
-            (di as i32, format!("Draw {}:{}", home, away))
-        } else {
-            (-1, format!("Draw {}:{} but no Draw outcome", home, away))
         }
-    } else if outcomes.len() == 2 {
-        // 2-way: матчим по именам ESPN команд
-        let home_idx = outcomes
-            .iter()
-            .position(|o| fuzzy_match(o, home_name));
-        let away_idx = outcomes
-            .iter()
-            .position(|o| fuzzy_match(o, away_name));
-
-        let home_idx = home_idx.unwrap_or(0);
-        let away_idx = away_idx.unwrap_or(1);
+    };
 
-        if home > away {
-            (
-                home_idx as i32,
-                format!("{} wins {}:{}", outcomes[home_idx], home, away),
-            )
-        } else if home < away {
-            (
-                away_idx as i32,
-                format!("{} wins {}:{}", outcomes[away_idx], home, away),
-            )
-        } else {
-            (-1, format!("Draw {}:{} in 2-way market", home, away))
-        }
+    if home > away {
+        (
+            Resolution::Winner(home_idx as u32),
+            format!("{} wins {}:{}", outcomes[home_idx], home, away),
+        )
+    } else if home < away {
+        (
+            Resolution::Winner(away_idx as u32),
+            format!("{} wins {}:{}", outcomes[away_idx], away, home),
+        )
+    } else if let Some(di) = draw_idx {
+        (Resolution::Winner(di as u32), format!("Draw {}:{}", home, away))
+    } else if draw {
+        (
+            Resolution::Void,
+            format!("Draw {}:{} but no Draw outcome", home, away),
+        )
     } else {
-        (-1, "Invalid outcomes count for winner".to_string())
+        // Ничья в 2-way рынке — push, ставки возвращаются
+        (
+            Resolution::Push,
+            format!("Draw {}:{} in 2-way market — push", home, away),
+        )
     }
 }
 
-/// Over/Under: outcomes ["Over X.5", "Under X.5"]
-fn resolve_over_under(outcomes: &[String], home: i32, away: i32) -> (i32, String) {
+/// Over/Under: outcomes ["Over X", "Under X"], линия из MarketKind.
+/// Тотал ровно на целой линии — push
+fn resolve_over_under(line_x10: u32, home: i32, away: i32) -> (Resolution, String) {
     let total = home + away;
-    // Извлекаем порог из названия первого исхода (напр. "Over 2.5" → 2.5)
-    let threshold = outcomes
-        .first()
-        .and_then(|o| {
-            o.split_whitespace()
-                .find_map(|word| word.parse::<f64>().ok())
-        })
-        .unwrap_or(2.5);
+    let threshold = format!("{}.{}", line_x10 / 10, line_x10 % 10);
 
-    if (total as f64) > threshold {
-        (
-            0,
+    match ((total as i64) * 10).cmp(&(line_x10 as i64)) {
+        std::cmp::Ordering::Greater => (
+            Resolution::Winner(0),
             format!("Total {}>{} ({}:{})", total, threshold, home, away),
-        )
-    } else {
-        (
-            1,
+        ),
+        std::cmp::Ordering::Less => (
+            Resolution::Winner(1),
             format!("Total {}<{} ({}:{})", total, threshold, home, away),
-        )
+        ),
+        std::cmp::Ordering::Equal => (
+            Resolution::Push,
+            format!("Total {}={} ({}:{}) — push", total, threshold, home, away),
+        ),
     }
 }
 
 /// Both teams to score: [Yes, No]
-fn resolve_both_score(home: i32, away: i32) -> (i32, String) {
+fn resolve_both_score(home: i32, away: i32) -> (Resolution, String) {
     if home > 0 && away > 0 {
-        (0, format!("Both scored ({}:{})", home, away))
+        (
+            Resolution::Winner(0),
+            format!("Both scored ({}:{})", home, away),
+        )
     } else {
-        (1, format!("Not both scored ({}:{})", home, away))
+        (
+            Resolution::Winner(1),
+            format!("Not both scored ({}:{})", home, away),
+        )
     }
 }
 
-/// Определяет winning_outcome из сырых данных ESPN + метаданных рынка
-fn determine_winner(
-    market: &Market,
+/// Spread: счёт хозяев с форой против счёта гостей. Исход 0 — хозяева;
+/// при привязке team id хозяева ESPN должны совпасть с исходом 0
+fn resolve_spread(
+    handicap_x10: i32,
+    team_ids: &[String],
     espn: &EspnResult,
-) -> (i32, f64, String) {
-    if espn.event_status != "final" {
-        return (-1, 0.0, format!("Event not completed (status: {})", espn.event_status));
-    }
-
-    if !espn.error.is_empty() {
-        return (-1, 0.0, format!("ESPN error: {}", espn.error));
-    }
-
-    if espn.home_score < 0 || espn.away_score < 0 {
-        return (-1, 0.0, "Could not parse scores from ESPN".to_string());
+) -> (Resolution, String) {
+    if !team_ids.is_empty() && team_ids[0] != espn.home_team_id {
+        return (
+            Resolution::Void,
+            format!(
+                "Home team {} ({}) does not match spread outcome team id {}",
+                espn.home_team, espn.home_team_id, team_ids[0]
+            ),
+        );
     }
 
-    let (winning_outcome, reasoning) = match market.market_type.as_str() {
-        "winner" => resolve_winner(
-            &market.outcomes,
-            espn.home_score,
-            espn.away_score,
-            &espn.home_team,
-            &espn.away_team,
+    let (home, away) = (espn.home_score, espn.away_score);
+    let handicap = format!(
+        "{}{}.{}",
+        if handicap_x10 < 0 { "-" } else { "+" },
+        handicap_x10.unsigned_abs() / 10,
+        handicap_x10.unsigned_abs() % 10
+    );
+    let margin = (home as i64) * 10 + handicap_x10 as i64 - (away as i64) * 10;
+
+    match margin.cmp(&0) {
+        std::cmp::Ordering::Greater => (
+            Resolution::Winner(0),
+            format!("{} {} covers ({}:{})", espn.home_team, handicap, home, away),
         ),
-        "over-under" => resolve_over_under(
-            &market.outcomes,
-            espn.home_score,
-            espn.away_score,
+        std::cmp::Ordering::Less => (
+            Resolution::Winner(1),
+            format!(
+                "{} covers vs {} ({}:{})",
+                espn.away_team, handicap, home, away
+            ),
         ),
-        "both-score" => resolve_both_score(espn.home_score, espn.away_score),
-        _ => (-1, format!("Unknown market type: {}", market.market_type)),
-    };
-
-    let confidence = if winning_outcome >= 0 { 1.0 } else { 0.0 };
-    (winning_outcome, confidence, reasoning)
+        std::cmp::Ordering::Equal => (
+            Resolution::Push,
+            format!(
+                "{} {} ties ({}:{}) — push",
+                espn.home_team, handicap, home, away
+            ),
+        ),
+    }
 }
 
-// ══════════════════════════════════════════════════════════════════
+/// Азиатская фора: целая и половинная линии — как spread, четвертная —
+/// половина ставки на каждой соседней линии (-0.25 = 0 и -0.5).
+/// Возможны полувыигрыш и полувозврат
+fn resolve_asian_handicap(
+    handicap_x100: i32,
+    team_ids: &[String],
+    espn: &EspnResult,
+) -> (Resolution, String) {
+    if !team_ids.is_empty() && team_ids[0] != espn.home_team_id {
+        return (
+            Resolution::Void,
+            format!(
+                "Home team {} ({}) does not match handicap outcome team id {}",
+                espn.home_team, espn.home_team_id, team_ids[0]
+            ),
+        );
+    }
 
-#[near]
-impl NearCast {
-    /// Инициализация контракта (ignore_state — для миграции)
-    #[init(ignore_state)]
-    pub fn new(oracle: Option<AccountId>) -> Self {
-        let owner = env::predecessor_account_id();
-        Self {
-            owner: owner.clone(),
-            oracle: oracle.unwrap_or(owner),
-            markets: IterableMap::new(StorageKey::Markets),
-            market_bets: LookupMap::new(StorageKey::MarketBets),
-            user_bets: LookupMap::new(StorageKey::UserBets),
-            balances: LookupMap::new(StorageKey::Balances),
-            market_count: 0,
-            total_volume: 0,
-            outlayer_source_repo: String::new(),
-            outlayer_source_commit: "main".to_string(),
-            outlayer_contract: "outlayer.testnet".parse().unwrap(),
-            reclaim_contract: "reclaim-protocol.testnet".parse().unwrap(),
-            tls_oracle_contract: "tls-oracle-v2.nearcast-oracle.testnet"
-                .parse()
-                .unwrap(),
+    let (home, away) = (espn.home_score, espn.away_score);
+    let lines = if handicap_x100 % 50 == 0 {
+        vec![handicap_x100]
+    } else {
+        vec![handicap_x100 - 25, handicap_x100 + 25]
+    };
+    let share = (BPS_DENOMINATOR / lines.len() as u128) as u16;
+
+    let mut settlement = [OutcomeSettlement::LOSE; 2];
+    for line in &lines {
+        let margin = (home as i64 - away as i64) * 100 + *line as i64;
+        match margin.cmp(&0) {
+            std::cmp::Ordering::Greater => settlement[0].win_bps += share,
+            std::cmp::Ordering::Less => settlement[1].win_bps += share,
+            std::cmp::Ordering::Equal => {
+                settlement[0].push_bps += share;
+                settlement[1].push_bps += share;
+            }
         }
     }
 
-    // ══════════════════════════════════════════════════════════════
-    // ВНУТРЕННИЙ БАЛАНС — deposit / withdraw
-    // ══════════════════════════════════════════════════════════════
-
-    /// Пополнение внутреннего баланса
-    #[payable]
-    pub fn deposit(&mut self) {
-        let sender = env::predecessor_account_id();
-        let amount = env::attached_deposit().as_yoctonear();
-        assert!(amount > 0, "Прикрепите NEAR для пополнения");
-
-        let current = self.balances.get(&sender).copied().unwrap_or(0);
-        self.balances.set(sender.clone(), Some(current + amount));
+    let reasoning = format!(
+        "{} {}{}.{:02} vs {} ({}:{}): home win {}bps push {}bps, away win {}bps push {}bps",
+        espn.home_team,
+        if handicap_x100 < 0 { "-" } else { "+" },
+        handicap_x100.unsigned_abs() / 100,
+        handicap_x100.unsigned_abs() % 100,
+        espn.away_team,
+        home,
+        away,
+        settlement[0].win_bps,
+        settlement[0].push_bps,
+        settlement[1].win_bps,
+        settlement[1].push_bps
+    );
+    (Resolution::Split(settlement.to_vec()), reasoning)
+}
 
-        log!("Депозит: {} пополнил баланс на {} yoctoNEAR", sender, amount);
+/// Correct score: исход с точным счётом, иначе "Any other";
+/// без подходящего исхода рынок аннулируется
+fn resolve_correct_score(outcomes: &[String], home: i32, away: i32) -> (Resolution, String) {
+    let score = (home as u32, away as u32);
+    if let Some(i) = outcomes
+        .iter()
+        .position(|o| parse_scoreline(o) == Some(score))
+    {
+        return (
+            Resolution::Winner(i as u32),
+            format!("Correct score {}:{}", home, away),
+        );
     }
+    match outcomes.iter().position(|o| is_other_label(o)) {
+        Some(i) => (
+            Resolution::Winner(i as u32),
+            format!("Score {}:{} — any other", home, away),
+        ),
+        None => (
+            Resolution::Void,
+            format!(
+                "Score {}:{} not listed and no \"Any other\" outcome",
+                home, away
+            ),
+        ),
+    }
+}
 
-    /// Вывод средств с внутреннего баланса на кошелёк
-    pub fn withdraw(&mut self, amount: U128) -> Promise {
-        let sender = env::predecessor_account_id();
-        let withdraw_amount: u128 = amount.into();
-        assert!(withdraw_amount > 0, "Сумма должна быть положительной");
-
-        let current = self.balances.get(&sender).copied().unwrap_or(0);
-        assert!(current >= withdraw_amount, "Недостаточно средств на балансе");
+/// Range: корзина, в которую попал тотал; тотал вне корзин — аннулирование
+fn resolve_range(outcomes: &[String], home: i32, away: i32) -> (Resolution, String) {
+    let total = (home + away) as u32;
+    let bucket = outcomes.iter().position(|o| {
+        parse_bucket(o).is_some_and(|(min, max)| total >= min && max.is_none_or(|m| total <= m))
+    });
+    match bucket {
+        Some(i) => (
+            Resolution::Winner(i as u32),
+            format!("Total {} in \"{}\" ({}:{})", total, outcomes[i], home, away),
+        ),
+        None => (
+            Resolution::Void,
+            format!("Total {} outside all buckets ({}:{})", total, home, away),
+        ),
+    }
+}
 
-        self.balances.set(sender.clone(), Some(current - withdraw_amount));
+/// Счёт за периоды from..=to как EspnResult для резолверов полного матча;
+/// None — источник не сообщил столько периодов
+fn period_result(espn: &EspnResult, from_period: u8, to_period: u8) -> Option<EspnResult> {
+    let range = from_period as usize - 1..to_period as usize;
+    let home = espn.home_periods.get(range.clone())?;
+    let away = espn.away_periods.get(range)?;
+    let mut part = espn.clone();
+    part.home_score = home.iter().sum();
+    part.away_score = away.iter().sum();
+    Some(part)
+}
 
-        log!("Вывод: {} выводит {} yoctoNEAR", sender, withdraw_amount);
-        Promise::new(sender).transfer(NearToken::from_yoctonear(withdraw_amount))
+/// Highest scoring period: период с наибольшей суммой очков обеих команд
+/// среди исходов (по одному на период основного времени)
+fn resolve_highest_scoring_period(outcomes: &[String], espn: &EspnResult) -> (Resolution, String) {
+    let n = outcomes.len();
+    if espn.home_periods.len() < n || espn.away_periods.len() < n {
+        return (
+            Resolution::Void,
+            format!(
+                "Period scores missing: {} of {} periods",
+                espn.home_periods.len().min(espn.away_periods.len()),
+                n
+            ),
+        );
     }
 
-    // ══════════════════════════════════════════════════════════════
-    // СОЗДАНИЕ РЫНКА
-    // ══════════════════════════════════════════════════════════════
+    let totals: Vec<i32> = (0..n)
+        .map(|i| espn.home_periods[i] + espn.away_periods[i])
+        .collect();
+    let best = totals.iter().copied().max().unwrap_or(0);
+    let leaders: Vec<usize> = (0..n).filter(|&i| totals[i] == best).collect();
+    match leaders.as_slice() {
+        [i] => (
+            Resolution::Winner(*i as u32),
+            format!("Period {} highest with {} ({:?})", i + 1, best, totals),
+        ),
+        _ => (
+            Resolution::Push,
+            format!("Periods tied at {} ({:?}) — push", best, totals),
+        ),
+    }
+}
 
-    pub fn create_market(
-        &mut self,
-        question: String,
-        description: String,
-        outcomes: Vec<String>,
-        category: String,
-        bets_end_date: String,
-        resolution_date: String,
-        espn_event_id: Option<String>,
-        sport: Option<String>,
-        league: Option<String>,
-        market_type: Option<String>,
-    ) -> u64 {
-        let sender = env::predecessor_account_id();
-        let now = env::block_timestamp();
+/// Head-to-head: исход спортсмена с флагом winner. Спортсмены рынка должны
+/// быть среди участников события, иначе аннулирование. Без победителя —
+/// ничья (исход с "" или push в 2-way)
+fn resolve_head_to_head(
+    outcomes: &[String],
+    athlete_ids: &[String],
+    draw: bool,
+    competitors: &[CompetitorResult],
+) -> (Resolution, String) {
+    if let Some(missing) = athlete_ids
+        .iter()
+        .find(|id| !id.is_empty() && !competitors.iter().any(|c| &c.id == *id))
+    {
+        return (
+            Resolution::Void,
+            format!("Athlete {} not among event competitors", missing),
+        );
+    }
 
-        let bets_end: u64 = bets_end_date.parse().expect("Невалидный bets_end_date");
-        let resolution: u64 = resolution_date.parse().expect("Невалидный resolution_date");
+    let winners: Vec<&CompetitorResult> = competitors.iter().filter(|c| c.winner).collect();
+    match winners.as_slice() {
+        [w] => match athlete_ids.iter().position(|id| *id == w.id) {
+            Some(i) => (
+                Resolution::Winner(i as u32),
+                format!("{} wins", outcomes[i]),
+            ),
+            None => (
+                Resolution::Void,
+                format!("Winner {} not among outcomes", w.id),
+            ),
+        },
+        [] => match draw_index(outcomes, athlete_ids) {
+            Some(di) if draw => (
+                Resolution::Winner(di as u32),
+                "No winner — draw".to_string(),
+            ),
+            _ if draw => (
+                Resolution::Void,
+                "No winner but no Draw outcome".to_string(),
+            ),
+            _ => (
+                Resolution::Push,
+                "No winner in 2-way market — push".to_string(),
+            ),
+        },
+        _ => (
+            Resolution::Void,
+            format!("{} competitors flagged as winner", winners.len()),
+        ),
+    }
+}
 
-        // Валидация
-        assert!(
-            !question.is_empty() && question.len() <= 500,
-            "Вопрос: от 1 до 500 символов"
+/// Top-N: выигрывают все исходы, чей спортсмен занял место 1..=N;
+/// не финишировавшие (место 0) проигрывают. Мест нет, спортсмена исхода
+/// нет среди участников или никто из исходов не в топе — аннулирование
+fn resolve_top_n(
+    athlete_ids: &[String],
+    n: u32,
+    competitors: &[CompetitorResult],
+) -> (Resolution, String) {
+    if !competitors.iter().any(|c| c.position > 0) {
+        return (
+            Resolution::Void,
+            "No finishing positions reported".to_string(),
         );
-        assert!(description.len() <= 2000, "Описание: до 2000 символов");
-        assert!(
-            outcomes.len() >= MIN_OUTCOMES && outcomes.len() <= MAX_OUTCOMES,
-            "Количество исходов: от {} до {}",
-            MIN_OUTCOMES,
-            MAX_OUTCOMES
-        );
-        assert!(bets_end > now, "Дедлайн ставок должен быть в будущем");
-        assert!(
-            resolution > bets_end,
-            "Дата разрешения должна быть после дедлайна ставок"
+    }
+    if let Some(missing) = athlete_ids
+        .iter()
+        .find(|id| !competitors.iter().any(|c| &c.id == *id))
+    {
+        return (
+            Resolution::Void,
+            format!("Athlete {} not among event competitors", missing),
         );
+    }
 
-        for (i, o) in outcomes.iter().enumerate() {
-            assert!(
-                !o.is_empty() && o.len() <= 200,
-                "Каждый исход: от 1 до 200 символов"
-            );
-            for j in (i + 1)..outcomes.len() {
-                assert!(o != &outcomes[j], "Исходы должны быть уникальными");
+    let position = |id: &String| {
+        competitors
+            .iter()
+            .find(|c| &c.id == id)
+            .map_or(0, |c| c.position)
+    };
+    let settlement: Vec<OutcomeSettlement> = athlete_ids
+        .iter()
+        .map(|id| {
+            let p = position(id);
+            if p >= 1 && p <= n {
+                OutcomeSettlement::WIN
+            } else {
+                OutcomeSettlement::LOSE
             }
-        }
+        })
+        .collect();
+    let places: Vec<String> = athlete_ids
+        .iter()
+        .map(|id| format!("{}#{}", id, position(id)))
+        .collect();
+
+    if !settlement.contains(&OutcomeSettlement::WIN) {
+        return (
+            Resolution::Void,
+            format!("No outcome athlete in top {} ({})", n, places.join(", ")),
+        );
+    }
+    (
+        Resolution::Split(settlement),
+        format!("Top {}: {}", n, places.join(", ")),
+    )
+}
 
-        let id = self.market_count;
-        self.market_count += 1;
+/// Player prop: показатель игрока против линии, равенство на целой линии —
+/// push. Игрок не играл или показатель не найден — аннулирование
+fn resolve_player_prop(
+    athlete_id: &str,
+    stat: &str,
+    line_x10: u32,
+    stats: &[PlayerStat],
+) -> (Resolution, String) {
+    let value = stats
+        .iter()
+        .find(|s| s.athlete_id == athlete_id && s.stat.eq_ignore_ascii_case(stat))
+        .and_then(|s| s.value_x10);
+    let Some(value) = value else {
+        return (
+            Resolution::Void,
+            format!("No {} for athlete {} (did not play?)", stat, athlete_id),
+        );
+    };
 
-        let outcome_pools = vec![U128(0); outcomes.len()];
+    let shown = format!(
+        "{} {}={}{}.{}",
+        athlete_id,
+        stat,
+        if value < 0 { "-" } else { "" },
+        value.unsigned_abs() / 10,
+        value.unsigned_abs() % 10
+    );
+    let line = format!("{}.{}", line_x10 / 10, line_x10 % 10);
+    match value.cmp(&(line_x10 as i64)) {
+        std::cmp::Ordering::Greater => (Resolution::Winner(0), format!("{} > {}", shown, line)),
+        std::cmp::Ordering::Less => (Resolution::Winner(1), format!("{} < {}", shown, line)),
+        std::cmp::Ordering::Equal => (Resolution::Push, format!("{} = {} — push", shown, line)),
+    }
+}
 
-        let market = Market {
-            id,
-            creator: sender,
-            question: question.clone(),
-            description,
-            outcomes: outcomes.clone(),
-            outcome_pools,
-            total_pool: U128(0),
-            category,
-            created_at: now,
-            bets_end_date: bets_end,
-            resolution_date: resolution,
-            resolved_outcome: -1,
-            status: "active".to_string(),
-            total_bets: 0,
-            espn_event_id: espn_event_id.unwrap_or_default(),
-            sport: sport.unwrap_or_default(),
-            league: league.unwrap_or_default(),
-            market_type: market_type.unwrap_or_else(|| "winner".to_string()),
-        };
+/// Outright: исход команды-победителя по ESPN team id, иначе исход
+/// "любая другая команда" (""); без подходящего исхода — аннулирование
+fn resolve_outright(
+    outcomes: &[String],
+    team_ids: &[String],
+    winner_id: &str,
+) -> (Resolution, String) {
+    if winner_id.is_empty() {
+        return (Resolution::Void, "Winner not reported".to_string());
+    }
+    if let Some(i) = team_ids.iter().position(|t| t == winner_id) {
+        return (
+            Resolution::Winner(i as u32),
+            format!("{} ({}) wins", outcomes[i], winner_id),
+        );
+    }
+    match team_ids.iter().position(|t| t.is_empty()) {
+        Some(i) => (
+            Resolution::Winner(i as u32),
+            format!("Winner {} — {}", winner_id, outcomes[i]),
+        ),
+        None => (
+            Resolution::Void,
+            format!("Winner {} not among outcomes", winner_id),
+        ),
+    }
+}
 
-        self.markets.insert(id, market);
-        self.market_bets.set(id, Some(Vec::new()));
+/// Счёт матча по правилу расчёта рынка (+ пометка для reasoning).
+/// Источник без данных о доп. времени — итоговый счёт как есть, кроме
+/// правила Regulation: итог мог включать овертайм.
+/// Err — нужный счёт неизвестен
+fn settled_score(
+    rule: SettlementRule,
+    espn: &EspnResult,
+) -> Result<(EspnResult, Option<String>), String> {
+    let extra = match espn.extra_time() {
+        Some(extra) if extra.overtime || extra.shootout => extra,
+        Some(_) => return Ok((espn.clone(), None)),
+        None if rule == SettlementRule::Regulation => {
+            return Err("Extra time not reported, regulation score unknown".to_string())
+        }
+        None => return Ok((espn.clone(), None)),
+    };
 
-        log!(
-            "Рынок создан: #{} — \"{}\" ({} исходов)",
-            id,
-            question,
-            outcomes.len()
-        );
-        id
+    let mut scored = espn.clone();
+    match rule {
+        SettlementRule::Regulation => {
+            let (Some(home), Some(away)) =
+                (extra.regulation_home_score, extra.regulation_away_score)
+            else {
+                return Err("Regulation score unavailable".to_string());
+            };
+            scored.home_score = home;
+            scored.away_score = away;
+        }
+        // После овертайма ничья, раз дошло до серии; в NHL итог включает
+        // победный буллит — убираем его
+        SettlementRule::IncludingOvertime if extra.shootout => {
+            let level = espn.home_score.min(espn.away_score);
+            scored.home_score = level;
+            scored.away_score = level;
+        }
+        SettlementRule::IncludingOvertime => return Ok((scored, None)),
+        // Футбольные пенальти в счёт не входят — победителю серии +1
+        SettlementRule::IncludingShootout => {
+            match (extra.home_shootout_score, extra.away_shootout_score) {
+                (Some(h), Some(a)) if extra.shootout && espn.home_score == espn.away_score => {
+                    if h > a {
+                        scored.home_score += 1;
+                    } else if a > h {
+                        scored.away_score += 1;
+                    }
+                }
+                _ => return Ok((scored, None)),
+            }
+        }
     }
 
-    // ══════════════════════════════════════════════════════════════
-    // СТАВКИ (списание с внутреннего баланса)
-    // ══════════════════════════════════════════════════════════════
+    let note = format!(
+        "{} {}:{}",
+        rule.as_str(),
+        scored.home_score,
+        scored.away_score
+    );
+    Ok((scored, Some(note)))
+}
 
-    pub fn place_bet(&mut self, market_id: u64, outcome: u32, amount: U128) {
-        let bet_amount: u128 = amount.into();
-        let sender = env::predecessor_account_id();
-        let now = env::block_timestamp();
+/// Чего не хватает в финальном ответе источника, чтобы рассчитать рынок.
+/// Такой ответ — не голос за аннулирование: источник не может судить,
+/// и рынок ждёт источник с полными данными. None — данных достаточно
+fn missing_data(market: &Market, espn: &EspnResult) -> Option<String> {
+    if market.settles_by_team_id() && (espn.home_team_id.is_empty() || espn.away_team_id.is_empty())
+    {
+        return Some("team ids not reported".to_string());
+    }
 
-        assert!(bet_amount >= MIN_BET, "Минимальная ставка: 0.1 NEAR");
+    let periods_needed = market.periods_needed();
+    if espn.home_periods.len().min(espn.away_periods.len()) < periods_needed {
+        return Some(format!(
+            "period scores missing for {} periods",
+            periods_needed
+        ));
+    }
 
-        let balance = self.balances.get(&sender).copied().unwrap_or(0);
-        assert!(
-            balance >= bet_amount,
-            "Недостаточно средств. Пополните депозит."
+    // Спортсмен не в списке — источник его не сообщил (обрезанный протокол),
+    // а не проиграл: сошедшие приходят с местом 0
+    if matches!(
+        market.kind,
+        Some(MarketKind::HeadToHead { .. } | MarketKind::TopN { .. })
+    ) {
+        if let Some(absent) = market
+            .outcome_team_ids
+            .iter()
+            .find(|id| !id.is_empty() && !espn.competitors.iter().any(|c| &c.id == *id))
+        {
+            return Some(format!("athlete {} not reported", absent));
+        }
+    }
+
+    // Запись со значением null — игрок не играл (аннулирование);
+    // записи нет — показатель не запрашивался у источника
+    if let Some(MarketKind::PlayerProp {
+        athlete_id, stat, ..
+    }) = &market.kind
+    {
+        if !espn
+            .player_stats
+            .iter()
+            .any(|s| &s.athlete_id == athlete_id && s.stat.eq_ignore_ascii_case(stat))
+        {
+            return Some(format!("{} for athlete {} not reported", stat, athlete_id));
+        }
+    }
+
+    if matches!(market.kind, Some(MarketKind::Outright { .. })) && espn.winner_id.is_empty() {
+        return Some("winner not reported".to_string());
+    }
+
+    let by_score = market
+        .kind
+        .as_ref()
+        .is_some_and(MarketKind::settles_on_score);
+    if by_score {
+        if let Err(reason) = settled_score(market.settlement_rule, espn) {
+            return Some(reason);
+        }
+    }
+    None
+}
+
+/// Определяет итог рынка из сырых данных ESPN + метаданных рынка
+fn determine_winner(market: &Market, espn: &EspnResult) -> (Resolution, f64, String) {
+    // Матч отменён или прерван без результата — рынок аннулируется
+    if VOID_EVENT_STATUSES.contains(&espn.event_status.as_str()) {
+        return (
+            Resolution::Void,
+            1.0,
+            format!("Event {} — market voided", espn.event_status),
         );
-        self.balances.set(sender.clone(), Some(balance - bet_amount));
+    }
 
-        let mut market = self.markets.get(&market_id).expect("Рынок не найден").clone();
+    if espn.event_status != "final" {
+        return (
+            Resolution::Void,
+            0.0,
+            format!("Event not completed (status: {})", espn.event_status),
+        );
+    }
 
-        assert!(market.status == "active", "Рынок не принимает ставки");
-        assert!(now < market.bets_end_date, "Время ставок истекло");
-        assert!(
-            (outcome as usize) < market.outcomes.len(),
-            "Недопустимый исход"
+    if !espn.error.is_empty() {
+        return (
+            Resolution::Void,
+            0.0,
+            format!("ESPN error: {}", espn.error),
         );
+    }
 
-        let pool: u128 = market.outcome_pools[outcome as usize].into();
-        market.outcome_pools[outcome as usize] = U128(pool + bet_amount);
-        let total: u128 = market.total_pool.into();
-        market.total_pool = U128(total + bet_amount);
-        market.total_bets += 1;
+    let scoreless = market.kind.as_ref().is_some_and(MarketKind::is_scoreless);
+    if !scoreless && (espn.home_score < 0 || espn.away_score < 0) {
+        return (
+            Resolution::Void,
+            0.0,
+            "Could not parse scores from ESPN".to_string(),
+        );
+    }
 
-        self.markets.insert(market_id, market);
+    // Счёт по правилу рынка (основное время / с OT / с серией).
+    // Рынки на периоды, участников и статистику правилом не затрагиваются
+    let by_score = market
+        .kind
+        .as_ref()
+        .is_some_and(MarketKind::settles_on_score);
+    let (scored, rule_note) = if by_score {
+        match settled_score(market.settlement_rule, espn) {
+            Ok(scored) => scored,
+            Err(reason) => return (Resolution::Void, 0.0, reason),
+        }
+    } else {
+        (espn.clone(), None)
+    };
+    let espn = &scored;
 
-        let bet = Bet {
-            market_id,
-            user: sender.clone(),
-            outcome,
-            amount: U128(bet_amount),
-            timestamp: now,
-            claimed: false,
-        };
+    let (resolution, reasoning) = match &market.kind {
+        Some(MarketKind::Winner { draw }) => {
+            resolve_winner(&market.outcomes, &market.outcome_team_ids, *draw, espn)
+        }
+        Some(MarketKind::OverUnder { line_x10 }) => {
+            resolve_over_under(*line_x10, espn.home_score, espn.away_score)
+        }
+        Some(MarketKind::BothScore) => resolve_both_score(espn.home_score, espn.away_score),
+        Some(MarketKind::Spread { handicap_x10 }) => {
+            resolve_spread(*handicap_x10, &market.outcome_team_ids, espn)
+        }
+        Some(MarketKind::AsianHandicap { handicap_x100 }) => {
+            resolve_asian_handicap(*handicap_x100, &market.outcome_team_ids, espn)
+        }
+        Some(MarketKind::CorrectScore) => {
+            resolve_correct_score(&market.outcomes, espn.home_score, espn.away_score)
+        }
+        Some(MarketKind::Range) => resolve_range(&market.outcomes, espn.home_score, espn.away_score),
+        Some(MarketKind::PeriodWinner {
+            from_period,
+            to_period,
+            draw,
+        }) => match period_result(espn, *from_period, *to_period) {
+            Some(part) => {
+                let (resolution, reasoning) =
+                    resolve_winner(&market.outcomes, &market.outcome_team_ids, *draw, &part);
+                (
+                    resolution,
+                    format!("Periods {}-{}: {}", from_period, to_period, reasoning),
+                )
+            }
+            None => (
+                Resolution::Void,
+                format!("Period scores missing for periods {}-{}", from_period, to_period),
+            ),
+        },
+        Some(MarketKind::PeriodOverUnder {
+            from_period,
+            to_period,
+            line_x10,
+        }) => match period_result(espn, *from_period, *to_period) {
+            Some(part) => {
+                let (resolution, reasoning) =
+                    resolve_over_under(*line_x10, part.home_score, part.away_score);
+                (
+                    resolution,
+                    format!("Periods {}-{}: {}", from_period, to_period, reasoning),
+                )
+            }
+            None => (
+                Resolution::Void,
+                format!("Period scores missing for periods {}-{}", from_period, to_period),
+            ),
+        },
+        Some(MarketKind::HighestScoringPeriod) => {
+            resolve_highest_scoring_period(&market.outcomes, espn)
+        }
+        Some(MarketKind::HeadToHead { draw }) => resolve_head_to_head(
+            &market.outcomes,
+            &market.outcome_team_ids,
+            *draw,
+            &espn.competitors,
+        ),
+        Some(MarketKind::TopN { n }) => {
+            resolve_top_n(&market.outcome_team_ids, *n, &espn.competitors)
+        }
+        Some(MarketKind::PlayerProp {
+            athlete_id,
+            stat,
+            line_x10,
+        }) => resolve_player_prop(athlete_id, stat, *line_x10, &espn.player_stats),
+        Some(MarketKind::Outright { .. }) => {
+            resolve_outright(&market.outcomes, &market.outcome_team_ids, &espn.winner_id)
+        }
+        None => (Resolution::Void, "Market has no kind".to_string()),
+    };
 
-        let mut bets = self.market_bets.get(&market_id).cloned().unwrap_or_default();
-        bets.push(bet.clone());
-        self.market_bets.set(market_id, Some(bets));
+    let confidence = match resolution {
+        Resolution::Void => 0.0,
+        _ => 1.0,
+    };
+    let reasoning = match rule_note {
+        Some(note) => format!("{} | {}", note, reasoning),
+        None => reasoning,
+    };
+    (resolution, confidence, reasoning)
+}
 
-        let mut user_bets = self.user_bets.get(&sender).cloned().unwrap_or_default();
-        user_bets.push(bet);
-        self.user_bets.set(sender.clone(), Some(user_bets));
+// ══════════════════════════════════════════════════════════════════
 
-        self.total_volume += bet_amount;
+#[near]
+impl NearCast {
+    /// Инициализация контракта (ignore_state — для миграции)
+    #[init(ignore_state)]
+    pub fn new(oracle: Option<AccountId>) -> Self {
+        let owner = env::predecessor_account_id();
+        Self {
+            owner: owner.clone(),
+            oracle: oracle.unwrap_or(owner),
+            markets: IterableMap::new(StorageKey::MarketsV2),
+            legacy_markets: IterableMap::new(StorageKey::Markets),
+            legacy_market_bets: LookupMap::new(StorageKey::MarketBets),
+            legacy_user_bets: LookupMap::new(StorageKey::UserBets),
+            balances: LookupMap::new(StorageKey::Balances),
+            pending_withdrawals: LookupMap::new(StorageKey::PendingWithdrawals),
+            market_count: 0,
+            total_volume: 0,
+            fee_config: FeeConfig {
+                protocol_fee_bps: 0,
+                max_creator_fee_bps: 0,
+            },
+            treasury: 0,
+            collateral_tokens: IterableMap::new(StorageKey::CollateralTokens),
+            ft_balances: LookupMap::new(StorageKey::FtBalances),
+            ft_treasury: LookupMap::new(StorageKey::FtTreasury),
+            outlayer_source_repo: String::new(),
+            outlayer_source_commit: "main".to_string(),
+            outlayer_contract: "outlayer.testnet".parse().unwrap(),
+            reclaim_contract: "reclaim-protocol.testnet".parse().unwrap(),
+            tls_oracle_contract: "tls-oracle-v2.nearcast-oracle.testnet"
+                .parse()
+                .unwrap(),
+            positions: LookupMap::new(StorageKey::Positions),
+            bet_log: LookupMap::new(StorageKey::BetLog),
+            user_markets: LookupMap::new(StorageKey::UserMarkets),
+            claimed_stakes: LookupMap::new(StorageKey::ClaimedStakes),
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts),
+            creation_bond: 0,
+            moderators: IterableSet::new(StorageKey::Moderators),
+            locked_bonds: LookupMap::new(StorageKey::LockedBonds),
+            resolution_config: ResolutionConfig {
+                proposal_bond: U128(DEFAULT_PROPOSAL_BOND),
+                dispute_window: DEFAULT_DISPUTE_WINDOW,
+                challenge_window: DEFAULT_CHALLENGE_WINDOW,
+                resolver_reward_bps: 0,
+            },
+            proposals: LookupMap::new(StorageKey::Proposals),
+            score_votes: LookupMap::new(StorageKey::ScoreVotes),
+            resolution_requests: LookupMap::new(StorageKey::ResolutionRequests),
+        }
+    }
 
+    /// Миграция со старого Borsh-layout (Vec<Bet> на рынок и пользователя,
+    /// статус рынка строкой). Переписывается только корень состояния:
+    /// рынки остаются под прежним префиксом и переносятся в новый layout
+    /// постранично через migrate_markets, затем старые ставки —
+    /// в позиции через migrate_market_bets.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let old: OldNearCast = env::state_read().expect("Нет состояния для миграции");
         log!(
-            "Ставка: {} поставил {} на исход #{} рынка #{}",
-            sender,
-            bet_amount,
-            outcome,
-            market_id
+            "Миграция: {} рынков ждут переноса в новый формат",
+            old.markets.len()
         );
+
+        Self {
+            owner: old.owner,
+            oracle: old.oracle,
+            markets: IterableMap::new(StorageKey::MarketsV2),
+            legacy_markets: old.markets,
+            legacy_market_bets: old.market_bets,
+            legacy_user_bets: old.user_bets,
+            balances: old.balances,
+            pending_withdrawals: LookupMap::new(StorageKey::PendingWithdrawals),
+            market_count: old.market_count,
+            total_volume: old.total_volume,
+            fee_config: FeeConfig {
+                protocol_fee_bps: 0,
+                max_creator_fee_bps: 0,
+            },
+            treasury: 0,
+            collateral_tokens: IterableMap::new(StorageKey::CollateralTokens),
+            ft_balances: LookupMap::new(StorageKey::FtBalances),
+            ft_treasury: LookupMap::new(StorageKey::FtTreasury),
+            outlayer_source_repo: old.outlayer_source_repo,
+            outlayer_source_commit: old.outlayer_source_commit,
+            outlayer_contract: old.outlayer_contract,
+            reclaim_contract: old.reclaim_contract,
+            tls_oracle_contract: old.tls_oracle_contract,
+            positions: LookupMap::new(StorageKey::Positions),
+            bet_log: LookupMap::new(StorageKey::BetLog),
+            user_markets: LookupMap::new(StorageKey::UserMarkets),
+            claimed_stakes: LookupMap::new(StorageKey::ClaimedStakes),
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts),
+            creation_bond: 0,
+            moderators: IterableSet::new(StorageKey::Moderators),
+            locked_bonds: LookupMap::new(StorageKey::LockedBonds),
+            resolution_config: ResolutionConfig {
+                proposal_bond: U128(DEFAULT_PROPOSAL_BOND),
+                dispute_window: DEFAULT_DISPUTE_WINDOW,
+                challenge_window: DEFAULT_CHALLENGE_WINDOW,
+                resolver_reward_bps: 0,
+            },
+            proposals: LookupMap::new(StorageKey::Proposals),
+            score_votes: LookupMap::new(StorageKey::ScoreVotes),
+            resolution_requests: LookupMap::new(StorageKey::ResolutionRequests),
+        }
     }
 
-    // ══════════════════════════════════════════════════════════════
-    // ESPN ORACLE — permissionless разрешение через OutLayer TEE
-    //
-    // Кто угодно может вызвать request_resolution с депозитом 0.1 NEAR.
-    // Контракт вызовет OutLayer, WASM Worker в TEE получит счёт из ESPN,
-    // и контракт ON-CHAIN определит победителя по именам команд.
-    // ══════════════════════════════════════════════════════════════
+    /// Переписывает до limit рынков старого layout в новый.
+    /// Возвращает число перенесённых рынков; 0 — перенос завершён.
+    pub fn migrate_markets(&mut self, limit: u32) -> u32 {
+        assert!(
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
+        );
+        assert!(limit > 0, "limit должен быть положительным");
 
-    /// Permissionless: кто угодно вызывает для рынков с ESPN данными
-    #[payable]
-    pub fn request_resolution(&mut self, market_id: u64) -> Promise {
-        let deposit = env::attached_deposit().as_yoctonear();
+        let ids: Vec<u64> = self
+            .legacy_markets
+            .keys()
+            .take(limit as usize)
+            .copied()
+            .collect();
+        for id in &ids {
+            let market = self
+                .legacy_markets
+                .remove(id)
+                .expect("Рынок не найден")
+                .into_market();
+            self.markets.insert(market.id, market);
+        }
+        log!(
+            "Миграция: {} рынков переписано в новый формат, осталось {}",
+            ids.len(),
+            self.legacy_markets.len()
+        );
+        ids.len() as u32
+    }
+
+    /// Переносит старые ставки рынка [from_index, from_index + limit)
+    /// в позиции и журнал ставок. Страницы идут подряд с нуля; после
+    /// последней старый Vec<Bet> удаляется и открываются выплаты по рынку.
+    /// Возвращает число перенесённых ставок.
+    pub fn migrate_market_bets(&mut self, market_id: u64, from_index: u32, limit: u32) -> u32 {
         assert!(
-            deposit >= OUTLAYER_MIN_DEPOSIT,
-            "Минимальный депозит: 0.1 NEAR для OutLayer"
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
         );
+        assert!(limit > 0, "limit должен быть положительным");
         assert!(
-            !self.outlayer_source_repo.is_empty(),
-            "OutLayer source не настроен"
+            self.legacy_markets.is_empty(),
+            "Сначала перенесите рынки через migrate_markets"
         );
-
-        let market = self.markets.get(&market_id).expect("Рынок не найден").clone();
+        let legacy = self
+            .legacy_market_bets
+            .get(&market_id)
+            .expect("Нет старых ставок для рынка");
+        let total = legacy.len();
         assert!(
-            !market.espn_event_id.is_empty(),
-            "Рынок не спортивный (нет espn_event_id)"
+            (from_index as usize) < total,
+            "from_index за пределами ставок рынка ({})",
+            total
         );
+        // Страница не должна повторяться или пропускать ставки
         assert!(
-            market.status == "active" || market.status == "closed",
-            "Рынок уже разрешён или аннулирован"
+            !self.bet_log.contains_key(&(market_id, from_index))
+                && (from_index == 0 || self.bet_log.contains_key(&(market_id, from_index - 1))),
+            "Следующая страница миграции рынка #{} начинается не с {}",
+            market_id,
+            from_index
         );
 
-        let now = env::block_timestamp();
-        assert!(
-            now >= market.resolution_date,
-            "Время разрешения ещё не наступило"
+        let to = total.min(from_index as usize + limit as usize);
+        // Копируется только страница, а не весь список ставок рынка
+        let page: Vec<Bet> = legacy[from_index as usize..to].to_vec();
+        let mut claimed: u128 = self.claimed_stakes.get(&market_id).copied().unwrap_or(0);
+        let mut users: Vec<AccountId> = Vec::new();
+        for (i, bet) in (from_index..).zip(page) {
+            self.bet_log.set((market_id, i), Some(bet.clone()));
+            self.internal_add_position(
+                market_id,
+                &bet.user,
+                bet.outcome,
+                bet.amount.into(),
+                bet.timestamp,
+            );
+            if bet.claimed {
+                let key = (market_id, bet.user.clone(), bet.outcome);
+                if let Some(position) = self.positions.get_mut(&key) {
+                    position.claimed = true;
+                }
+                claimed += u128::from(bet.amount);
+            }
+            if !users.contains(&bet.user) {
+                users.push(bet.user.clone());
+            }
+        }
+        self.claimed_stakes.set(market_id, Some(claimed));
+
+        // Ставки рынка уходят из старых списков пользователей страницы —
+        // дальше их показывают позиции
+        for user in users {
+            let mut user_bets = self
+                .legacy_user_bets
+                .get(&user)
+                .cloned()
+                .unwrap_or_default();
+            user_bets.retain(|b| b.market_id != market_id);
+            if user_bets.is_empty() {
+                self.legacy_user_bets.remove(&user);
+            } else {
+                self.legacy_user_bets.set(user, Some(user_bets));
+            }
+        }
+
+        let done = to == total;
+        if done {
+            self.legacy_market_bets.remove(&market_id);
+        }
+        log!(
+            "Миграция: рынок #{} — ставки {}..{} из {} перенесены в позиции{}",
+            market_id,
+            from_index,
+            to,
+            total,
+            if done { ", перенос завершён" } else { "" }
         );
+        (to - from_index as usize) as u32
+    }
 
-        // Входные данные для Worker — только ESPN координаты
-        let worker_input = WorkerInput {
-            espn_event_id: market.espn_event_id.clone(),
-            sport: market.sport.clone(),
-            league: market.league.clone(),
-        };
-        let input_data = serde_json::to_string(&worker_input).unwrap();
+    // ══════════════════════════════════════════════════════════════
+    // ВНУТРЕННИЙ БАЛАНС — deposit / withdraw
+    // ══════════════════════════════════════════════════════════════
 
-        // Формируем source для OutLayer
-        let source = OutLayerSource {
-            github: Some(GitHubSource {
-                repo: self.outlayer_source_repo.clone(),
-                commit: self.outlayer_source_commit.clone(),
-                build_target: "wasm32-wasip2".to_string(),
-            }),
-        };
+    /// Пополнение внутреннего баланса
+    #[payable]
+    pub fn deposit(&mut self) {
+        let sender = env::predecessor_account_id();
+        let amount = env::attached_deposit().as_yoctonear();
+        assert!(amount > 0, "Прикрепите NEAR для пополнения");
 
-        let limits = ResourceLimits {
-            max_instructions: 1_000_000_000,
-            max_memory_mb: 128,
-            max_execution_seconds: 60,
-        };
+        let initial_storage = env::storage_usage();
+        self.internal_deposit(&sender, &None, amount);
+        self.internal_charge_storage(&sender, initial_storage);
 
-        // Аргументы для OutLayer request_execution
-        let args = serde_json::json!({
-            "source": source,
-            "resource_limits": limits,
-            "input_data": input_data,
-            "response_format": "Json",
-        });
+        log!("Депозит: {} пополнил баланс на {} yoctoNEAR", sender, amount);
+    }
+
+    /// NEP-141: пополнение баланса в токене из белого списка.
+    /// Вызывается контрактом токена после ft_transfer_call; msg должен быть пустым.
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let token_id = env::predecessor_account_id();
+        assert!(
+            self.collateral_tokens.contains_key(&token_id),
+            "Токен {} не принимается в качестве залога",
+            token_id
+        );
+        assert!(msg.is_empty(), "Неизвестный msg для ft_on_transfer");
+        assert!(amount.0 > 0, "Сумма должна быть положительной");
 
-        let callback_args = ResolutionCallbackArgs { market_id };
+        let initial_storage = env::storage_usage();
+        self.internal_deposit(&sender_id, &Some(token_id.clone()), amount.0);
+        self.internal_charge_storage(&sender_id, initial_storage);
 
         log!(
-            "OutLayer запрос для рынка #{} (ESPN: {})",
-            market_id,
-            market.espn_event_id
+            "Депозит: {} пополнил баланс на {} {}",
+            sender_id,
+            amount.0,
+            token_id
         );
+        PromiseOrValue::Value(U128(0))
+    }
 
-        // Cross-contract call: OutLayer → callback
-        Promise::new(self.outlayer_contract.clone())
-            .function_call(
-                "request_execution".to_string(),
-                serde_json::to_vec(&args).unwrap(),
-                NearToken::from_yoctonear(deposit),
-                GAS_FOR_OUTLAYER,
-            )
-            .then(
-                Promise::new(env::current_account_id()).function_call(
-                    "on_resolution_result".to_string(),
-                    serde_json::to_vec(&callback_args).unwrap(),
-                    NearToken::from_yoctonear(0),
-                    GAS_FOR_CALLBACK,
-                ),
-            )
+    /// Вывод средств с внутреннего баланса на кошелёк
+    /// (token_id — NEP-141 токен, без него — NEAR)
+    ///
+    /// Баланс списывается сразу, а при неудачном transfer / ft_transfer
+    /// возвращается в on_withdraw_complete.
+    pub fn withdraw(&mut self, amount: U128, token_id: Option<AccountId>) -> Promise {
+        let initial_storage = env::storage_usage();
+        let sender = env::predecessor_account_id();
+        let withdraw_amount: u128 = amount.into();
+        assert!(withdraw_amount > 0, "Сумма должна быть положительной");
+        assert!(
+            self.pending_withdrawals.get(&sender).is_none(),
+            "Предыдущий вывод ещё не завершён"
+        );
+
+        self.internal_withdraw(&sender, &token_id, withdraw_amount);
+        self.pending_withdrawals.set(
+            sender.clone(),
+            Some(PendingWithdrawal {
+                amount,
+                token_id: token_id.clone(),
+                requested_at: env::block_timestamp(),
+            }),
+        );
+        self.internal_charge_storage(&sender, initial_storage);
+
+        let callback_args = serde_json::json!({
+            "account_id": sender,
+            "amount": amount,
+            "token_id": token_id,
+        });
+
+        log!(
+            "Вывод: {} выводит {} {}",
+            sender,
+            withdraw_amount,
+            token_label(&token_id)
+        );
+        transfer_promise(&sender, &token_id, withdraw_amount).then(
+            Promise::new(env::current_account_id()).function_call(
+                "on_withdraw_complete".to_string(),
+                serde_json::to_vec(&callback_args).unwrap(),
+                NearToken::from_yoctonear(0),
+                GAS_FOR_WITHDRAW_CALLBACK,
+            ),
+        )
     }
 
-    /// Callback от OutLayer — парсит сырые данные ESPN, определяет победителя ON-CHAIN
+    /// Callback после transfer — при ошибке возвращает сумму на баланс
     #[private]
-    pub fn on_resolution_result(&mut self, market_id: u64) -> String {
+    pub fn on_withdraw_complete(
+        &mut self,
+        account_id: AccountId,
+        amount: U128,
+        token_id: Option<AccountId>,
+    ) -> bool {
         assert_eq!(
             env::promise_results_count(),
             1,
             "Ожидается один результат"
         );
 
-        let result = env::promise_result_checked(0, 1_000_000);
-        match result {
-            Ok(data) => {
-                let result_str =
-                    String::from_utf8(data).unwrap_or_else(|_| "invalid utf8".to_string());
+        let initial_storage = env::storage_usage();
+        self.pending_withdrawals.remove(&account_id);
+        if self.storage_accounts.contains_key(&account_id) {
+            self.internal_charge_storage(&account_id, initial_storage);
+        }
 
-                match serde_json::from_str::<EspnResult>(&result_str) {
-                    Ok(espn_result) => {
-                        // Получаем рынок для доступа к outcomes и market_type
-                        let market = match self.markets.get(&market_id) {
-                            Some(m) => m.clone(),
-                            None => {
-                                log!("OutLayer: рынок #{} не найден", market_id);
-                                return format!("Market #{} not found", market_id);
-                            }
-                        };
-
-                        // Определяем победителя ON-CHAIN
-                        let (winning_outcome, confidence, reasoning) =
-                            determine_winner(&market, &espn_result);
-
-                        self.apply_resolution(
-                            market_id,
-                            winning_outcome,
-                            confidence,
-                            &reasoning,
-                            &espn_result.event_status,
-                            espn_result.home_score,
-                            espn_result.away_score,
-                        );
+        if env::promise_result_checked(0, 0).is_ok() {
+            return true;
+        }
 
-                        log!(
-                            "OutLayer: рынок #{} — исход={}, счёт={}:{}, {}",
-                            market_id,
-                            winning_outcome,
-                            espn_result.home_score,
-                            espn_result.away_score,
-                            reasoning
-                        );
-                        format!(
-                            "Resolved: outcome={}, score={}:{}, {}",
-                            winning_outcome,
-                            espn_result.home_score,
-                            espn_result.away_score,
-                            reasoning
-                        )
-                    }
-                    Err(e) => {
-                        log!(
-                            "OutLayer: ошибка парсинга для рынка #{}: {}",
-                            market_id,
-                            e
-                        );
-                        format!("Parse error: {}", e)
-                    }
-                }
-            }
-            Err(_) => {
-                log!("OutLayer: вызов не удался для рынка #{}", market_id);
-                "OutLayer call failed".to_string()
-            }
+        let refund: u128 = amount.into();
+        self.internal_deposit(&account_id, &token_id, refund);
+
+        log!(
+            "Вывод не удался: {} {} возвращены на баланс {}",
+            refund,
+            token_label(&token_id),
+            account_id
+        );
+        false
+    }
+
+    /// Баланс аккаунта в NEAR (token_id = None) или NEP-141 токене
+    fn internal_balance(&self, account_id: &AccountId, token_id: &Option<AccountId>) -> u128 {
+        match token_id {
+            None => self.balances.get(account_id).copied().unwrap_or(0),
+            Some(token) => self
+                .ft_balances
+                .get(&(account_id.clone(), token.clone()))
+                .copied()
+                .unwrap_or(0),
         }
     }
 
-    /// Применяет результат к рынку
-    fn apply_resolution(
+    fn internal_deposit(
         &mut self,
-        market_id: u64,
-        winning_outcome: i32,
-        confidence: f64,
-        reasoning: &str,
-        event_status: &str,
-        home_score: i32,
-        away_score: i32,
+        account_id: &AccountId,
+        token_id: &Option<AccountId>,
+        amount: u128,
     ) {
-        let mut market = match self.markets.get(&market_id) {
-            Some(m) => m.clone(),
-            None => return,
-        };
+        let current = self.internal_balance(account_id, token_id);
+        match token_id {
+            None => self
+                .balances
+                .set(account_id.clone(), Some(current + amount)),
+            Some(token) => self
+                .ft_balances
+                .set((account_id.clone(), token.clone()), Some(current + amount)),
+        }
+    }
 
-        if market.status != "active" && market.status != "closed" {
-            return;
+    fn internal_withdraw(
+        &mut self,
+        account_id: &AccountId,
+        token_id: &Option<AccountId>,
+        amount: u128,
+    ) {
+        let current = self.internal_balance(account_id, token_id);
+        assert!(current >= amount, "Недостаточно средств на балансе");
+        match token_id {
+            None => self
+                .balances
+                .set(account_id.clone(), Some(current - amount)),
+            Some(token) => self
+                .ft_balances
+                .set((account_id.clone(), token.clone()), Some(current - amount)),
         }
+    }
 
-        if event_status != "final" {
-            log!(
-                "OutLayer: матч не завершён для рынка #{} (status: {})",
-                market_id,
-                event_status
+    fn treasury_balance(&self, token_id: &Option<AccountId>) -> u128 {
+        match token_id {
+            None => self.treasury,
+            Some(token) => self.ft_treasury.get(token).copied().unwrap_or(0),
+        }
+    }
+
+    fn set_treasury_balance(&mut self, token_id: &Option<AccountId>, amount: u128) {
+        match token_id {
+            None => self.treasury = amount,
+            Some(token) => self.ft_treasury.set(token.clone(), Some(amount)),
+        }
+    }
+
+    // ══════════════════════════════════════════════════════════════
+    // СОЗДАНИЕ РЫНКА
+    // ══════════════════════════════════════════════════════════════
+
+    /// Создание рынка — требует залог ровно creation_bond: attached NEAR,
+    /// недостающее списывается с внутреннего баланса NEAR (вызов
+    /// function-call ключом без депозита), излишек attached зачисляется на него
+    #[payable]
+    pub fn create_market(
+        &mut self,
+        question: String,
+        description: String,
+        outcomes: Vec<String>,
+        category: String,
+        bets_end_date: String,
+        resolution_date: String,
+        espn_event_id: Option<String>,
+        sport: Option<String>,
+        league: Option<String>,
+        market_type: Option<String>,
+        kind: Option<MarketKind>,
+        creator_fee_bps: Option<u16>,
+        collateral_token: Option<AccountId>,
+        resolution_policy: Option<ResolutionPolicy>,
+        resolution_deadline: Option<String>,
+        outcome_team_ids: Option<Vec<String>>,
+        settlement_rule: Option<SettlementRule>,
+    ) -> u64 {
+        let sender = env::predecessor_account_id();
+        let now = env::block_timestamp();
+        let initial_storage = env::storage_usage();
+        let attached = env::attached_deposit().as_yoctonear();
+        let bond = self.creation_bond;
+        if bond > attached {
+            assert!(
+                self.internal_balance(&sender, &None) >= bond - attached,
+                "Залог за создание рынка: {} yoctoNEAR (attached или с баланса NEAR)",
+                self.creation_bond
             );
-            return;
+            self.internal_withdraw(&sender, &None, bond - attached);
+        } else if attached > bond {
+            self.internal_deposit(&sender, &None, attached - bond);
         }
 
-        if winning_outcome == -1 || confidence < 0.3 {
-            market.resolved_outcome = -2;
-            market.status = "voided".to_string();
-            self.markets.insert(market_id, market);
-            log!(
-                "Рынок #{} аннулирован через OutLayer: {}",
-                market_id,
-                reasoning
+        let bets_end: u64 = bets_end_date.parse().expect("Невалидный bets_end_date");
+        let resolution: u64 = resolution_date.parse().expect("Невалидный resolution_date");
+        let deadline: u64 = match resolution_deadline {
+            Some(d) => d.parse().expect("Невалидный resolution_deadline"),
+            None => resolution + DEFAULT_RESOLUTION_GRACE,
+        };
+
+        // Валидация
+        assert!(
+            !question.is_empty() && question.len() <= 500,
+            "Вопрос: от 1 до 500 символов"
+        );
+        assert!(description.len() <= 2000, "Описание: до 2000 символов");
+        assert!(
+            outcomes.len() >= MIN_OUTCOMES && outcomes.len() <= MAX_OUTCOMES,
+            "Количество исходов: от {} до {}",
+            MIN_OUTCOMES,
+            MAX_OUTCOMES
+        );
+        let creator_fee_bps = creator_fee_bps.unwrap_or(0);
+        assert!(
+            creator_fee_bps <= self.fee_config.max_creator_fee_bps,
+            "Комиссия создателя: до {} bps",
+            self.fee_config.max_creator_fee_bps
+        );
+        if let Some(ref token) = collateral_token {
+            assert!(
+                self.collateral_tokens.contains_key(token),
+                "Токен {} не принимается в качестве залога",
+                token
             );
-        } else if winning_outcome >= 0
-            && (winning_outcome as usize) < market.outcomes.len()
-        {
-            market.resolved_outcome = winning_outcome;
-            market.status = "resolved".to_string();
+        }
+        // Тип спортивного рынка: явный kind или из строкового market_type
+        assert!(
+            kind.is_none() || espn_event_id.is_some(),
+            "Тип рынка (kind) задаётся только для спортивных рынков"
+        );
+        let kind = espn_event_id.as_ref().map(|_| {
+            let kind = kind.unwrap_or_else(|| {
+                MarketKind::from_legacy(market_type.as_deref().unwrap_or("winner"), &outcomes)
+            });
+            kind.validate(&outcomes, outcome_team_ids.as_deref().unwrap_or(&[]));
+            kind
+        });
+        if let Some(ref team_ids) = outcome_team_ids {
+            assert!(
+                espn_event_id.is_some(),
+                "Привязка команд задаётся только для спортивных рынков"
+            );
+            assert!(
+                team_ids.len() == outcomes.len(),
+                "ESPN team id нужен для каждого исхода (\"\" — не команда)"
+            );
+            let teams: Vec<&String> = team_ids.iter().filter(|t| !t.is_empty()).collect();
+            assert!(teams.len() >= 2, "Нужны team id минимум двух команд");
+            for (i, t) in teams.iter().enumerate() {
+                assert!(
+                    !teams[i + 1..].contains(t),
+                    "ESPN team id должны быть уникальными"
+                );
+            }
+        }
+        assert!(
+            settlement_rule.is_none() || espn_event_id.is_some(),
+            "Правило расчёта задаётся только для спортивных рынков"
+        );
+        let settlement_rule = settlement_rule
+            .unwrap_or_else(|| SettlementRule::default_for(sport.as_deref().unwrap_or("")));
+        if let Some(ref policy) = resolution_policy {
+            assert!(
+                espn_event_id.is_some(),
+                "Политика разрешения задаётся только для спортивных рынков"
+            );
+            assert!(
+                policy.required >= 1 && policy.required as usize <= policy.sources.len(),
+                "Кворум: от 1 до {} источников",
+                policy.sources.len()
+            );
+            for (i, src) in policy.sources.iter().enumerate() {
+                assert!(
+                    !policy.sources[i + 1..].contains(src),
+                    "Источники разрешения должны быть уникальными"
+                );
+            }
+        }
+        assert!(bets_end > now, "Дедлайн ставок должен быть в будущем");
+        assert!(
+            resolution > bets_end,
+            "Дата разрешения должна быть после дедлайна ставок"
+        );
+        assert!(
+            deadline > resolution,
+            "Срок разрешения должен быть после даты разрешения"
+        );
 
-            let outcome_name = market.outcomes[winning_outcome as usize].clone();
-            self.markets.insert(market_id, market);
-            log!(
-                "Рынок #{} разрешён через OutLayer: исход #{} (\"{}\") | {}:{} | {}",
-                market_id,
-                winning_outcome,
-                outcome_name,
-                home_score,
-                away_score,
-                reasoning
+        for (i, o) in outcomes.iter().enumerate() {
+            assert!(
+                !o.is_empty() && o.len() <= 200,
+                "Каждый исход: от 1 до 200 символов"
             );
+            for other in &outcomes[i + 1..] {
+                assert!(o != other, "Исходы должны быть уникальными");
+            }
+        }
+
+        let id = self.market_count;
+        self.market_count += 1;
+
+        let outcome_pools = vec![U128(0); outcomes.len()];
+
+        let market = Market {
+            id,
+            creator: sender.clone(),
+            question: question.clone(),
+            description,
+            outcomes: outcomes.clone(),
+            outcome_pools,
+            total_pool: U128(0),
+            category,
+            created_at: now,
+            bets_end_date: bets_end,
+            resolution_date: resolution,
+            resolved_outcome: None,
+            status: MarketStatus::Active,
+            total_bets: 0,
+            espn_event_id: espn_event_id.unwrap_or_default(),
+            sport: sport.unwrap_or_default(),
+            league: league.unwrap_or_default(),
+            market_type: match kind {
+                Some(ref k) => k.as_str().to_string(),
+                None => market_type.unwrap_or_else(|| "winner".to_string()),
+            },
+            protocol_fee_bps: self.fee_config.protocol_fee_bps,
+            creator_fee_bps,
+            fees_collected: U128(0),
+            collateral_token,
+            creator_bond: U128(bond),
+            resolution_policy: resolution_policy.unwrap_or_default(),
+            resolution_deadline: deadline,
+            outcome_team_ids: outcome_team_ids.unwrap_or_default(),
+            kind,
+            settlement: Vec::new(),
+            settlement_rule,
+        };
+
+        self.markets.insert(id, market);
+        if bond > 0 {
+            let locked = self.locked_bonds.get(&sender).copied().unwrap_or(0);
+            self.locked_bonds.set(sender.clone(), Some(locked + bond));
         }
+        self.internal_charge_storage(&sender, initial_storage);
+
+        log!(
+            "Рынок создан: #{} — \"{}\" ({} исходов)",
+            id,
+            question,
+            outcomes.len()
+        );
+        id
     }
 
     // ══════════════════════════════════════════════════════════════
-    // TLS ORACLE — альтернативное разрешение через MPC-TLS + ZK proof
-    //
-    // Relayer вызывает resolve_with_tls_attestation, передавая attestation_id
-    // и распарсенные off-chain данные. Контракт верифицирует данные через
-    // cross-contract view call к TLS Oracle (сравнивает с response_data).
+    // СТАВКИ (списание с внутреннего баланса)
     // ══════════════════════════════════════════════════════════════
 
-    /// Разрешить рынок через TLS Oracle аттестацию
-    ///
-    /// Делает cross-contract view call к TLS Oracle для верификации данных.
-    /// Любой может вызвать — permissionless (аналог request_resolution).
-    pub fn resolve_with_tls_attestation(
-        &mut self,
-        market_id: u64,
-        attestation_id: u64,
-        home_score: i32,
-        away_score: i32,
-        home_team: String,
-        away_team: String,
-        event_status: String,
-    ) -> Promise {
-        let market = self
+    pub fn place_bet(&mut self, market_id: u64, outcome: u32, amount: U128) {
+        let bet_amount: u128 = amount.into();
+        let sender = env::predecessor_account_id();
+        let now = env::block_timestamp();
+        let initial_storage = env::storage_usage();
+
+        let mut market = self
             .markets
             .get(&market_id)
             .expect("Рынок не найден")
             .clone();
+
+        // Ставка списывается в токене залога рынка — пулы строго в одном токене
+        let token_id = market.collateral_token.clone();
+        match token_id {
+            None => assert!(bet_amount >= MIN_BET, "Минимальная ставка: 0.1 NEAR"),
+            Some(ref token) => {
+                let min_bet: u128 = self
+                    .collateral_tokens
+                    .get(token)
+                    .map(|m| m.0)
+                    .expect("Токен залога рынка удалён из белого списка");
+                assert!(
+                    bet_amount >= min_bet,
+                    "Минимальная ставка: {} {}",
+                    min_bet,
+                    token
+                );
+            }
+        }
+
+        let balance = self.internal_balance(&sender, &token_id);
         assert!(
-            !market.espn_event_id.is_empty(),
-            "Рынок не спортивный (нет espn_event_id)"
-        );
-        assert!(
-            market.status == "active" || market.status == "closed",
-            "Рынок уже разрешён или аннулирован"
+            balance >= bet_amount,
+            "Недостаточно средств. Пополните депозит."
         );
+        self.internal_withdraw(&sender, &token_id, bet_amount);
 
-        let now = env::block_timestamp();
         assert!(
-            now >= market.resolution_date,
-            "Время разрешения ещё не наступило"
+            market.status == MarketStatus::Active,
+            "Рынок не принимает ставки"
         );
-
-        log!(
-            "TLS Oracle запрос для рынка #{} (attestation #{})",
-            market_id,
-            attestation_id
+        assert!(now < market.bets_end_date, "Время ставок истекло");
+        assert!(
+            (outcome as usize) < market.outcomes.len(),
+            "Недопустимый исход"
         );
 
-        // Аргументы для callback — передаём ожидаемые данные для верификации
-        let callback_args = serde_json::json!({
-            "market_id": market_id,
-            "attestation_id": attestation_id,
-            "expected_home_score": home_score,
-            "expected_away_score": away_score,
-            "_expected_home_team": home_team,
-            "_expected_away_team": away_team,
-            "_expected_event_status": event_status,
+        let pool: u128 = market.outcome_pools[outcome as usize].into();
+        market.outcome_pools[outcome as usize] = U128(pool + bet_amount);
+        let total: u128 = market.total_pool.into();
+        market.total_pool = U128(total + bet_amount);
+        let bet_index = market.total_bets;
+        market.total_bets += 1;
+
+        self.markets.insert(market_id, market);
+
+        let bet = Bet {
+            market_id,
+            user: sender.clone(),
+            outcome,
+            amount: U128(bet_amount),
+            timestamp: now,
+            claimed: false,
+        };
+        self.bet_log.set((market_id, bet_index), Some(bet));
+        self.internal_add_position(market_id, &sender, outcome, bet_amount, now);
+        self.internal_charge_storage(&sender, initial_storage);
+
+        if token_id.is_none() {
+            self.total_volume += bet_amount;
+        }
+
+        log!(
+            "Ставка: {} поставил {} на исход #{} рынка #{}",
+            sender,
+            bet_amount,
+            outcome,
+            market_id
+        );
+    }
+
+    /// Добавляет ставку к позиции (market_id, аккаунт, исход)
+    fn internal_add_position(
+        &mut self,
+        market_id: u64,
+        account_id: &AccountId,
+        outcome: u32,
+        amount: u128,
+        timestamp: u64,
+    ) {
+        let key = (market_id, account_id.clone(), outcome);
+        let mut position = self.positions.get(&key).cloned().unwrap_or(Position {
+            stake: U128(0),
+            claimed: false,
+            last_bet_at: 0,
         });
+        position.stake = U128(position.stake.0 + amount);
+        position.last_bet_at = timestamp;
+        self.positions.set(key, Some(position));
+
+        let mut markets = self
+            .user_markets
+            .get(account_id)
+            .cloned()
+            .unwrap_or_default();
+        if !markets.contains(&market_id) {
+            markets.push(market_id);
+            self.user_markets.set(account_id.clone(), Some(markets));
+        }
+    }
 
-        // Cross-contract view call к TLS Oracle: get_attestation(id)
-        let view_args = serde_json::json!({ "id": attestation_id });
+    /// Permissionless: фиксирует закрытие ставок on-chain после bets_end_date
+    pub fn close_market(&mut self, market_id: u64) {
+        let mut market = self.markets.get(&market_id).expect("Рынок не найден").clone();
+        assert!(
+            market.status == MarketStatus::Active,
+            "Рынок не принимает ставки"
+        );
+        assert!(
+            env::block_timestamp() >= market.bets_end_date,
+            "Время ставок ещё не истекло"
+        );
 
-        Promise::new(self.tls_oracle_contract.clone())
+        market.transition(MarketStatus::Closed);
+        self.markets.insert(market_id, market);
+
+        log!("Рынок #{} закрыт для ставок", market_id);
+    }
+
+    /// Отмена невалидного рынка владельцем или модератором:
+    /// ставки возвращаются, залог создателя уходит в казну
+    pub fn cancel_market(&mut self, market_id: u64, reason: String) {
+        let caller = env::predecessor_account_id();
+        assert!(
+            caller == self.owner || self.moderators.contains(&caller),
+            "Только владелец или модератор"
+        );
+
+        let mut market = self
+            .markets
+            .get(&market_id)
+            .expect("Рынок не найден")
+            .clone();
+        assert!(
+            matches!(
+                market.status,
+                MarketStatus::Active | MarketStatus::Closed | MarketStatus::ResolutionPending
+            ),
+            "Рынок уже разрешён или аннулирован"
+        );
+
+        market.resolved_outcome = None;
+        market.transition(MarketStatus::Voided);
+        self.release_creator_bond(&mut market, true);
+        self.markets.insert(market_id, market);
+
+        log!("Рынок #{} отменён {}: {}", market_id, caller, reason);
+    }
+
+    /// Permissionless: аннулирует рынок, не разрешённый к resolution_deadline.
+    /// Ставки возвращаются через claim_winnings, залог создателю,
+    /// залоги зависшего предложения и спора — их сторонам.
+    /// Предложение с открытым окном оспаривания не аннулируется —
+    /// его сначала финализирует finalize_proposal или разбирает арбитраж
+    pub fn void_expired_market(&mut self, market_id: u64) {
+        let mut market = self
+            .markets
+            .get(&market_id)
+            .expect("Рынок не найден")
+            .clone();
+        let now = env::block_timestamp();
+        assert!(
+            now >= market.resolution_deadline,
+            "Срок разрешения ещё не истёк"
+        );
+        assert!(
+            matches!(
+                market.status,
+                MarketStatus::Active
+                    | MarketStatus::Closed
+                    | MarketStatus::ResolutionPending
+                    | MarketStatus::Proposed
+                    | MarketStatus::Disputed
+            ),
+            "Рынок уже разрешён или аннулирован"
+        );
+
+        if let Some(proposal) = self.proposals.get(&market_id) {
+            assert!(
+                market.status != MarketStatus::Proposed || now >= proposal.dispute_deadline,
+                "Окно для оспаривания ещё открыто — дождитесь finalize_proposal"
+            );
+        }
+        if let Some(proposal) = self.remove_proposal(market_id) {
+            self.internal_deposit(&proposal.proposer, &None, proposal.bond.0);
+            if let Some(disputer) = &proposal.disputer {
+                self.internal_deposit(disputer, &None, proposal.dispute_bond.0);
+            }
+        }
+        self.score_votes.remove(&market_id);
+
+        market.resolved_outcome = None;
+        market.transition(MarketStatus::Voided);
+        self.release_creator_bond(&mut market, false);
+        self.markets.insert(market_id, market);
+
+        log!("Рынок #{} аннулирован: истёк срок разрешения", market_id);
+    }
+
+    // ══════════════════════════════════════════════════════════════
+    // ESPN ORACLE — permissionless разрешение через OutLayer TEE
+    //
+    // Кто угодно может вызвать request_resolution с депозитом 0.1 NEAR.
+    // Контракт вызовет OutLayer, WASM Worker в TEE получит счёт из ESPN,
+    // и контракт ON-CHAIN определит победителя по ESPN team id.
+    // В OutLayer уходит ровно OUTLAYER_MIN_DEPOSIT: излишек сразу, а при
+    // сбое вызова и весь депозит, зачисляется инициатору на внутренний баланс.
+    // Неизрасходованную часть успешного запуска OutLayer возвращает
+    // плательщику — инициатору (payer_account_id).
+    // ══════════════════════════════════════════════════════════════
+
+    /// Permissionless: кто угодно вызывает для рынков с ESPN данными
+    #[payable]
+    pub fn request_resolution(&mut self, market_id: u64) -> Promise {
+        let initial_storage = env::storage_usage();
+        let deposit = env::attached_deposit().as_yoctonear();
+        assert!(
+            deposit >= OUTLAYER_MIN_DEPOSIT,
+            "Минимальный депозит: 0.1 NEAR для OutLayer"
+        );
+        assert!(
+            !self.outlayer_source_repo.is_empty(),
+            "OutLayer source не настроен"
+        );
+
+        let mut market = self.markets.get(&market_id).expect("Рынок не найден").clone();
+        assert!(
+            !market.espn_event_id.is_empty(),
+            "Рынок не спортивный (нет espn_event_id)"
+        );
+
+        let now = env::block_timestamp();
+        assert!(
+            now >= market.resolution_date,
+            "Время разрешения ещё не наступило"
+        );
+
+        let request_seq =
+            self.lock_resolution(&mut market, OracleSource::Outlayer, OUTLAYER_MIN_DEPOSIT);
+        self.markets.insert(market_id, market.clone());
+
+        // Излишек сверх стоимости запроса — обратно инициатору
+        let excess = deposit - OUTLAYER_MIN_DEPOSIT;
+        if excess > 0 {
+            self.internal_deposit(&env::predecessor_account_id(), &None, excess);
+        }
+        self.internal_charge_storage_growth(&env::predecessor_account_id(), initial_storage);
+
+        // Входные данные для Worker — только ESPN координаты
+        let player_stats = match &market.kind {
+            Some(MarketKind::PlayerProp {
+                athlete_id, stat, ..
+            }) => vec![PlayerStatRequest {
+                athlete_id: athlete_id.clone(),
+                stat: stat.clone(),
+            }],
+            _ => Vec::new(),
+        };
+        let worker_input = WorkerInput {
+            espn_event_id: market.espn_event_id.clone(),
+            sport: market.sport.clone(),
+            league: market.league.clone(),
+            player_stats,
+            athlete_ids: match &market.kind {
+                Some(MarketKind::HeadToHead { .. } | MarketKind::TopN { .. }) => market
+                    .outcome_team_ids
+                    .iter()
+                    .filter(|id| !id.is_empty())
+                    .cloned()
+                    .collect(),
+                _ => Vec::new(),
+            },
+            mode: match &market.kind {
+                Some(MarketKind::Outright { source }) => Some(source.worker_mode()),
+                _ => None,
+            },
+        };
+        let input_data = serde_json::to_string(&worker_input).unwrap();
+
+        // Формируем source для OutLayer
+        let source = OutLayerSource {
+            github: Some(GitHubSource {
+                repo: self.outlayer_source_repo.clone(),
+                commit: self.outlayer_source_commit.clone(),
+                build_target: "wasm32-wasip2".to_string(),
+            }),
+        };
+
+        let limits = ResourceLimits {
+            max_instructions: 1_000_000_000,
+            max_memory_mb: 128,
+            max_execution_seconds: 60,
+        };
+
+        // Аргументы для OutLayer request_execution
+        let args = serde_json::json!({
+            "source": source,
+            "resource_limits": limits,
+            "input_data": input_data,
+            "response_format": "Json",
+            "payer_account_id": env::predecessor_account_id(),
+        });
+
+        let callback_args = ResolutionCallbackArgs {
+            market_id,
+            requester: env::predecessor_account_id(),
+            request_seq,
+        };
+
+        log!(
+            "OutLayer запрос для рынка #{} (ESPN: {})",
+            market_id,
+            market.espn_event_id
+        );
+
+        // Cross-contract call: OutLayer → callback
+        Promise::new(self.outlayer_contract.clone())
             .function_call(
-                "get_attestation".to_string(),
-                serde_json::to_vec(&view_args).unwrap(),
-                NearToken::from_yoctonear(0),
-                GAS_FOR_TLS_VIEW,
+                "request_execution".to_string(),
+                serde_json::to_vec(&args).unwrap(),
+                NearToken::from_yoctonear(OUTLAYER_MIN_DEPOSIT),
+                GAS_FOR_OUTLAYER,
             )
             .then(
                 Promise::new(env::current_account_id()).function_call(
-                    "on_tls_attestation_result".to_string(),
+                    "on_resolution_result".to_string(),
                     serde_json::to_vec(&callback_args).unwrap(),
                     NearToken::from_yoctonear(0),
-                    GAS_FOR_TLS_CALLBACK,
+                    GAS_FOR_CALLBACK,
                 ),
             )
     }
 
-    /// Callback от TLS Oracle — верифицирует аттестацию и разрешает рынок
+    /// Callback от OutLayer — парсит сырые данные ESPN, определяет победителя ON-CHAIN
     #[private]
-    pub fn on_tls_attestation_result(
+    pub fn on_resolution_result(
         &mut self,
         market_id: u64,
-        attestation_id: u64,
-        expected_home_score: i32,
-        expected_away_score: i32,
-        _expected_home_team: String,
-        _expected_away_team: String,
-        _expected_event_status: String,
+        requester: AccountId,
+        request_seq: u32,
+    ) -> String {
+        if !self.is_active_request(market_id, request_seq) {
+            log!(
+                "OutLayer: устаревший ответ на запрос #{} рынка #{}",
+                request_seq,
+                market_id
+            );
+            // Блокировка уже снята, но депозит упавшего вызова всё равно вернулся
+            if matches!(env::promise_result_checked(0, 0), Err(PromiseError::Failed)) {
+                self.refund_resolution_deposit(market_id, request_seq, &requester);
+            }
+            return "Stale resolution request".to_string();
+        }
+        let initial_storage = env::storage_usage();
+        let result = self.handle_outlayer_result(market_id, request_seq, requester.clone());
+        self.finish_resolution_request(market_id, request_seq, &result);
+        // Голос и предварительный исход оплачивает инициатор запроса
+        self.internal_charge_storage_growth(&requester, initial_storage);
+        result
+    }
+
+    fn handle_outlayer_result(
+        &mut self,
+        market_id: u64,
+        request_seq: u32,
+        requester: AccountId,
     ) -> String {
         assert_eq!(
             env::promise_results_count(),
@@ -992,370 +3557,4213 @@ impl NearCast {
                 let result_str =
                     String::from_utf8(data).unwrap_or_else(|_| "invalid utf8".to_string());
 
-                // Парсим аттестацию из TLS Oracle
-                let attestation: TlsAttestation = match serde_json::from_str(&result_str) {
-                    Ok(a) => a,
-                    Err(e) => {
+                match serde_json::from_str::<EspnResult>(&result_str) {
+                    Ok(espn_result) => {
                         log!(
-                            "TLS Oracle: ошибка парсинга аттестации #{}: {}",
-                            attestation_id,
-                            e
+                            "OutLayer: рынок #{} — счёт={}:{}, статус={}",
+                            market_id,
+                            espn_result.home_score,
+                            espn_result.away_score,
+                            espn_result.event_status
                         );
-                        return format!("Attestation parse error: {}", e);
+                        self.record_score_vote(
+                            market_id,
+                            &requester,
+                            OracleSource::Outlayer,
+                            &espn_result,
+                        )
                     }
-                };
-
-                // Верификация: server_name должен быть ESPN
-                if attestation.server_name != "site.api.espn.com" {
-                    log!(
-                        "TLS Oracle: неверный server_name '{}' (ожидался site.api.espn.com)",
-                        attestation.server_name
-                    );
-                    return "Invalid server_name".to_string();
-                }
-
-                // Верификация: source_url должен содержать espn_event_id рынка
-                let market = match self.markets.get(&market_id) {
-                    Some(m) => m.clone(),
-                    None => return format!("Market #{} not found", market_id),
-                };
-
-                if !attestation.source_url.contains(&market.espn_event_id) {
-                    log!(
-                        "TLS Oracle: source_url не содержит event_id '{}'. URL: {}",
-                        market.espn_event_id,
-                        attestation.source_url
-                    );
-                    return "source_url does not match ESPN event".to_string();
-                }
-
-                // Парсим response_data — компактный формат ESPN
-                let espn_data: TlsEspnData = match serde_json::from_str(&attestation.response_data)
-                {
-                    Ok(d) => d,
                     Err(e) => {
                         log!(
-                            "TLS Oracle: ошибка парсинга response_data: {}",
+                            "OutLayer: ошибка парсинга для рынка #{}: {}",
+                            market_id,
                             e
                         );
-                        return format!("response_data parse error: {}", e);
+                        self.release_resolution(market_id);
+                        format!("Parse error: {}", e)
                     }
-                };
+                }
+            }
+            Err(_) => {
+                log!("OutLayer: вызов не удался для рынка #{}", market_id);
+                self.refund_resolution_deposit(market_id, request_seq, &requester);
+                self.release_resolution(market_id);
+                "OutLayer call failed".to_string()
+            }
+        }
+    }
 
-                // Верификация: сравниваем данные из аттестации с переданными relayer
-                if espn_data.hs != expected_home_score
-                    || espn_data.away_score != expected_away_score
-                {
-                    log!(
-                        "TLS Oracle: данные не совпадают! Аттестация: {}:{}, ожидалось: {}:{}",
-                        espn_data.hs,
-                        espn_data.away_score,
-                        expected_home_score,
-                        expected_away_score
-                    );
-                    return "Score mismatch between attestation and request".to_string();
+    /// Берёт блокировку разрешения: пока предыдущий запрос в полёте,
+    /// новые отклоняются; просроченная блокировка снимается.
+    /// Переводит рынок в ResolutionPending и возвращает номер запроса
+    fn lock_resolution(&mut self, market: &mut Market, source: OracleSource, deposit: u128) -> u32 {
+        let block_height = env::block_height();
+        let mut requests = self
+            .resolution_requests
+            .get(&market.id)
+            .cloned()
+            .unwrap_or_default();
+
+        market.close_if_expired(env::block_timestamp());
+        if market.status == MarketStatus::ResolutionPending {
+            if let Some(last) = requests.last_mut() {
+                assert!(
+                    last.result.is_some()
+                        || block_height >= last.block_height + RESOLUTION_LOCK_BLOCKS,
+                    "Разрешение рынка уже запрошено {} в блоке {}, ждём ответа",
+                    last.requester,
+                    last.block_height
+                );
+                if last.result.is_none() {
+                    last.result = Some("expired".to_string());
                 }
+            }
+            log!(
+                "Рынок #{}: блокировка разрешения просрочена, запрос повторяется",
+                market.id
+            );
+        } else {
+            assert!(
+                market.status == MarketStatus::Closed,
+                "Рынок уже разрешается, разрешён или аннулирован"
+            );
+            market.transition(MarketStatus::ResolutionPending);
+        }
 
-                // Конвертируем в EspnResult для determine_winner
-                let espn_result = EspnResult {
-                    home_team: espn_data.ht.clone(),
-                    away_team: espn_data.at.clone(),
-                    home_score: espn_data.hs,
-                    away_score: espn_data.away_score,
-                    event_status: if espn_data.st == "final" {
-                        "final".to_string()
-                    } else {
-                        espn_data.st.clone()
-                    },
-                    error: String::new(),
-                };
+        let seq = requests.last().map(|r| r.seq + 1).unwrap_or(0);
+        requests.push(ResolutionRequest {
+            seq,
+            requester: env::predecessor_account_id(),
+            source,
+            deposit: U128(deposit),
+            refunded: U128(0),
+            block_height,
+            requested_at: env::block_timestamp(),
+            result: None,
+        });
+        if requests.len() > MAX_RESOLUTION_REQUESTS {
+            requests.remove(0);
+        }
+        self.resolution_requests.insert(market.id, requests);
+        seq
+    }
+
+    /// Callback относится к текущему запросу в полёте
+    fn is_active_request(&self, market_id: u64, request_seq: u32) -> bool {
+        self.resolution_requests
+            .get(&market_id)
+            .and_then(|requests| requests.last())
+            .map(|r| r.seq == request_seq && r.result.is_none())
+            .unwrap_or(false)
+    }
+
+    /// Депозит упавшего вызова OutLayer вернулся на контракт —
+    /// зачисляем его инициатору запроса `request_seq`.
+    /// Запрос, вытесненный из истории, вернул OUTLAYER_MIN_DEPOSIT
+    fn refund_resolution_deposit(
+        &mut self,
+        market_id: u64,
+        request_seq: u32,
+        requester: &AccountId,
+    ) {
+        let amount = match self
+            .resolution_requests
+            .get_mut(&market_id)
+            .and_then(|requests| requests.iter_mut().find(|r| r.seq == request_seq))
+        {
+            Some(request) => {
+                let amount = request.deposit.0 - request.refunded.0;
+                request.refunded = request.deposit;
+                amount
+            }
+            None => OUTLAYER_MIN_DEPOSIT,
+        };
+        if amount == 0 {
+            return;
+        }
+
+        self.internal_deposit(requester, &None, amount);
+        log!(
+            "Депозит {} yoctoNEAR за разрешение рынка #{} возвращён {}",
+            amount,
+            market_id,
+            requester
+        );
+    }
+
+    /// Сохраняет итог запроса для UI и снимает блокировку
+    fn finish_resolution_request(&mut self, market_id: u64, request_seq: u32, result: &str) {
+        if let Some(requests) = self.resolution_requests.get_mut(&market_id) {
+            if let Some(r) = requests.iter_mut().find(|r| r.seq == request_seq) {
+                r.result = Some(result.to_string());
+            }
+        }
+    }
+
+    /// Записывает голос источника за счёт матча и подводит итог
+    /// по политике рынка: кворум совпавших голосов даёт предварительный
+    /// исход, а если ни одна группа совпавших голосов уже не наберёт
+    /// кворум — рынок передаётся в спор oracle
+    fn record_score_vote(
+        &mut self,
+        market_id: u64,
+        requester: &AccountId,
+        source: OracleSource,
+        result: &EspnResult,
+    ) -> String {
+        let mut market = match self.markets.get(&market_id) {
+            Some(m) => m.clone(),
+            None => return format!("Market #{} not found", market_id),
+        };
 
-                // Определяем победителя ON-CHAIN (используем ту же логику)
-                let (winning_outcome, confidence, reasoning) =
-                    determine_winner(&market, &espn_result);
+        if !matches!(
+            market.status,
+            MarketStatus::ResolutionPending | MarketStatus::Closed
+        ) {
+            return format!("Market #{} is not awaiting resolution", market_id);
+        }
+
+        // Источник вне политики не голосует и не переносит матч
+        if !market.resolution_policy.sources.contains(&source) {
+            log!(
+                "Рынок #{}: источник {} не входит в политику разрешения",
+                market_id,
+                source.as_str()
+            );
+            self.release_resolution_by(market_id, source);
+            return format!("Source {} is not allowed", source.as_str());
+        }
+
+        let status = result.event_status.as_str();
+        if DELAYED_EVENT_STATUSES.contains(&status) {
+            self.reschedule_market(&mut market, result.start_time);
+            self.markets.insert(market_id, market);
+            self.release_resolution_by(market_id, source);
+            return format!("Delayed: {}", status);
+        }
+
+        // Отменённый матч — тоже итоговый голос: рынок аннулируется
+        if status != "final" && !VOID_EVENT_STATUSES.contains(&status) {
+            log!(
+                "{}: матч не завершён для рынка #{} (status: {})",
+                source.as_str(),
+                market_id,
+                result.event_status
+            );
+            self.release_resolution_by(market_id, source);
+            return format!("Not final: {}", result.event_status);
+        }
 
-                self.apply_resolution(
+        // Источник без нужных рынку данных (team id и т.п.) не голосует
+        if status == "final" {
+            if let Some(missing) = missing_data(&market, result) {
+                log!(
+                    "{}: рынок #{} не рассчитать по ответу ({}), голос не учтён",
+                    source.as_str(),
                     market_id,
-                    winning_outcome,
-                    confidence,
-                    &format!("[TLS Oracle #{}] {}", attestation_id, reasoning),
-                    &espn_result.event_status,
-                    espn_result.home_score,
-                    espn_result.away_score,
+                    missing
+                );
+                self.release_resolution_by(market_id, source);
+                return format!("Cannot judge: {}", missing);
+            }
+        }
+
+        let mut votes = self
+            .score_votes
+            .get(&market_id)
+            .cloned()
+            .unwrap_or_default();
+        votes.retain(|v| v.source != source);
+        votes.push(ScoreVote {
+            source,
+            home_team: result.home_team.clone(),
+            away_team: result.away_team.clone(),
+            home_team_id: result.home_team_id.clone(),
+            away_team_id: result.away_team_id.clone(),
+            home_score: result.home_score,
+            away_score: result.away_score,
+            event_status: result.event_status.clone(),
+            voted_at: env::block_timestamp(),
+            home_periods: result.home_periods.clone(),
+            away_periods: result.away_periods.clone(),
+            extra_time: result.extra_time(),
+            competitors: result.competitors.clone(),
+            player_stats: result.player_stats.clone(),
+            winner_id: result.winner_id.clone(),
+        });
+        self.score_votes.insert(market_id, votes.clone());
+
+        // Самая большая группа голосов с одинаковыми данными для расчёта
+        let keys: Vec<VoteKey> = votes.iter().map(|v| v.settlement_key(&market)).collect();
+        let agreeing: Vec<&ScoreVote> = keys
+            .iter()
+            .map(|key| {
+                votes
+                    .iter()
+                    .zip(&keys)
+                    .filter(|(_, k)| *k == key)
+                    .map(|(v, _)| v)
+                    .collect::<Vec<_>>()
+            })
+            .max_by_key(|group| group.len())
+            .unwrap_or_default();
+        let required = market.resolution_policy.required as usize;
+        let pending = market
+            .resolution_policy
+            .sources
+            .len()
+            .saturating_sub(votes.len());
+
+        // Расхождение, которое оставшиеся источники не разрешат, — спор решает oracle
+        if agreeing.len() + pending < required {
+            let now = env::block_timestamp();
+            market.transition(MarketStatus::Disputed);
+            self.markets.insert(market_id, market);
+            self.proposals.set(
+                market_id,
+                Some(Proposal {
+                    proposer: requester.clone(),
+                    outcome: None,
+                    settlement: None,
+                    reasoning: "источники разошлись в счёте".to_string(),
+                    bond: U128(0),
+                    proposed_at: now,
+                    dispute_deadline: now,
+                    disputer: None,
+                    dispute_bond: U128(0),
+                }),
+            );
+            log!(
+                "Рынок #{}: источники разошлись в счёте, арбитраж — {}",
+                market_id,
+                self.oracle
+            );
+            return "Sources disagree, market disputed".to_string();
+        }
+
+        if agreeing.len() < required {
+            log!(
+                "Рынок #{}: голос {} учтён ({}/{})",
+                market_id,
+                source.as_str(),
+                agreeing.len(),
+                required
+            );
+            self.release_resolution_by(market_id, source);
+            return format!("Vote recorded: {}/{}", agreeing.len(), required);
+        }
+
+        // Кворум набран — определяем победителя ON-CHAIN по счёту группы.
+        // Не все источники сообщают team id и периоды — берём у того, кто сообщил
+        let head = agreeing[0];
+        let mut result = result.clone();
+        result.home_team = head.home_team.clone();
+        result.away_team = head.away_team.clone();
+        result.home_score = head.home_score;
+        result.away_score = head.away_score;
+        result.event_status = head.event_status.clone();
+        if let Some(v) = agreeing.iter().find(|v| !v.home_team_id.is_empty()) {
+            result.home_team_id = v.home_team_id.clone();
+            result.away_team_id = v.away_team_id.clone();
+        }
+        if let Some(v) = agreeing.iter().find(|v| !v.home_periods.is_empty()) {
+            result.home_periods = v.home_periods.clone();
+            result.away_periods = v.away_periods.clone();
+        }
+        if let Some(extra) = agreeing.iter().find_map(|v| v.extra_time.as_ref()) {
+            result.set_extra_time(extra);
+        }
+        if let Some(v) = agreeing.iter().find(|v| !v.competitors.is_empty()) {
+            result.competitors = v.competitors.clone();
+        }
+        if let Some(v) = agreeing.iter().find(|v| !v.player_stats.is_empty()) {
+            result.player_stats = v.player_stats.clone();
+        }
+        if let Some(v) = agreeing.iter().find(|v| !v.winner_id.is_empty()) {
+            result.winner_id = v.winner_id.clone();
+        }
+        let (resolution, confidence, reasoning) = determine_winner(&market, &result);
+        let sources: Vec<&str> = agreeing.iter().map(|v| v.source.as_str()).collect();
+        let reasoning = format!(
+            "{}:{} [{}] | {}",
+            result.home_score,
+            result.away_score,
+            sources.join("+"),
+            reasoning
+        );
+        let summary = format!("Provisionally resolved: {:?}, {}", resolution, reasoning);
+        self.apply_espn_result(market, requester, resolution, confidence, &reasoning);
+        summary
+    }
+
+    /// Перенесённый матч: сдвигает resolution_date на новую дату начала
+    /// (и resolution_deadline, если он раньше), рынок остаётся в ожидании
+    fn reschedule_market(&mut self, market: &mut Market, start_time: u64) {
+        // Голоса за несостоявшийся матч больше не действительны
+        self.score_votes.remove(&market.id);
+        let new_resolution = start_time
+            .saturating_mul(1_000_000_000)
+            .saturating_add(RESCHEDULE_RESOLUTION_DELAY);
+        if start_time == 0 || new_resolution <= market.resolution_date {
+            log!(
+                "Рынок #{}: матч перенесён, новая дата неизвестна",
+                market.id
+            );
+            return;
+        }
+
+        market.resolution_date = new_resolution;
+        market.resolution_deadline = market
+            .resolution_deadline
+            .max(new_resolution + DEFAULT_RESOLUTION_GRACE);
+        log!(
+            "Рынок #{}: матч перенесён, разрешение после {}, срок до {}",
+            market.id,
+            market.resolution_date,
+            market.resolution_deadline
+        );
+    }
+
+    /// Финальный счёт становится предварительным исходом
+    /// на challenge_window, выплаты открываются только после финализации
+    fn apply_espn_result(
+        &mut self,
+        market: Market,
+        requester: &AccountId,
+        resolution: Resolution,
+        confidence: f64,
+        reasoning: &str,
+    ) {
+        let settlement = if confidence < 0.3 {
+            None
+        } else {
+            resolution.settlement(market.outcomes.len())
+        };
+        let challenge_window = self.resolution_config.challenge_window;
+        self.open_proposal(
+            market,
+            requester.clone(),
+            settlement,
+            reasoning.to_string(),
+            0,
+            challenge_window,
+        );
+    }
+
+    /// Удаляет предложение рынка; освобождённый storage
+    /// возвращается предложившему
+    fn remove_proposal(&mut self, market_id: u64) -> Option<Proposal> {
+        self.flush_collections();
+        let initial_storage = env::storage_usage();
+        let proposal = self.proposals.remove(&market_id)?;
+        if self.storage_accounts.contains_key(&proposal.proposer) {
+            self.internal_charge_storage(&proposal.proposer, initial_storage);
+        }
+        Some(proposal)
+    }
+
+    /// Переводит рынок в Proposed и открывает окно для оспаривания
+    fn open_proposal(
+        &mut self,
+        mut market: Market,
+        proposer: AccountId,
+        settlement: Option<Vec<OutcomeSettlement>>,
+        reasoning: String,
+        bond: u128,
+        window: u64,
+    ) {
+        let market_id = market.id;
+        let outcome = settlement.as_deref().and_then(single_winner);
+        let now = env::block_timestamp();
+        let dispute_deadline = now + window;
+
+        market.transition(MarketStatus::Proposed);
+        self.markets.insert(market_id, market);
+
+        log!(
+            "Рынок #{}: {} предложил исход {:?}, споры до {} | {}",
+            market_id,
+            proposer,
+            outcome,
+            dispute_deadline,
+            reasoning
+        );
+
+        self.proposals.set(
+            market_id,
+            Some(Proposal {
+                proposer,
+                outcome,
+                settlement,
+                reasoning,
+                bond: U128(bond),
+                proposed_at: now,
+                dispute_deadline,
+                disputer: None,
+                dispute_bond: U128(0),
+            }),
+        );
+    }
+
+    /// Общий финальный шаг всех путей разрешения (ESPN oracle, оптимистичный):
+    /// итог по исходам → Resolved с удержанием комиссий, None или невалидный → Voided.
+    /// Залог создателя возвращается в обоих случаях.
+    fn apply_resolution(
+        &mut self,
+        market_id: u64,
+        settlement: Option<Vec<OutcomeSettlement>>,
+        reasoning: &str,
+    ) {
+        let mut market = match self.markets.get(&market_id) {
+            Some(m) => m.clone(),
+            None => return,
+        };
+
+        if !matches!(
+            market.status,
+            MarketStatus::ResolutionPending | MarketStatus::Proposed | MarketStatus::Disputed
+        ) {
+            return;
+        }
+
+        match settlement.filter(|s| s.len() == market.outcomes.len()) {
+            Some(settlement) => {
+                market.resolved_outcome = single_winner(&settlement);
+                market.settlement = settlement.clone();
+                market.transition(MarketStatus::Resolved);
+                // Инициатор разрешения, чей итог подтвердился
+                let resolver = self
+                    .proposals
+                    .get(&market_id)
+                    .filter(|p| p.settlement.as_ref() == Some(&settlement))
+                    .map(|p| p.proposer.clone());
+                self.collect_fees(&mut market, resolver);
+                self.release_creator_bond(&mut market, false);
+
+                match market.resolved_outcome {
+                    Some(winning_outcome) => log!(
+                        "Рынок #{} разрешён: исход #{} (\"{}\") | {}",
+                        market_id,
+                        winning_outcome,
+                        market.outcomes[winning_outcome as usize],
+                        reasoning
+                    ),
+                    None => log!(
+                        "Рынок #{} разрешён: push / частичный итог, ставки возвращаются | {}",
+                        market_id,
+                        reasoning
+                    ),
+                }
+            }
+            None => {
+                market.resolved_outcome = None;
+                market.transition(MarketStatus::Voided);
+                self.release_creator_bond(&mut market, false);
+
+                log!("Рынок #{} аннулирован: {}", market_id, reasoning);
+            }
+        }
+        self.markets.insert(market_id, market);
+        // Рынок рассчитан — предложение больше не нужно
+        self.remove_proposal(market_id);
+    }
+
+    /// Удерживает комиссии с проигравших пулов разрешённого рынка:
+    /// протокольная — в казну (за вычетом награды инициатору разрешения),
+    /// создателя — на его внутренний баланс.
+    /// Если на победивший исход никто не ставил или проигравших нет (push),
+    /// комиссии не берутся.
+    fn collect_fees(&mut self, market: &mut Market, resolver: Option<AccountId>) {
+        let (winning_pool, _, losing_pool) = market.settled_pools();
+        if winning_pool == 0 || losing_pool == 0 {
+            return;
+        }
+
+        let token_id = market.collateral_token.clone();
+        let (protocol_fee, creator_fee) = market.fees_on(losing_pool);
+        let resolver_reward = match resolver {
+            Some(ref resolver) => {
+                let reward = safe_mul_div(
+                    protocol_fee,
+                    self.resolution_config.resolver_reward_bps as u128,
+                    BPS_DENOMINATOR,
                 );
+                if reward > 0 {
+                    self.internal_deposit(resolver, &token_id, reward);
+                    log!(
+                        "Награда за разрешение рынка #{}: {} {} → {}",
+                        market.id,
+                        reward,
+                        token_label(&token_id),
+                        resolver
+                    );
+                }
+                reward
+            }
+            None => 0,
+        };
+        let treasury = self.treasury_balance(&token_id);
+        self.set_treasury_balance(&token_id, treasury + protocol_fee - resolver_reward);
+        if creator_fee > 0 {
+            self.internal_deposit(&market.creator, &token_id, creator_fee);
+        }
+        market.fees_collected = U128(protocol_fee + creator_fee);
+
+        log!(
+            "Комиссии рынка #{}: протокол {}, создатель {} ({})",
+            market.id,
+            protocol_fee,
+            creator_fee,
+            token_label(&token_id)
+        );
+    }
+
+    /// Снимает залог создателя: возврат на его внутренний баланс
+    /// или конфискация в казну (`slash`)
+    fn release_creator_bond(&mut self, market: &mut Market, slash: bool) {
+        let bond: u128 = market.creator_bond.into();
+        if bond == 0 {
+            return;
+        }
+        market.creator_bond = U128(0);
+
+        let locked = self.locked_bonds.get(&market.creator).copied().unwrap_or(0);
+        if locked > bond {
+            self.locked_bonds
+                .set(market.creator.clone(), Some(locked - bond));
+        } else {
+            self.locked_bonds.remove(&market.creator);
+        }
+
+        if slash {
+            self.treasury += bond;
+            log!(
+                "Залог {} yoctoNEAR за рынок #{} конфискован в казну",
+                bond,
+                market.id
+            );
+        } else {
+            self.internal_deposit(&market.creator, &None, bond);
+            log!(
+                "Залог {} yoctoNEAR за рынок #{} возвращён {}",
+                bond,
+                market.id,
+                market.creator
+            );
+        }
+    }
+
+    /// Снимает блокировку, только если её держит запрос этого источника:
+    /// голос без запроса (TLS Oracle, oracle) чужую блокировку не трогает
+    fn release_resolution_by(&mut self, market_id: u64, source: OracleSource) {
+        let holds_lock = self
+            .resolution_requests
+            .get(&market_id)
+            .and_then(|requests| requests.last())
+            .is_some_and(|r| r.source == source && r.result.is_none());
+        if holds_lock {
+            self.release_resolution(market_id);
+        }
+    }
+
+    /// Возвращает рынок из ResolutionPending в Closed, если oracle не ответил
+    fn release_resolution(&mut self, market_id: u64) {
+        if let Some(market) = self.markets.get_mut(&market_id) {
+            if market.status == MarketStatus::ResolutionPending {
+                market.transition(MarketStatus::Closed);
+            }
+        }
+    }
+
+    /// Голос назначенного oracle за счёт спортивного рынка
+    /// (один из источников политики разрешения).
+    /// start_time — новая дата начала перенесённого матча (unix, секунды),
+    /// *_periods — очки по периодам для рынков на периоды,
+    /// extra_time — овертайм / серия для правила расчёта рынка,
+    /// competitors — участники индивидуального события,
+    /// player_stats — показатели игроков для player-prop рынков,
+    /// winner_id — победитель outright-рынка
+    pub fn submit_oracle_score(
+        &mut self,
+        market_id: u64,
+        home_team: String,
+        away_team: String,
+        home_score: i32,
+        away_score: i32,
+        event_status: String,
+        start_time: Option<u64>,
+        home_team_id: Option<String>,
+        away_team_id: Option<String>,
+        home_periods: Option<Vec<i32>>,
+        away_periods: Option<Vec<i32>>,
+        extra_time: Option<ExtraTime>,
+        competitors: Option<Vec<CompetitorResult>>,
+        player_stats: Option<Vec<PlayerStat>>,
+        winner_id: Option<String>,
+    ) -> String {
+        let initial_storage = env::storage_usage();
+        let oracle = env::predecessor_account_id();
+        assert!(oracle == self.oracle, "Только oracle");
+
+        let mut market = self
+            .markets
+            .get(&market_id)
+            .expect("Рынок не найден")
+            .clone();
+        assert!(
+            !market.espn_event_id.is_empty(),
+            "Рынок не спортивный (нет espn_event_id)"
+        );
+
+        let now = env::block_timestamp();
+        assert!(
+            now >= market.resolution_date,
+            "Время разрешения ещё не наступило"
+        );
+
+        market.close_if_expired(now);
+        assert!(
+            market.status == MarketStatus::Closed,
+            "Рынок уже разрешается, разрешён или аннулирован"
+        );
+        self.markets.insert(market_id, market);
+
+        let mut result = EspnResult {
+            home_team,
+            away_team,
+            home_team_id: home_team_id.unwrap_or_default(),
+            away_team_id: away_team_id.unwrap_or_default(),
+            home_score,
+            away_score,
+            event_status,
+            error: String::new(),
+            start_time: start_time.unwrap_or(0),
+            home_periods: home_periods.unwrap_or_default(),
+            away_periods: away_periods.unwrap_or_default(),
+            overtime: None,
+            shootout: None,
+            regulation_home_score: None,
+            regulation_away_score: None,
+            home_shootout_score: None,
+            away_shootout_score: None,
+            competitors: competitors.unwrap_or_default(),
+            player_stats: player_stats.unwrap_or_default(),
+            winner_id: winner_id.unwrap_or_default(),
+        };
+        if let Some(ref extra) = extra_time {
+            result.set_extra_time(extra);
+        }
+        let outcome =
+            self.record_score_vote(market_id, &oracle, OracleSource::DesignatedOracle, &result);
+        self.internal_charge_storage_growth(&oracle, initial_storage);
+        outcome
+    }
+
+    // ══════════════════════════════════════════════════════════════
+    // ОПТИМИСТИЧНОЕ РАЗРЕШЕНИЕ И ПЕРИОД ОСПАРИВАНИЯ
+    //
+    // Рынки без ESPN: после resolution_date кто угодно предлагает исход
+    // с залогом. Результаты OutLayer / TLS Oracle тоже становятся
+    // предварительным исходом (без залога, на challenge_window).
+    // Пока окно открыто, любой может оспорить исход залогом —
+    // тогда спор решает oracle (арбитраж или повторный запрос),
+    // а залог проигравшего уходит победителю.
+    // Неоспоренное предложение финализируется через apply_resolution.
+    // ══════════════════════════════════════════════════════════════
+
+    /// Предложить исход (None — аннулировать рынок) с залогом proposal_bond
+    #[payable]
+    pub fn propose_outcome(&mut self, market_id: u64, outcome: Option<u32>) {
+        let initial_storage = env::storage_usage();
+        let proposer = env::predecessor_account_id();
+        let bond = env::attached_deposit().as_yoctonear();
+        let required_bond: u128 = self.resolution_config.proposal_bond.into();
+        assert!(
+            bond >= required_bond,
+            "Залог за предложение: {} yoctoNEAR",
+            required_bond
+        );
+
+        let mut market = self
+            .markets
+            .get(&market_id)
+            .expect("Рынок не найден")
+            .clone();
+        assert!(
+            market.espn_event_id.is_empty(),
+            "Спортивные рынки разрешаются через ESPN oracle"
+        );
+        if let Some(o) = outcome {
+            assert!((o as usize) < market.outcomes.len(), "Недопустимый исход");
+        }
+
+        let now = env::block_timestamp();
+        assert!(
+            now >= market.resolution_date,
+            "Время разрешения ещё не наступило"
+        );
+
+        market.close_if_expired(now);
+        assert!(
+            market.status == MarketStatus::Closed,
+            "Исход рынка уже предложен, разрешён или аннулирован"
+        );
+
+        let dispute_window = self.resolution_config.dispute_window;
+        let settlement =
+            outcome.and_then(|o| Resolution::Winner(o).settlement(market.outcomes.len()));
+        self.open_proposal(
+            market,
+            proposer.clone(),
+            settlement,
+            "оптимистичное предложение".to_string(),
+            bond,
+            dispute_window,
+        );
+        self.internal_charge_storage(&proposer, initial_storage);
+    }
+
+    /// Оспорить предварительный исход залогом не меньше залога
+    /// предложившего (и не меньше proposal_bond для результатов oracle)
+    #[payable]
+    pub fn dispute(&mut self, market_id: u64) {
+        let initial_storage = env::storage_usage();
+        let disputer = env::predecessor_account_id();
+        let bond = env::attached_deposit().as_yoctonear();
+
+        let mut market = self
+            .markets
+            .get(&market_id)
+            .expect("Рынок не найден")
+            .clone();
+        assert!(
+            market.status == MarketStatus::Proposed,
+            "Нет активного предложения для оспаривания"
+        );
+
+        let mut proposal = self
+            .proposals
+            .get(&market_id)
+            .expect("Предложение не найдено")
+            .clone();
+        assert!(
+            env::block_timestamp() < proposal.dispute_deadline,
+            "Окно для оспаривания закрыто"
+        );
+        let required_bond = proposal.bond.0.max(self.resolution_config.proposal_bond.0);
+        assert!(
+            bond >= required_bond,
+            "Залог за спор: {} yoctoNEAR",
+            required_bond
+        );
+
+        market.transition(MarketStatus::Disputed);
+        self.markets.insert(market_id, market);
+
+        proposal.disputer = Some(disputer.clone());
+        proposal.dispute_bond = U128(bond);
+        self.proposals.set(market_id, Some(proposal));
+        self.internal_charge_storage_growth(&disputer, initial_storage);
+
+        log!(
+            "Рынок #{}: {} оспорил предложение, арбитраж — {}",
+            market_id,
+            disputer,
+            self.oracle
+        );
+    }
+
+    /// Permissionless: финализирует неоспоренное предложение после окна споров
+    pub fn finalize_proposal(&mut self, market_id: u64) {
+        let market = self.markets.get(&market_id).expect("Рынок не найден");
+        assert!(
+            market.status == MarketStatus::Proposed,
+            "Нет предложения для финализации"
+        );
+
+        let proposal = self
+            .proposals
+            .get(&market_id)
+            .expect("Предложение не найдено")
+            .clone();
+        assert!(
+            env::block_timestamp() >= proposal.dispute_deadline,
+            "Окно для оспаривания ещё открыто"
+        );
+
+        self.internal_deposit(&proposal.proposer, &None, proposal.bond.0);
+        self.apply_resolution(
+            market_id,
+            proposal.settlement,
+            &format!("предложение {} не оспорено", proposal.proposer),
+        );
+    }
+
+    /// Арбитраж спора назначенным oracle: победитель спора
+    /// получает свой залог и залог проигравшего.
+    /// При конфликте источников счёта залогов нет — oracle задаёт исход.
+    /// push = true — ставки возвращаются всем (outcome игнорируется),
+    /// split — доли выигрыша и возврата по исходам (четвертные линии)
+    pub fn arbitrate(
+        &mut self,
+        market_id: u64,
+        outcome: Option<u32>,
+        push: Option<bool>,
+        split: Option<Vec<OutcomeSettlement>>,
+    ) {
+        assert!(
+            env::predecessor_account_id() == self.oracle,
+            "Только oracle"
+        );
+
+        let market = self.markets.get(&market_id).expect("Рынок не найден");
+        assert!(
+            market.status == MarketStatus::Disputed,
+            "Рынок не находится в споре"
+        );
+        if let Some(o) = outcome {
+            assert!((o as usize) < market.outcomes.len(), "Недопустимый исход");
+        }
+        if let Some(split) = &split {
+            assert!(
+                split.len() == market.outcomes.len(),
+                "Доли нужны для каждого исхода"
+            );
+            assert!(
+                split
+                    .iter()
+                    .all(|s| s.win_bps as u128 + s.push_bps as u128 <= BPS_DENOMINATOR),
+                "Сумма долей исхода больше 100%"
+            );
+        }
+        let resolution = match (split, push.unwrap_or(false), outcome) {
+            (Some(split), _, _) => Resolution::Split(split),
+            (None, true, _) => Resolution::Push,
+            (None, false, Some(o)) => Resolution::Winner(o),
+            (None, false, None) => Resolution::Void,
+        };
+        let settlement = resolution.settlement(market.outcomes.len());
+
+        let proposal = self
+            .proposals
+            .get(&market_id)
+            .expect("Предложение не найдено")
+            .clone();
+        let reasoning = match proposal.disputer.clone() {
+            Some(disputer) => {
+                let winner = if settlement == proposal.settlement {
+                    proposal.proposer.clone()
+                } else {
+                    disputer
+                };
+                self.internal_deposit(&winner, &None, proposal.bond.0 + proposal.dispute_bond.0);
+                format!("арбитраж oracle, залоги получает {}", winner)
+            }
+            None => format!("арбитраж oracle: {}", proposal.reasoning),
+        };
+
+        self.apply_resolution(market_id, settlement, &reasoning);
+    }
+
+    /// Арбитр отправляет оспоренный результат oracle на повторный запрос:
+    /// залоги возвращаются сторонам, рынок снова ждёт request_resolution
+    pub fn requery(&mut self, market_id: u64) {
+        assert!(
+            env::predecessor_account_id() == self.oracle,
+            "Только oracle"
+        );
+
+        let mut market = self
+            .markets
+            .get(&market_id)
+            .expect("Рынок не найден")
+            .clone();
+        assert!(
+            market.status == MarketStatus::Disputed,
+            "Рынок не находится в споре"
+        );
+        assert!(
+            !market.espn_event_id.is_empty(),
+            "Повторный запрос возможен только для спортивных рынков"
+        );
+
+        let proposal = self
+            .remove_proposal(market_id)
+            .expect("Предложение не найдено");
+        self.internal_deposit(&proposal.proposer, &None, proposal.bond.0);
+        if let Some(disputer) = &proposal.disputer {
+            self.internal_deposit(disputer, &None, proposal.dispute_bond.0);
+        }
+
+        // Голоса источников собираются заново
+        self.score_votes.remove(&market_id);
+        market.transition(MarketStatus::Closed);
+        self.markets.insert(market_id, market);
+
+        log!(
+            "Рынок #{}: арбитр отправил результат на повторный запрос",
+            market_id
+        );
+    }
+
+    // ══════════════════════════════════════════════════════════════
+    // TLS ORACLE — альтернативное разрешение через MPC-TLS + ZK proof
+    //
+    // Relayer вызывает resolve_with_tls_attestation, передавая attestation_id
+    // и распарсенные off-chain данные. Контракт верифицирует данные через
+    // cross-contract view call к TLS Oracle (сравнивает с response_data).
+    // ══════════════════════════════════════════════════════════════
+
+    /// Разрешить рынок через TLS Oracle аттестацию
+    ///
+    /// Делает cross-contract view call к TLS Oracle для верификации данных.
+    /// Любой может вызвать — permissionless (аналог request_resolution).
+    /// Вызов бесплатный, поэтому блокировку разрешения не берёт:
+    /// голос учитывается, если рынок ещё ждёт разрешения к callback
+    pub fn resolve_with_tls_attestation(
+        &mut self,
+        market_id: u64,
+        attestation_id: u64,
+        home_score: i32,
+        away_score: i32,
+        home_team: String,
+        away_team: String,
+        event_status: String,
+    ) -> Promise {
+        let mut market = self
+            .markets
+            .get(&market_id)
+            .expect("Рынок не найден")
+            .clone();
+        assert!(
+            !market.espn_event_id.is_empty(),
+            "Рынок не спортивный (нет espn_event_id)"
+        );
+
+        let now = env::block_timestamp();
+        assert!(
+            now >= market.resolution_date,
+            "Время разрешения ещё не наступило"
+        );
+
+        market.close_if_expired(now);
+        assert!(
+            matches!(
+                market.status,
+                MarketStatus::Closed | MarketStatus::ResolutionPending
+            ),
+            "Рынок уже разрешается, разрешён или аннулирован"
+        );
+        self.markets.insert(market_id, market);
+
+        log!(
+            "TLS Oracle запрос для рынка #{} (attestation #{})",
+            market_id,
+            attestation_id
+        );
+
+        // Аргументы для callback — передаём ожидаемые данные для верификации
+        let callback_args = serde_json::json!({
+            "market_id": market_id,
+            "requester": env::predecessor_account_id(),
+            "attestation_id": attestation_id,
+            "expected_home_score": home_score,
+            "expected_away_score": away_score,
+            "_expected_home_team": home_team,
+            "_expected_away_team": away_team,
+            "_expected_event_status": event_status,
+        });
+
+        // Cross-contract view call к TLS Oracle: get_attestation(id)
+        let view_args = serde_json::json!({ "id": attestation_id });
+
+        Promise::new(self.tls_oracle_contract.clone())
+            .function_call(
+                "get_attestation".to_string(),
+                serde_json::to_vec(&view_args).unwrap(),
+                NearToken::from_yoctonear(0),
+                GAS_FOR_TLS_VIEW,
+            )
+            .then(
+                Promise::new(env::current_account_id()).function_call(
+                    "on_tls_attestation_result".to_string(),
+                    serde_json::to_vec(&callback_args).unwrap(),
+                    NearToken::from_yoctonear(0),
+                    GAS_FOR_TLS_CALLBACK,
+                ),
+            )
+    }
+
+    /// Callback от TLS Oracle — верифицирует аттестацию и разрешает рынок
+    #[private]
+    pub fn on_tls_attestation_result(
+        &mut self,
+        market_id: u64,
+        requester: AccountId,
+        attestation_id: u64,
+        expected_home_score: i32,
+        expected_away_score: i32,
+        _expected_home_team: String,
+        _expected_away_team: String,
+        _expected_event_status: String,
+    ) -> String {
+        let initial_storage = env::storage_usage();
+        let result = self.handle_tls_attestation(
+            market_id,
+            requester.clone(),
+            attestation_id,
+            expected_home_score,
+            expected_away_score,
+        );
+        self.internal_charge_storage_growth(&requester, initial_storage);
+        result
+    }
+
+    fn handle_tls_attestation(
+        &mut self,
+        market_id: u64,
+        requester: AccountId,
+        attestation_id: u64,
+        expected_home_score: i32,
+        expected_away_score: i32,
+    ) -> String {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "Ожидается один результат"
+        );
+
+        let result = env::promise_result_checked(0, 1_000_000);
+        match result {
+            Ok(data) => {
+                let result_str =
+                    String::from_utf8(data).unwrap_or_else(|_| "invalid utf8".to_string());
+
+                // Парсим аттестацию из TLS Oracle
+                let attestation: TlsAttestation = match serde_json::from_str(&result_str) {
+                    Ok(a) => a,
+                    Err(e) => {
+                        log!(
+                            "TLS Oracle: ошибка парсинга аттестации #{}: {}",
+                            attestation_id,
+                            e
+                        );
+                        return format!("Attestation parse error: {}", e);
+                    }
+                };
+
+                // Верификация: server_name должен быть ESPN
+                if attestation.server_name != "site.api.espn.com" {
+                    log!(
+                        "TLS Oracle: неверный server_name '{}' (ожидался site.api.espn.com)",
+                        attestation.server_name
+                    );
+                    return "Invalid server_name".to_string();
+                }
+
+                // Верификация: source_url должен содержать espn_event_id рынка
+                let market = match self.markets.get(&market_id) {
+                    Some(m) => m.clone(),
+                    None => return format!("Market #{} not found", market_id),
+                };
+
+                if !attestation.source_url.contains(&market.espn_event_id) {
+                    log!(
+                        "TLS Oracle: source_url не содержит event_id '{}'. URL: {}",
+                        market.espn_event_id,
+                        attestation.source_url
+                    );
+                    return "source_url does not match ESPN event".to_string();
+                }
+
+                // Парсим response_data — компактный формат ESPN
+                let espn_data: TlsEspnData = match serde_json::from_str(&attestation.response_data)
+                {
+                    Ok(d) => d,
+                    Err(e) => {
+                        log!(
+                            "TLS Oracle: ошибка парсинга response_data: {}",
+                            e
+                        );
+                        return format!("response_data parse error: {}", e);
+                    }
+                };
+
+                // Верификация: сравниваем данные из аттестации с переданными relayer
+                if espn_data.hs != expected_home_score
+                    || espn_data.away_score != expected_away_score
+                {
+                    log!(
+                        "TLS Oracle: данные не совпадают! Аттестация: {}:{}, ожидалось: {}:{}",
+                        espn_data.hs,
+                        espn_data.away_score,
+                        expected_home_score,
+                        expected_away_score
+                    );
+                    return "Score mismatch between attestation and request".to_string();
+                }
+
+                // Конвертируем в EspnResult для determine_winner
+                let espn_result = EspnResult {
+                    home_team: espn_data.ht.clone(),
+                    away_team: espn_data.at.clone(),
+                    home_team_id: espn_data.hid.clone(),
+                    away_team_id: espn_data.aid.clone(),
+                    home_score: espn_data.hs,
+                    away_score: espn_data.away_score,
+                    event_status: if espn_data.st == "final" {
+                        "final".to_string()
+                    } else {
+                        espn_data.st.clone()
+                    },
+                    error: String::new(),
+                    start_time: 0,
+                    home_periods: espn_data.hp.clone(),
+                    away_periods: espn_data.ap.clone(),
+                    overtime: espn_data.ot,
+                    shootout: espn_data.so,
+                    regulation_home_score: espn_data.rhs,
+                    regulation_away_score: espn_data.ras,
+                    home_shootout_score: espn_data.hss,
+                    away_shootout_score: espn_data.ass,
+                    competitors: Vec::new(),
+                    player_stats: Vec::new(),
+                    winner_id: String::new(),
+                };
+
+                log!(
+                    "TLS Oracle: рынок #{} (attestation #{}) — счёт={}:{}, статус={}",
+                    market_id,
+                    attestation_id,
+                    espn_result.home_score,
+                    espn_result.away_score,
+                    espn_result.event_status
+                );
+
+                // Голос учитывается по политике рынка (та же логика, что у OutLayer)
+                self.record_score_vote(
+                    market_id,
+                    &requester,
+                    OracleSource::TlsOracle,
+                    &espn_result,
+                )
+            }
+            Err(_) => {
+                log!(
+                    "TLS Oracle: view call не удался для аттестации #{}",
+                    attestation_id
+                );
+                "TLS Oracle view call failed".to_string()
+            }
+        }
+    }
+
+    // ══════════════════════════════════════════════════════════════
+    // ПОЛУЧЕНИЕ ВЫИГРЫША / ВОЗВРАТ
+    // ══════════════════════════════════════════════════════════════
+
+    /// Выплата выигрыша / возврат. Позиции пользователя на рынке после
+    /// выплаты удаляются, освобождённый storage возвращается на его депозит.
+    pub fn claim_winnings(&mut self, market_id: u64) {
+        let sender = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        let mut market = self.markets.get(&market_id).expect("Рынок не найден").clone();
+        assert!(
+            market.status == MarketStatus::Resolved || market.status == MarketStatus::Voided,
+            "Рынок ещё не разрешён"
+        );
+
+        assert!(
+            !self.legacy_market_bets.contains_key(&market_id),
+            "Ставки рынка ещё не перенесены в позиции"
+        );
+
+        // Позиции пользователя по всем исходам (не больше MAX_OUTCOMES чтений):
+        // выигравшая доля ставки делит проигранный пул, доля push возвращается
+        let mut stake: u128 = 0;
+        let mut win_stake: u128 = 0;
+        let mut push_stake: u128 = 0;
+        let mut has_claimed = false;
+        for outcome in 0..market.outcomes.len() as u32 {
+            if !market.is_payable(outcome) {
+                continue;
+            }
+            let key = (market_id, sender.clone(), outcome);
+            if let Some(position) = self.positions.get(&key) {
+                if position.claimed {
+                    has_claimed = true;
+                    break;
+                }
+                let s = u128::from(position.stake);
+                stake += s;
+                match market.settlement.get(outcome as usize) {
+                    Some(o) if market.status == MarketStatus::Resolved => {
+                        win_stake += safe_mul_div(s, o.win_bps as u128, BPS_DENOMINATOR);
+                        push_stake += safe_mul_div(s, o.push_bps as u128, BPS_DENOMINATOR);
+                    }
+                    _ => push_stake += s,
+                }
+            }
+        }
+
+        let mut payout = push_stake;
+        if win_stake > 0 {
+            let (winning_pool, _, losing_pool) = market.settled_pools();
+            let fees: u128 = market.fees_collected.into();
+            // Безопасный расчёт без overflow u128:
+            // stake * pool может переполнить (yoctoNEAR ~ 10^24)
+            // Делим через промежуточный масштаб
+            payout += safe_mul_div(win_stake, winning_pool + losing_pool - fees, winning_pool);
+        }
+
+        assert!(!has_claimed, "Выигрыш уже получен");
+        assert!(payout > 0, "Нет ставок для получения");
+
+        self.internal_prune_positions(&market, &sender, true);
+        let claimed = self.claimed_stakes.get(&market_id).copied().unwrap_or(0) + stake;
+        self.claimed_stakes.set(market_id, Some(claimed));
+        let all_claimed = claimed >= market.payable_stake();
+
+        self.internal_deposit(&sender, &market.collateral_token, payout);
+
+        let action = if win_stake == 0 {
+            "Возврат"
+        } else {
+            "Выигрыш"
+        };
+        log!(
+            "{}: {} получил {} {} с рынка #{}",
+            action,
+            sender,
+            payout,
+            token_label(&market.collateral_token),
+            market_id
+        );
+
+        if all_claimed {
+            market.transition(MarketStatus::Finalized);
+            self.markets.insert(market_id, market);
+            log!("Рынок #{} завершён: все выплаты получены", market_id);
+        }
+
+        // Ставки, сделанные до NEP-145, не оплачивались — таким аккаунтам
+        // регистрация для получения выплаты не нужна
+        if self.storage_accounts.contains_key(&sender) {
+            self.internal_charge_storage(&sender, initial_storage);
+        }
+    }
+
+    /// Удаляет проигравшие и уже выплаченные позиции пользователя
+    /// на разрешённом рынке и возвращает освобождённый storage
+    pub fn prune_positions(&mut self, market_id: u64) {
+        let sender = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+
+        let market = self
+            .markets
+            .get(&market_id)
+            .expect("Рынок не найден")
+            .clone();
+        assert!(
+            matches!(
+                market.status,
+                MarketStatus::Resolved | MarketStatus::Voided | MarketStatus::Finalized
+            ),
+            "Рынок ещё не разрешён"
+        );
+
+        self.internal_prune_positions(&market, &sender, false);
+        if self.storage_accounts.contains_key(&sender) {
+            self.internal_charge_storage(&sender, initial_storage);
+        }
+    }
+
+    /// Удаляет позиции аккаунта на рынке: все (`include_payable`) или только
+    /// не участвующие в выплате и уже выплаченные
+    fn internal_prune_positions(
+        &mut self,
+        market: &Market,
+        account_id: &AccountId,
+        include_payable: bool,
+    ) {
+        let mut remaining = false;
+        for outcome in 0..market.outcomes.len() as u32 {
+            let key = (market.id, account_id.clone(), outcome);
+            let prunable = match self.positions.get(&key) {
+                Some(position) => {
+                    include_payable || position.claimed || !market.is_payable(outcome)
+                }
+                None => continue,
+            };
+            if prunable {
+                self.positions.remove(&key);
+            } else {
+                remaining = true;
+            }
+        }
+
+        if !remaining {
+            let mut markets = self
+                .user_markets
+                .get(account_id)
+                .cloned()
+                .unwrap_or_default();
+            markets.retain(|&id| id != market.id);
+            if markets.is_empty() {
+                self.user_markets.remove(account_id);
+            } else {
+                self.user_markets.set(account_id.clone(), Some(markets));
+            }
+        }
+    }
+
+    // ══════════════════════════════════════════════════════════════
+    // STORAGE MANAGEMENT (NEP-145)
+    //
+    // Каждый вызов, добавляющий состояние (create_market, place_bet,
+    // deposit, ft_on_transfer), оплачивается из storage-депозита
+    // вызывающего. Освобождённый storage возвращается в депозит.
+    // Регистрация и нехватка депозита покрываются из внутреннего баланса
+    // NEAR: первый deposit регистрирует аккаунт сам. Явный storage_deposit
+    // (минимум — storage_balance_bounds) нужен без баланса в NEAR,
+    // например перед первым ft_transfer_call.
+    // ══════════════════════════════════════════════════════════════
+
+    /// Регистрация / пополнение storage-депозита
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit().as_yoctonear();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let min_balance = storage_min_balance();
+
+        let refund = match self.storage_accounts.get(&account_id).cloned() {
+            Some(mut account) => {
+                if registration_only.unwrap_or(false) {
+                    amount
+                } else {
+                    account.deposit += amount;
+                    self.storage_accounts.set(account_id.clone(), Some(account));
+                    0
+                }
+            }
+            None => {
+                assert!(
+                    amount >= min_balance,
+                    "Минимальный storage депозит: {} yoctoNEAR",
+                    min_balance
+                );
+                let deposit = if registration_only.unwrap_or(false) {
+                    min_balance
+                } else {
+                    amount
+                };
+                self.storage_accounts.set(
+                    account_id.clone(),
+                    Some(StorageAccount {
+                        deposit,
+                        used_bytes: STORAGE_REGISTRATION_BYTES,
+                    }),
+                );
+                log!("Storage: {} зарегистрирован", account_id);
+                amount - deposit
+            }
+        };
+
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id())
+                .transfer(NearToken::from_yoctonear(refund))
+                .detach();
+        }
+
+        self.storage_balance_of(account_id)
+            .expect("Аккаунт не зарегистрирован")
+    }
+
+    /// Вывод свободной части storage-депозита (по умолчанию — всей)
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_eq!(
+            env::attached_deposit(),
+            ONE_YOCTO,
+            "Требуется ровно 1 yoctoNEAR"
+        );
+        let account_id = env::predecessor_account_id();
+        let mut account = self
+            .storage_accounts
+            .get(&account_id)
+            .cloned()
+            .expect("Аккаунт не зарегистрирован");
+
+        let available = storage_available(&account);
+        let amount: u128 = amount.map(|a| a.into()).unwrap_or(available);
+        assert!(
+            amount <= available,
+            "Доступно для вывода: {} yoctoNEAR",
+            available
+        );
+
+        if amount > 0 {
+            account.deposit -= amount;
+            self.storage_accounts.set(account_id.clone(), Some(account));
+            Promise::new(account_id.clone())
+                .transfer(NearToken::from_yoctonear(amount))
+                .detach();
+            log!("Storage: {} вывел {} yoctoNEAR", account_id, amount);
+        }
+
+        self.storage_balance_of(account_id)
+            .expect("Аккаунт не зарегистрирован")
+    }
+
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_accounts
+            .get(&account_id)
+            .map(|account| StorageBalance {
+                total: U128(account.deposit),
+                available: U128(storage_available(account)),
+            })
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(storage_min_balance()),
+            max: None,
+        }
+    }
+
+    /// Коллекции near_sdk::store пишут в storage только при flush —
+    /// без него storage_usage не видит изменений текущего вызова
+    fn flush_collections(&mut self) {
+        self.markets.flush();
+        self.legacy_markets.flush();
+        self.legacy_market_bets.flush();
+        self.legacy_user_bets.flush();
+        self.balances.flush();
+        self.pending_withdrawals.flush();
+        self.collateral_tokens.flush();
+        self.ft_balances.flush();
+        self.ft_treasury.flush();
+        self.positions.flush();
+        self.bet_log.flush();
+        self.user_markets.flush();
+        self.claimed_stakes.flush();
+        self.storage_accounts.flush();
+        self.moderators.flush();
+        self.locked_bonds.flush();
+        self.proposals.flush();
+        self.score_votes.flush();
+        self.resolution_requests.flush();
+    }
+
+    /// Относит изменение storage с момента `initial_usage` на аккаунт:
+    /// рост — списывается с депозита, освобождение — возвращается.
+    /// Незарегистрированный аккаунт регистрируется, недостающий депозит
+    /// переводится с внутреннего баланса NEAR
+    fn internal_charge_storage(&mut self, account_id: &AccountId, initial_usage: u64) {
+        self.flush_collections();
+        let current_usage = env::storage_usage();
+        let mut account = self
+            .storage_accounts
+            .get(account_id)
+            .cloned()
+            .unwrap_or(StorageAccount {
+                deposit: 0,
+                used_bytes: STORAGE_REGISTRATION_BYTES,
+            });
+
+        if current_usage >= initial_usage {
+            account.used_bytes += current_usage - initial_usage;
+        } else {
+            account.used_bytes = account
+                .used_bytes
+                .saturating_sub(initial_usage - current_usage)
+                .max(STORAGE_REGISTRATION_BYTES);
+        }
+
+        let required = storage_cost(account.used_bytes);
+        if required > account.deposit {
+            let shortfall = required - account.deposit;
+            let balance = self.internal_balance(account_id, &None);
+            assert!(
+                balance >= shortfall,
+                "Недостаточно storage депозита: требуется ещё {} yoctoNEAR. Пополните баланс NEAR или вызовите storage_deposit",
+                shortfall
+            );
+            self.internal_withdraw(account_id, &None, shortfall);
+            account.deposit += shortfall;
+            log!(
+                "Storage: {} yoctoNEAR с баланса {} переведено в storage депозит",
+                shortfall,
+                account_id
+            );
+        }
+        self.storage_accounts.set(account_id.clone(), Some(account));
+    }
+
+    /// Списывает только рост storage: освобождённое чужими данными
+    /// (голоса других источников, история запросов) аккаунту не зачисляется
+    fn internal_charge_storage_growth(&mut self, account_id: &AccountId, initial_usage: u64) {
+        self.flush_collections();
+        if env::storage_usage() > initial_usage {
+            self.internal_charge_storage(account_id, initial_usage);
+        }
+    }
+
+    // ══════════════════════════════════════════════════════════════
+    // АДМИНИСТРАТИВНЫЕ МЕТОДЫ
+    // ══════════════════════════════════════════════════════════════
+
+    /// Настройка OutLayer — GitHub repo с WASM Worker
+    pub fn set_outlayer_config(
+        &mut self,
+        source_repo: String,
+        source_commit: Option<String>,
+        outlayer_contract: Option<AccountId>,
+    ) {
+        assert!(
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
+        );
+        self.outlayer_source_repo = source_repo.clone();
+        if let Some(commit) = source_commit {
+            self.outlayer_source_commit = commit;
+        }
+        if let Some(contract) = outlayer_contract {
+            self.outlayer_contract = contract;
+        }
+        log!(
+            "OutLayer настроен: repo={}, commit={}, contract={}",
+            source_repo,
+            self.outlayer_source_commit,
+            self.outlayer_contract
+        );
+    }
+
+    /// Настройка комиссий протокола и потолка комиссии создателя
+    pub fn set_fee_config(&mut self, protocol_fee_bps: u16, max_creator_fee_bps: u16) {
+        assert!(
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
+        );
+        assert!(
+            protocol_fee_bps as u32 + max_creator_fee_bps as u32 <= MAX_TOTAL_FEE_BPS as u32,
+            "Суммарная комиссия: до {} bps",
+            MAX_TOTAL_FEE_BPS
+        );
+        self.fee_config = FeeConfig {
+            protocol_fee_bps,
+            max_creator_fee_bps,
+        };
+        log!(
+            "Комиссии настроены: протокол {} bps, создатель до {} bps",
+            protocol_fee_bps,
+            max_creator_fee_bps
+        );
+    }
+
+    /// Вывод комиссий из казны на кошелёк владельца (по умолчанию — всё)
+    pub fn withdraw_treasury(
+        &mut self,
+        amount: Option<U128>,
+        token_id: Option<AccountId>,
+    ) -> Promise {
+        assert!(
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
+        );
+        let treasury = self.treasury_balance(&token_id);
+        let withdraw_amount: u128 = amount.map(|a| a.into()).unwrap_or(treasury);
+        assert!(withdraw_amount > 0, "Сумма должна быть положительной");
+        assert!(treasury >= withdraw_amount, "Недостаточно средств в казне");
+
+        self.set_treasury_balance(&token_id, treasury - withdraw_amount);
+
+        let callback_args = serde_json::json!({
+            "amount": U128(withdraw_amount),
+            "token_id": token_id,
+        });
+
+        log!(
+            "Казна: вывод {} {} на {}",
+            withdraw_amount,
+            token_label(&token_id),
+            self.owner
+        );
+        transfer_promise(&self.owner, &token_id, withdraw_amount).then(
+            Promise::new(env::current_account_id()).function_call(
+                "on_treasury_withdraw_complete".to_string(),
+                serde_json::to_vec(&callback_args).unwrap(),
+                NearToken::from_yoctonear(0),
+                GAS_FOR_WITHDRAW_CALLBACK,
+            ),
+        )
+    }
+
+    /// Callback после вывода казны — при ошибке возвращает сумму в казну
+    #[private]
+    pub fn on_treasury_withdraw_complete(
+        &mut self,
+        amount: U128,
+        token_id: Option<AccountId>,
+    ) -> bool {
+        assert_eq!(
+            env::promise_results_count(),
+            1,
+            "Ожидается один результат"
+        );
+
+        if env::promise_result_checked(0, 0).is_ok() {
+            return true;
+        }
+
+        let treasury = self.treasury_balance(&token_id);
+        self.set_treasury_balance(&token_id, treasury + amount.0);
+        log!(
+            "Вывод казны не удался: {} {} возвращены",
+            amount.0,
+            token_label(&token_id)
+        );
+        false
+    }
+
+    /// Параметры оспаривания: залог, окно споров для предложений,
+    /// период оспаривания результатов oracle и награда за разрешение
+    pub fn set_resolution_config(
+        &mut self,
+        proposal_bond: U128,
+        dispute_window: u64,
+        challenge_window: u64,
+        resolver_reward_bps: u16,
+    ) {
+        assert!(
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
+        );
+        assert!(
+            dispute_window > 0 && challenge_window > 0,
+            "Окна споров должны быть положительными"
+        );
+        assert!(
+            resolver_reward_bps as u128 <= BPS_DENOMINATOR,
+            "Награда за разрешение: до {} bps комиссии протокола",
+            BPS_DENOMINATOR
+        );
+        self.resolution_config = ResolutionConfig {
+            proposal_bond,
+            dispute_window,
+            challenge_window,
+            resolver_reward_bps,
+        };
+        log!(
+            "Оспаривание: залог {} yoctoNEAR, окно предложений {} нс, окно oracle {} нс, награда {} bps",
+            proposal_bond.0,
+            dispute_window,
+            challenge_window,
+            resolver_reward_bps
+        );
+    }
+
+    /// Размер залога за создание рынка
+    pub fn set_creation_bond(&mut self, amount: U128) {
+        assert!(
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
+        );
+        self.creation_bond = amount.into();
+        log!("Залог за создание рынка: {} yoctoNEAR", amount.0);
+    }
+
+    pub fn add_moderator(&mut self, account_id: AccountId) {
+        assert!(
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
+        );
+        self.moderators.insert(account_id.clone());
+        log!("Модератор добавлен: {}", account_id);
+    }
+
+    pub fn remove_moderator(&mut self, account_id: AccountId) {
+        assert!(
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
+        );
+        self.moderators.remove(&account_id);
+        log!("Модератор удалён: {}", account_id);
+    }
+
+    /// Добавить NEP-141 токен в белый список залогов (или обновить min_bet)
+    pub fn add_collateral_token(&mut self, token_id: AccountId, min_bet: U128) {
+        assert!(
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
+        );
+        assert!(
+            min_bet.0 > 0,
+            "Минимальная ставка должна быть положительной"
+        );
+        self.collateral_tokens.insert(token_id.clone(), min_bet);
+        log!(
+            "Токен залога добавлен: {} (min_bet {})",
+            token_id,
+            min_bet.0
+        );
+    }
+
+    /// Убрать токен из белого списка — новые рынки и депозиты в нём запрещены,
+    /// существующие балансы остаются доступны для вывода
+    pub fn remove_collateral_token(&mut self, token_id: AccountId) {
+        assert!(
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
+        );
+        self.collateral_tokens.remove(&token_id);
+        log!("Токен залога удалён: {}", token_id);
+    }
+
+    /// Настройка TLS Oracle контракта
+    pub fn set_tls_oracle_config(&mut self, tls_oracle_contract: AccountId) {
+        assert!(
+            env::predecessor_account_id() == self.owner,
+            "Только владелец"
+        );
+        self.tls_oracle_contract = tls_oracle_contract.clone();
+        log!("TLS Oracle настроен: {}", tls_oracle_contract);
+    }
+
+    // ══════════════════════════════════════════════════════════════
+    // VIEW МЕТОДЫ
+    // ══════════════════════════════════════════════════════════════
+
+    pub fn get_balance(&self, account_id: AccountId, token_id: Option<AccountId>) -> U128 {
+        U128(self.internal_balance(&account_id, &token_id))
+    }
+
+    pub fn get_collateral_tokens(&self) -> serde_json::Value {
+        let tokens: Vec<serde_json::Value> = self
+            .collateral_tokens
+            .iter()
+            .map(
+                |(token_id, min_bet)| serde_json::json!({ "tokenId": token_id, "minBet": min_bet }),
+            )
+            .collect();
+        serde_json::Value::Array(tokens)
+    }
+
+    pub fn get_treasury(&self, token_id: Option<AccountId>) -> U128 {
+        U128(self.treasury_balance(&token_id))
+    }
+
+    pub fn get_pending_withdrawal(&self, account_id: AccountId) -> Option<PendingWithdrawal> {
+        self.pending_withdrawals.get(&account_id).cloned()
+    }
+
+    pub fn get_market(&self, market_id: u64) -> Option<Market> {
+        self.markets.get(&market_id).cloned()
+    }
+
+    pub fn get_markets(
+        &self,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+        category: Option<String>,
+        status: Option<String>,
+    ) -> Vec<Market> {
+        let skip = from_index.unwrap_or(0);
+        let max = limit.unwrap_or(50) as usize;
+        let mut results: Vec<Market> = Vec::new();
+        let mut skipped: u64 = 0;
+
+        let mut id = self.market_count;
+        while id > 0 && results.len() < max {
+            id -= 1;
+            let market = match self.markets.get(&id) {
+                Some(m) => m.clone(),
+                None => continue,
+            };
+
+            if let Some(ref cat) = category {
+                if cat != "все" && market.category != *cat {
+                    continue;
+                }
+            }
+            if let Some(ref st) = status {
+                if st != "все" && market.status.as_str() != st {
+                    continue;
+                }
+            }
+
+            if skipped < skip {
+                skipped += 1;
+                continue;
+            }
+
+            results.push(market);
+        }
+
+        results
+    }
+
+    pub fn get_odds(&self, market_id: u64) -> Option<serde_json::Value> {
+        let market = self.markets.get(&market_id)?;
+        let total_pool: u128 = market.total_pool.into();
+
+        let odds: Vec<f64> = market
+            .outcome_pools
+            .iter()
+            .map(|pool| {
+                let p: u128 = (*pool).into();
+                if p == 0 || total_pool == 0 {
+                    0.0
+                } else {
+                    (total_pool as f64 * 1000.0 / p as f64).round() / 1000.0
+                }
+            })
+            .collect();
+
+        // Коэффициенты за вычетом комиссий протокола и создателя
+        let net_odds: Vec<f64> = market
+            .outcome_pools
+            .iter()
+            .map(|pool| {
+                let p: u128 = (*pool).into();
+                if p == 0 {
+                    0.0
+                } else {
+                    let net_pool = market.net_payout(p, p, total_pool);
+                    (net_pool as f64 * 1000.0 / p as f64).round() / 1000.0
+                }
+            })
+            .collect();
+
+        Some(serde_json::json!({
+            "outcomes": market.outcomes,
+            "odds": odds,
+            "netOdds": net_odds,
+            "pools": market.outcome_pools,
+            "totalPool": market.total_pool,
+            "protocolFeeBps": market.protocol_fee_bps,
+            "creatorFeeBps": market.creator_fee_bps,
+        }))
+    }
+
+    /// Котировка: сколько получит ставка `amount` на `outcome`, если рынок
+    /// разрешится в её пользу при текущих пулах (с учётом самой ставки и комиссий)
+    pub fn get_payout_quote(
+        &self,
+        market_id: u64,
+        outcome: u32,
+        amount: U128,
+    ) -> Option<serde_json::Value> {
+        let market = self.markets.get(&market_id)?;
+        let stake: u128 = amount.into();
+        let pool: u128 = (*market.outcome_pools.get(outcome as usize)?).into();
+        let total_pool: u128 = market.total_pool.into();
+
+        let new_pool = pool + stake;
+        let new_total = total_pool + stake;
+        let (protocol_fee, creator_fee) = market.fees_on(new_total - new_pool);
+        let payout = market.net_payout(stake, new_pool, new_total);
+        let net_odds = if stake == 0 {
+            0.0
+        } else {
+            (payout as f64 * 1000.0 / stake as f64).round() / 1000.0
+        };
+
+        Some(serde_json::json!({
+            "outcome": outcome,
+            "stake": amount,
+            "payout": U128(payout),
+            "netOdds": net_odds,
+            "protocolFee": U128(protocol_fee),
+            "creatorFee": U128(creator_fee),
+        }))
+    }
+
+    /// История ставок рынка (постранично, в порядке размещения)
+    pub fn get_market_bets(
+        &self,
+        market_id: u64,
+        from_index: Option<u32>,
+        limit: Option<u32>,
+    ) -> Vec<Bet> {
+        let from = from_index.unwrap_or(0);
+        let max = limit.unwrap_or(100);
+
+        if let Some(legacy) = self.legacy_market_bets.get(&market_id) {
+            return legacy
+                .iter()
+                .skip(from as usize)
+                .take(max as usize)
+                .cloned()
+                .collect();
+        }
+
+        let total = match self.markets.get(&market_id) {
+            Some(m) => m.total_bets,
+            None => return Vec::new(),
+        };
+        (from..total.min(from.saturating_add(max)))
+            .filter_map(|i| self.bet_log.get(&(market_id, i)).cloned())
+            .collect()
+    }
+
+    /// Позиция аккаунта на исходе рынка
+    pub fn get_position(
+        &self,
+        market_id: u64,
+        account_id: AccountId,
+        outcome: u32,
+    ) -> Option<Position> {
+        self.positions
+            .get(&(market_id, account_id, outcome))
+            .cloned()
+    }
+
+    /// Ставки пользователя, агрегированные по (рынок, исход)
+    pub fn get_user_bets(&self, account_id: AccountId) -> Vec<Bet> {
+        let mut bets: Vec<Bet> = self
+            .legacy_user_bets
+            .get(&account_id)
+            .cloned()
+            .unwrap_or_default();
+
+        let market_ids = self
+            .user_markets
+            .get(&account_id)
+            .cloned()
+            .unwrap_or_default();
+        for market_id in market_ids {
+            let outcomes = match self.markets.get(&market_id) {
+                Some(m) => m.outcomes.len() as u32,
+                None => continue,
+            };
+            for outcome in 0..outcomes {
+                let key = (market_id, account_id.clone(), outcome);
+                if let Some(position) = self.positions.get(&key) {
+                    bets.push(Bet {
+                        market_id,
+                        user: account_id.clone(),
+                        outcome,
+                        amount: position.stake,
+                        timestamp: position.last_bet_at,
+                        claimed: position.claimed,
+                    });
+                }
+            }
+        }
+        bets
+    }
+
+    pub fn get_stats(&self) -> serde_json::Value {
+        serde_json::json!({
+            "totalMarkets": self.market_count,
+            "totalVolume": U128(self.total_volume),
+            "owner": self.owner,
+            "oracle": self.oracle,
+            "outlayerSourceRepo": self.outlayer_source_repo,
+            "outlayerContract": self.outlayer_contract,
+            "tlsOracleContract": self.tls_oracle_contract,
+            "treasury": U128(self.treasury),
+        })
+    }
+
+    pub fn get_fee_config(&self) -> FeeConfig {
+        self.fee_config.clone()
+    }
+
+    pub fn get_resolution_config(&self) -> ResolutionConfig {
+        self.resolution_config.clone()
+    }
+
+    pub fn get_proposal(&self, market_id: u64) -> Option<Proposal> {
+        self.proposals.get(&market_id).cloned()
+    }
+
+    /// Последние запросы разрешения рынка с их итогами
+    pub fn get_resolution_requests(&self, market_id: u64) -> Vec<ResolutionRequest> {
+        self.resolution_requests
+            .get(&market_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Запрос разрешения в полёте (для индикатора «разрешение идёт»)
+    pub fn get_pending_resolution(&self, market_id: u64) -> Option<ResolutionRequest> {
+        self.resolution_requests
+            .get(&market_id)
+            .and_then(|requests| requests.last())
+            .filter(|r| {
+                r.result.is_none() && env::block_height() < r.block_height + RESOLUTION_LOCK_BLOCKS
+            })
+            .cloned()
+    }
+
+    pub fn get_score_votes(&self, market_id: u64) -> Vec<ScoreVote> {
+        self.score_votes
+            .get(&market_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn get_creation_bond(&self) -> U128 {
+        U128(self.creation_bond)
+    }
+
+    /// Сумма залогов создателя в ещё не разрешённых рынках
+    pub fn get_locked_bonds(&self, account_id: AccountId) -> U128 {
+        U128(self.locked_bonds.get(&account_id).copied().unwrap_or(0))
+    }
+
+    pub fn get_moderators(&self) -> Vec<AccountId> {
+        self.moderators.iter().cloned().collect()
+    }
+
+    pub fn get_outlayer_config(&self) -> serde_json::Value {
+        serde_json::json!({
+            "source_repo": self.outlayer_source_repo,
+            "source_commit": self.outlayer_source_commit,
+            "outlayer_contract": self.outlayer_contract,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, PromiseResult};
+
+    /// 2026-01-01, наносекунды
+    const NOW: u64 = 1_767_225_600_000_000_000;
+    const HOUR: u64 = 60 * 60 * 1_000_000_000;
+
+    fn context(predecessor: AccountId) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id("nearcast.testnet".parse().unwrap())
+            .predecessor_account_id(predecessor.clone())
+            .signer_account_id(predecessor)
+            .block_timestamp(NOW);
+        builder
+    }
+
+    // ── Миграция ─────────────────────────────────────────────────
+
+    /// Layout состояния первого деплоя — отдельная копия, чтобы тест
+    /// поймал расхождение OldNearCast / OldMarket с реальным состоянием
+    mod baseline {
+        use near_sdk::json_types::U128;
+        use near_sdk::store::{IterableMap, LookupMap};
+        use near_sdk::{near, AccountId};
+
+        #[near(serializers = [borsh])]
+        pub struct Market {
+            pub id: u64,
+            pub creator: AccountId,
+            pub question: String,
+            pub description: String,
+            pub outcomes: Vec<String>,
+            pub outcome_pools: Vec<U128>,
+            pub total_pool: U128,
+            pub category: String,
+            pub created_at: u64,
+            pub bets_end_date: u64,
+            pub resolution_date: u64,
+            pub resolved_outcome: i32,
+            pub status: String,
+            pub total_bets: u32,
+            pub espn_event_id: String,
+            pub sport: String,
+            pub league: String,
+            pub market_type: String,
+        }
+
+        #[derive(Clone)]
+        #[near(serializers = [borsh])]
+        pub struct Bet {
+            pub market_id: u64,
+            pub user: AccountId,
+            pub outcome: u32,
+            pub amount: U128,
+            pub timestamp: u64,
+            pub claimed: bool,
+        }
+
+        #[near(serializers = [borsh])]
+        pub struct NearCast {
+            pub owner: AccountId,
+            pub oracle: AccountId,
+            pub markets: IterableMap<u64, Market>,
+            pub market_bets: LookupMap<u64, Vec<Bet>>,
+            pub user_bets: LookupMap<AccountId, Vec<Bet>>,
+            pub balances: LookupMap<AccountId, u128>,
+            pub market_count: u64,
+            pub total_volume: u128,
+            pub outlayer_source_repo: String,
+            pub outlayer_source_commit: String,
+            pub outlayer_contract: AccountId,
+            pub reclaim_contract: AccountId,
+            pub tls_oracle_contract: AccountId,
+        }
+    }
+
+    fn baseline_market(
+        id: u64,
+        status: &str,
+        resolved_outcome: i32,
+        market_type: &str,
+        outcomes: &[&str],
+        sport: &str,
+        bets: &[baseline::Bet],
+    ) -> baseline::Market {
+        let mut pools = vec![0u128; outcomes.len()];
+        for bet in bets {
+            pools[bet.outcome as usize] += bet.amount.0;
+        }
+        baseline::Market {
+            id,
+            creator: accounts(0),
+            question: format!("Рынок {}", id),
+            description: String::new(),
+            outcomes: outcomes.iter().map(|o| o.to_string()).collect(),
+            outcome_pools: pools.iter().map(|p| U128(*p)).collect(),
+            total_pool: U128(pools.iter().sum()),
+            category: "sports".to_string(),
+            created_at: NOW - 48 * HOUR,
+            bets_end_date: NOW - 24 * HOUR,
+            resolution_date: NOW - 12 * HOUR,
+            resolved_outcome,
+            status: status.to_string(),
+            total_bets: bets.len() as u32,
+            espn_event_id: if sport.is_empty() {
+                String::new()
+            } else {
+                format!("40{}", id)
+            },
+            sport: sport.to_string(),
+            league: String::new(),
+            market_type: market_type.to_string(),
+        }
+    }
+
+    fn baseline_bet(market_id: u64, user: AccountId, outcome: u32, near: u128) -> baseline::Bet {
+        baseline::Bet {
+            market_id,
+            user,
+            outcome,
+            amount: U128(near * ONE_NEAR),
+            timestamp: NOW - 30 * HOUR,
+            claimed: false,
+        }
+    }
+
+    /// Пишет состояние первого деплоя: разрешённый футбольный 1X2 с тремя
+    /// ставками, активный тотал и аннулированный рынок без ESPN
+    fn write_baseline_state() {
+        let bets = vec![
+            baseline_bet(0, accounts(2), 0, 2),
+            baseline_bet(0, accounts(3), 1, 3),
+            baseline_bet(0, accounts(2), 0, 1),
+        ];
+        let mut old = baseline::NearCast {
+            owner: accounts(0),
+            oracle: accounts(1),
+            markets: IterableMap::new(StorageKey::Markets),
+            market_bets: LookupMap::new(StorageKey::MarketBets),
+            user_bets: LookupMap::new(StorageKey::UserBets),
+            balances: LookupMap::new(StorageKey::Balances),
+            market_count: 3,
+            total_volume: 6 * ONE_NEAR,
+            outlayer_source_repo: "github.com/nearcast/worker".to_string(),
+            outlayer_source_commit: "main".to_string(),
+            outlayer_contract: "outlayer.testnet".parse().unwrap(),
+            reclaim_contract: "reclaim-protocol.testnet".parse().unwrap(),
+            tls_oracle_contract: "tls-oracle.testnet".parse().unwrap(),
+        };
+        old.markets.insert(
+            0,
+            baseline_market(
+                0,
+                "resolved",
+                0,
+                "winner",
+                &["Arsenal", "Chelsea", "Draw"],
+                "soccer",
+                &bets,
+            ),
+        );
+        old.markets.insert(
+            1,
+            baseline_market(
+                1,
+                "active",
+                -1,
+                "over-under",
+                &["Over 3", "Under 3"],
+                "basketball",
+                &[],
+            ),
+        );
+        old.markets.insert(
+            2,
+            baseline_market(2, "voided", -2, "winner", &["Да", "Нет"], "", &[]),
+        );
+        old.market_bets.insert(0, bets.clone());
+        old.user_bets
+            .insert(accounts(2), vec![bets[0].clone(), bets[2].clone()]);
+        old.user_bets.insert(accounts(3), vec![bets[1].clone()]);
+        old.balances.insert(accounts(2), ONE_NEAR);
+        old.markets.flush();
+        old.market_bets.flush();
+        old.user_bets.flush();
+        old.balances.flush();
+        env::state_write(&old);
+    }
+
+    /// Состояние первого деплоя после migrate и переноса всех рынков
+    fn migrated_contract() -> NearCast {
+        testing_env!(context(accounts(0)).build());
+        write_baseline_state();
+        let mut contract = NearCast::migrate();
+        while contract.migrate_markets(2) > 0 {}
+        contract
+    }
+
+    #[test]
+    fn migrate_rewrites_markets_page_by_page() {
+        testing_env!(context(accounts(0)).build());
+        write_baseline_state();
+        let mut contract = NearCast::migrate();
+        assert!(contract.markets.is_empty());
+
+        assert_eq!(contract.migrate_markets(2), 2);
+        assert_eq!(contract.markets.len(), 2);
+        assert_eq!(contract.legacy_markets.len(), 1);
+        assert_eq!(contract.migrate_markets(2), 1);
+        assert_eq!(contract.migrate_markets(2), 0);
+        assert!(contract.legacy_markets.is_empty());
+        for id in 0..3 {
+            assert_eq!(contract.markets.get(&id).unwrap().id, id);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Сначала перенесите рынки")]
+    fn migrate_market_bets_waits_for_all_markets() {
+        testing_env!(context(accounts(0)).build());
+        write_baseline_state();
+        let mut contract = NearCast::migrate();
+        contract.migrate_markets(2);
+        contract.migrate_market_bets(0, 0, 3);
+    }
+
+    #[test]
+    fn migrate_decodes_baseline_state() {
+        let contract = migrated_contract();
+        assert_eq!(contract.owner, accounts(0));
+        assert_eq!(contract.oracle, accounts(1));
+        assert_eq!(contract.market_count, 3);
+        assert_eq!(contract.total_volume, 6 * ONE_NEAR);
+        assert_eq!(contract.internal_balance(&accounts(2), &None), ONE_NEAR);
+
+        let resolved = contract.markets.get(&0).unwrap();
+        assert!(resolved.status == MarketStatus::Resolved);
+        assert_eq!(resolved.resolved_outcome, Some(0));
+        assert_eq!(
+            resolved.settlement,
+            vec![
+                OutcomeSettlement::WIN,
+                OutcomeSettlement::LOSE,
+                OutcomeSettlement::LOSE
+            ]
+        );
+        assert!(resolved.kind == Some(MarketKind::Winner { draw: true }));
+        assert!(resolved.settlement_rule == SettlementRule::IncludingOvertime);
+        assert_eq!(resolved.total_pool.0, 6 * ONE_NEAR);
+        assert_eq!(
+            resolved.resolution_deadline,
+            resolved.resolution_date + DEFAULT_RESOLUTION_GRACE
+        );
+
+        let active = contract.markets.get(&1).unwrap();
+        assert!(active.status == MarketStatus::Active);
+        assert_eq!(active.resolved_outcome, None);
+        assert!(active.settlement.is_empty());
+        // Старый контракт: "Over 3" выигрывал только при тотале больше 3
+        assert!(active.kind == Some(MarketKind::OverUnder { line_x10: 35 }));
+
+        let voided = contract.markets.get(&2).unwrap();
+        assert!(voided.status == MarketStatus::Voided);
+        assert_eq!(voided.resolved_outcome, None);
+        assert!(voided.kind.is_none());
+        assert_eq!(voided.market_type, "winner");
+    }
+
+    #[test]
+    fn migrate_market_bets_moves_bets_page_by_page() {
+        let mut contract = migrated_contract();
+
+        assert_eq!(contract.get_market_bets(0, None, None).len(), 3);
+        assert_eq!(contract.migrate_market_bets(0, 0, 2), 2);
+        assert!(contract.legacy_market_bets.contains_key(&0));
+        assert_eq!(contract.migrate_market_bets(0, 2, 2), 1);
+        assert!(!contract.legacy_market_bets.contains_key(&0));
+
+        let position = contract.get_position(0, accounts(2), 0).unwrap();
+        assert_eq!(position.stake.0, 3 * ONE_NEAR);
+        assert_eq!(contract.get_market_bets(0, None, None).len(), 3);
+        assert!(contract.legacy_user_bets.get(&accounts(2)).is_none());
+        assert_eq!(contract.get_user_bets(accounts(3)).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Следующая страница миграции")]
+    fn migrate_market_bets_rejects_repeated_page() {
+        let mut contract = migrated_contract();
+        contract.migrate_market_bets(0, 0, 1);
+        contract.migrate_market_bets(0, 0, 1);
+    }
+
+    // ── Разрешение и выплаты ─────────────────────────────────────
+
+    /// Ставки до NOW + HOUR, разрешение с NOW + 2 * HOUR
+    const RESOLUTION: u64 = NOW + 2 * HOUR;
+    /// После challenge_window — предварительный исход можно финализировать
+    const FINALIZE: u64 = RESOLUTION + DEFAULT_CHALLENGE_WINDOW;
+
+    /// Контракт: владелец accounts(0), oracle accounts(1)
+    fn setup() -> NearCast {
+        testing_env!(context(accounts(0)).build());
+        let mut contract = NearCast::new(Some(accounts(1)));
+        register(&mut contract, accounts(0));
+        // Oracle оплачивает storage своих голосов
+        register(&mut contract, accounts(1));
+        testing_env!(context(accounts(0)).build());
+        contract
+    }
+
+    /// Storage-депозит с запасом — рост storage не трогает баланс
+    fn register(contract: &mut NearCast, account: AccountId) {
+        testing_env!(context(account)
+            .attached_deposit(NearToken::from_near(1))
+            .build());
+        contract.storage_deposit(None, None);
+    }
+
+    fn fund(contract: &mut NearCast, account: AccountId, near: u128) {
+        register(contract, account.clone());
+        testing_env!(context(account)
+            .attached_deposit(NearToken::from_yoctonear(near * ONE_NEAR))
+            .build());
+        contract.deposit();
+    }
+
+    /// Баскетбольный рынок accounts(0) с комиссией создателя по максимуму
+    fn sports_market(
+        contract: &mut NearCast,
+        outcomes: &[&str],
+        kind: MarketKind,
+        policy: Option<ResolutionPolicy>,
+    ) -> u64 {
+        sport_market(contract, "basketball", outcomes, kind, policy)
+    }
+
+    fn sport_market(
+        contract: &mut NearCast,
+        sport: &str,
+        outcomes: &[&str],
+        kind: MarketKind,
+        policy: Option<ResolutionPolicy>,
+    ) -> u64 {
+        testing_env!(context(accounts(0)).build());
+        let creator_fee_bps = contract.fee_config.max_creator_fee_bps;
+        contract.create_market(
+            "Матч".to_string(),
+            String::new(),
+            outcomes.iter().map(|o| o.to_string()).collect(),
+            "sports".to_string(),
+            (NOW + HOUR).to_string(),
+            RESOLUTION.to_string(),
+            Some("401".to_string()),
+            Some(sport.to_string()),
+            Some("nba".to_string()),
+            None,
+            Some(kind),
+            Some(creator_fee_bps),
+            None,
+            policy,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn bet(contract: &mut NearCast, account: AccountId, market_id: u64, outcome: u32, near: u128) {
+        testing_env!(context(account).build());
+        contract.place_bet(market_id, outcome, U128(near * ONE_NEAR));
+    }
+
+    /// Итоговый счёт от назначенного oracle
+    fn oracle_score(contract: &mut NearCast, market_id: u64, home: i32, away: i32) -> String {
+        oracle_score_at(contract, market_id, home, away, RESOLUTION)
+    }
+
+    fn oracle_score_at(
+        contract: &mut NearCast,
+        market_id: u64,
+        home: i32,
+        away: i32,
+        timestamp: u64,
+    ) -> String {
+        testing_env!(context(accounts(1)).block_timestamp(timestamp).build());
+        contract.submit_oracle_score(
+            market_id,
+            "Home".to_string(),
+            "Away".to_string(),
+            home,
+            away,
+            "final".to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn finalize(contract: &mut NearCast, market_id: u64) {
+        testing_env!(context(accounts(5)).block_timestamp(FINALIZE).build());
+        contract.finalize_proposal(market_id);
+    }
+
+    /// Выплата claim_winnings — прирост внутреннего баланса
+    fn claim(contract: &mut NearCast, account: AccountId, market_id: u64) -> u128 {
+        testing_env!(context(account.clone()).block_timestamp(FINALIZE).build());
+        let before = contract.internal_balance(&account, &None);
+        contract.claim_winnings(market_id);
+        contract.internal_balance(&account, &None) - before
+    }
+
+    #[test]
+    fn fees_come_from_losing_pool_and_winners_split_the_rest() {
+        let mut contract = setup();
+        contract.set_fee_config(200, 100);
+        for account in [accounts(2), accounts(3), accounts(4)] {
+            fund(&mut contract, account, 10);
+        }
+        let id = sports_market(
+            &mut contract,
+            &["Over 215.5", "Under 215.5"],
+            MarketKind::OverUnder { line_x10: 2155 },
+            None,
+        );
+        bet(&mut contract, accounts(2), id, 0, 3);
+        bet(&mut contract, accounts(3), id, 0, 1);
+        bet(&mut contract, accounts(4), id, 1, 4);
+
+        oracle_score(&mut contract, id, 120, 110);
+        finalize(&mut contract, id);
+
+        // Проигравший пул 4 NEAR: протокол 2% (0.08), создатель 1% (0.04)
+        let fees = 12 * ONE_NEAR / 100;
+        assert_eq!(contract.get_treasury(None).0, 8 * ONE_NEAR / 100);
+        assert_eq!(
+            contract.get_balance(accounts(0), None).0,
+            4 * ONE_NEAR / 100
+        );
+        assert_eq!(contract.markets.get(&id).unwrap().fees_collected.0, fees);
+
+        assert_eq!(
+            claim(&mut contract, accounts(2), id),
+            (8 * ONE_NEAR - fees) * 3 / 4
+        );
+        assert_eq!(
+            claim(&mut contract, accounts(3), id),
+            (8 * ONE_NEAR - fees) / 4
+        );
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Finalized);
+    }
+
+    #[test]
+    fn push_on_the_line_refunds_every_stake_without_fees() {
+        let mut contract = setup();
+        contract.set_fee_config(200, 100);
+        for account in [accounts(2), accounts(3)] {
+            fund(&mut contract, account, 10);
+        }
+        let id = sports_market(
+            &mut contract,
+            &["Over 230", "Under 230"],
+            MarketKind::OverUnder { line_x10: 2300 },
+            None,
+        );
+        bet(&mut contract, accounts(2), id, 0, 3);
+        bet(&mut contract, accounts(3), id, 1, 2);
+
+        oracle_score(&mut contract, id, 120, 110);
+        finalize(&mut contract, id);
+
+        assert_eq!(contract.markets.get(&id).unwrap().fees_collected.0, 0);
+        assert_eq!(contract.get_treasury(None).0, 0);
+        assert_eq!(claim(&mut contract, accounts(2), id), 3 * ONE_NEAR);
+        assert_eq!(claim(&mut contract, accounts(3), id), 2 * ONE_NEAR);
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Finalized);
+    }
+
+    #[test]
+    fn quarter_handicap_splits_stakes_between_push_and_result() {
+        let mut contract = setup();
+        contract.set_fee_config(0, 0);
+        for account in [accounts(2), accounts(3)] {
+            fund(&mut contract, account, 10);
+        }
+        let id = sports_market(
+            &mut contract,
+            &["Home -0.25", "Away +0.25"],
+            MarketKind::AsianHandicap { handicap_x100: -25 },
+            None,
+        );
+        bet(&mut contract, accounts(2), id, 0, 4);
+        bet(&mut contract, accounts(3), id, 1, 2);
+
+        oracle_score(&mut contract, id, 100, 100);
+        finalize(&mut contract, id);
+
+        // Ничья при -0.25: половина ставки хозяев — push, половина проиграна,
+        // у гостей половина — push, половина выигрывает проигранные 2 NEAR
+        let market = contract.markets.get(&id).unwrap();
+        assert_eq!(market.settlement[0].win_bps, 0);
+        assert_eq!(market.settlement[0].push_bps, 5_000);
+        assert_eq!(market.settlement[1].win_bps, 5_000);
+        assert_eq!(market.settlement[1].push_bps, 5_000);
+        assert_eq!(claim(&mut contract, accounts(2), id), 2 * ONE_NEAR);
+        assert_eq!(claim(&mut contract, accounts(3), id), 4 * ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = "ждём ответа")]
+    fn resolution_lock_rejects_second_request_and_credits_excess() {
+        let mut contract = setup();
+        contract.set_outlayer_config("github.com/nearcast/worker".to_string(), None, None);
+        let id = sports_market(
+            &mut contract,
+            &["Over 215.5", "Under 215.5"],
+            MarketKind::OverUnder { line_x10: 2155 },
+            None,
+        );
+        register(&mut contract, accounts(2));
+
+        testing_env!(context(accounts(2))
+            .block_timestamp(RESOLUTION)
+            .attached_deposit(NearToken::from_millinear(500))
+            .build());
+        contract.request_resolution(id).detach();
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::ResolutionPending);
+        // В OutLayer ушло 0.1 NEAR, излишек — на внутренний баланс
+        assert_eq!(
+            contract.get_balance(accounts(2), None).0,
+            OUTLAYER_MIN_DEPOSIT * 4
+        );
+
+        contract.request_resolution(id).detach();
+    }
+
+    #[test]
+    fn storage_is_paid_from_the_deposit_without_losing_funds() {
+        let mut contract = setup();
+        let id = sports_market(
+            &mut contract,
+            &["Over 215.5", "Under 215.5"],
+            MarketKind::OverUnder { line_x10: 2155 },
+            None,
+        );
+
+        // Первый deposit сам регистрирует аккаунт из внутреннего баланса
+        testing_env!(context(accounts(2))
+            .attached_deposit(NearToken::from_near(5))
+            .build());
+        contract.deposit();
+        let registered = contract.storage_balance_of(accounts(2)).unwrap();
+        assert!(registered.total.0 > contract.storage_balance_bounds().min.0);
+        assert_eq!(
+            contract.get_balance(accounts(2), None).0 + registered.total.0,
+            5 * ONE_NEAR
+        );
+
+        bet(&mut contract, accounts(2), id, 0, 1);
+        let storage = contract.storage_balance_of(accounts(2)).unwrap();
+        assert!(storage.total.0 > registered.total.0);
+        assert_eq!(
+            contract.get_balance(accounts(2), None).0
+                + contract.get_position(id, accounts(2), 0).unwrap().stake.0
+                + storage.total.0,
+            5 * ONE_NEAR
+        );
+    }
+
+    #[test]
+    fn expired_dispute_is_voided_and_bonds_returned() {
+        let mut contract = setup();
+        fund(&mut contract, accounts(2), 10);
+        register(&mut contract, accounts(3));
+        let id = sports_market(
+            &mut contract,
+            &["Over 215.5", "Under 215.5"],
+            MarketKind::OverUnder { line_x10: 2155 },
+            None,
+        );
+        bet(&mut contract, accounts(2), id, 0, 2);
+        oracle_score(&mut contract, id, 120, 110);
+
+        testing_env!(context(accounts(3))
+            .block_timestamp(RESOLUTION)
+            .attached_deposit(NearToken::from_near(1))
+            .build());
+        contract.dispute(id);
+
+        // Oracle не разобрал спор до resolution_deadline
+        testing_env!(context(accounts(5))
+            .block_timestamp(RESOLUTION + DEFAULT_RESOLUTION_GRACE)
+            .build());
+        contract.void_expired_market(id);
+
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Voided);
+        assert!(contract.proposals.get(&id).is_none());
+        assert_eq!(contract.get_balance(accounts(3), None).0, ONE_NEAR);
+        assert_eq!(claim(&mut contract, accounts(2), id), 2 * ONE_NEAR);
+    }
+
+    #[test]
+    fn creation_bond_is_taken_from_the_internal_balance() {
+        let mut contract = setup();
+        contract.set_creation_bond(U128(ONE_NEAR));
+        fund(&mut contract, accounts(0), 5);
+
+        let id = sports_market(
+            &mut contract,
+            &["Over 215.5", "Under 215.5"],
+            MarketKind::OverUnder { line_x10: 2155 },
+            None,
+        );
+
+        assert_eq!(contract.markets.get(&id).unwrap().creator_bond.0, ONE_NEAR);
+        assert_eq!(contract.get_balance(accounts(0), None).0, 4 * ONE_NEAR);
+
+        // Attached сверх залога не уходит в залог, а зачисляется на баланс
+        testing_env!(context(accounts(0))
+            .attached_deposit(NearToken::from_near(3))
+            .build());
+        let id = contract.create_market(
+            "Матч".to_string(),
+            String::new(),
+            vec!["Да".to_string(), "Нет".to_string()],
+            "sports".to_string(),
+            (NOW + HOUR).to_string(),
+            RESOLUTION.to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert_eq!(contract.markets.get(&id).unwrap().creator_bond.0, ONE_NEAR);
+        assert_eq!(contract.get_balance(accounts(0), None).0, 6 * ONE_NEAR);
+        assert_eq!(
+            contract.locked_bonds.get(&accounts(0)).copied(),
+            Some(2 * ONE_NEAR)
+        );
+    }
+
+    /// Голос OutLayer / TLS Oracle в обход cross-contract вызова
+    fn source_score(
+        contract: &mut NearCast,
+        market_id: u64,
+        source: OracleSource,
+        home: i32,
+        away: i32,
+    ) -> String {
+        source_result(
+            contract,
+            market_id,
+            source,
+            serde_json::json!({
+                "home_team": "Home",
+                "away_team": "Away",
+                "home_score": home,
+                "away_score": away,
+                "event_status": "final",
+                "error": "",
+            }),
+        )
+    }
+
+    fn source_result(
+        contract: &mut NearCast,
+        market_id: u64,
+        source: OracleSource,
+        result: serde_json::Value,
+    ) -> String {
+        testing_env!(context(accounts(5)).block_timestamp(RESOLUTION).build());
+        let result: EspnResult = serde_json::from_value(result).unwrap();
+        contract.record_score_vote(market_id, &accounts(5), source, &result)
+    }
+
+    #[test]
+    fn quorum_is_reached_by_the_largest_agreeing_group() {
+        let mut contract = setup();
+        for account in [accounts(2), accounts(3)] {
+            fund(&mut contract, account, 10);
+        }
+        let id = sports_market(
+            &mut contract,
+            &["Over 215.5", "Under 215.5"],
+            MarketKind::OverUnder { line_x10: 2155 },
+            Some(ResolutionPolicy {
+                sources: vec![
+                    OracleSource::Outlayer,
+                    OracleSource::TlsOracle,
+                    OracleSource::DesignatedOracle,
+                ],
+                required: 2,
+            }),
+        );
+        bet(&mut contract, accounts(2), id, 0, 1);
+        bet(&mut contract, accounts(3), id, 1, 1);
+
+        assert_eq!(
+            oracle_score(&mut contract, id, 120, 110),
+            "Vote recorded: 1/2"
+        );
+        // Расхождение, но третий источник ещё может дать кворум
+        assert_eq!(
+            source_score(&mut contract, id, OracleSource::Outlayer, 100, 110),
+            "Vote recorded: 1/2"
+        );
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Closed);
+
+        source_score(&mut contract, id, OracleSource::TlsOracle, 120, 110);
+        let market = contract.markets.get(&id).unwrap();
+        assert!(market.status == MarketStatus::Proposed);
+        assert_eq!(
+            contract.proposals.get(&id).unwrap().settlement,
+            Some(vec![OutcomeSettlement::WIN, OutcomeSettlement::LOSE])
+        );
+    }
+
+    #[test]
+    fn split_votes_without_possible_quorum_dispute_the_market() {
+        let mut contract = setup();
+        let id = sports_market(
+            &mut contract,
+            &["Over 215.5", "Under 215.5"],
+            MarketKind::OverUnder { line_x10: 2155 },
+            Some(ResolutionPolicy {
+                sources: vec![OracleSource::Outlayer, OracleSource::DesignatedOracle],
+                required: 2,
+            }),
+        );
+
+        oracle_score(&mut contract, id, 120, 110);
+        assert_eq!(
+            source_score(&mut contract, id, OracleSource::Outlayer, 100, 110),
+            "Sources disagree, market disputed"
+        );
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Disputed);
+    }
+
+    /// Хоккейный матч на трёх источниках с кворумом 2
+    fn three_source_market(contract: &mut NearCast, team_ids: Option<Vec<String>>) -> u64 {
+        testing_env!(context(accounts(0)).build());
+        contract.create_market(
+            "Матч".to_string(),
+            String::new(),
+            vec!["Home".to_string(), "Away".to_string()],
+            "sports".to_string(),
+            (NOW + HOUR).to_string(),
+            RESOLUTION.to_string(),
+            Some("401".to_string()),
+            Some("hockey".to_string()),
+            Some("nhl".to_string()),
+            None,
+            Some(MarketKind::Winner { draw: false }),
+            None,
+            None,
+            Some(ResolutionPolicy {
+                sources: vec![
+                    OracleSource::Outlayer,
+                    OracleSource::TlsOracle,
+                    OracleSource::DesignatedOracle,
+                ],
+                required: 2,
+            }),
+            None,
+            team_ids,
+            None,
+        )
+    }
+
+    fn team_result(home_id: &str, away_id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "home_team": "Home", "away_team": "Away",
+            "home_team_id": home_id, "away_team_id": away_id,
+            "home_score": 3, "away_score": 2, "event_status": "final", "error": "",
+        })
+    }
+
+    #[test]
+    fn votes_with_swapped_team_ids_do_not_agree() {
+        let mut contract = setup();
+        let id = three_source_market(&mut contract, Some(vec!["10".into(), "20".into()]));
+        testing_env!(context(accounts(5)).block_timestamp(RESOLUTION).build());
+        contract.close_market(id);
+
+        // Тот же счёт 3:2, но хозяева — другая команда
+        source_result(
+            &mut contract,
+            id,
+            OracleSource::Outlayer,
+            team_result("10", "20"),
+        );
+        assert_eq!(
+            source_result(
+                &mut contract,
+                id,
+                OracleSource::TlsOracle,
+                team_result("20", "10")
+            ),
+            "Vote recorded: 1/2"
+        );
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Closed);
+
+        let summary = source_result(
+            &mut contract,
+            id,
+            OracleSource::DesignatedOracle,
+            team_result("20", "10"),
+        );
+        assert!(summary.starts_with("Provisionally resolved: Winner(1)"));
+    }
+
+    #[test]
+    fn vote_without_extra_time_does_not_bridge_conflicting_shootouts() {
+        let mut contract = setup();
+        let id = three_source_market(&mut contract, None);
+        let shootout = |home: i32, away: i32| {
+            serde_json::json!({
+                "home_team": "Home", "away_team": "Away",
+                "home_score": 2, "away_score": 2, "event_status": "final", "error": "",
+                "overtime": true, "shootout": true,
+                "home_shootout_score": home, "away_shootout_score": away,
+            })
+        };
+
+        // 2:2 без данных о серии совместим с обоими исходами серии,
+        // но кворум из противоречащих друг другу голосов не собирается
+        assert_eq!(oracle_score(&mut contract, id, 2, 2), "Vote recorded: 1/2");
+        assert_eq!(
+            source_result(&mut contract, id, OracleSource::Outlayer, shootout(3, 2)),
+            "Vote recorded: 1/2"
+        );
+        assert_eq!(
+            source_result(&mut contract, id, OracleSource::TlsOracle, shootout(2, 3)),
+            "Sources disagree, market disputed"
+        );
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Disputed);
+    }
+
+    /// Контекст callback контракта с заданным результатом вызова
+    fn callback_context(block_height: u64, result: PromiseResult) {
+        testing_env!(
+            context("nearcast.testnet".parse().unwrap())
+                .block_timestamp(RESOLUTION)
+                .block_height(block_height)
+                .build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+    }
+
+    #[test]
+    fn failed_outlayer_call_is_refunded_even_after_the_lock_expired() {
+        let mut contract = setup();
+        contract.set_outlayer_config("github.com/nearcast/worker".to_string(), None, None);
+        let id = sports_market(
+            &mut contract,
+            &["Over 215.5", "Under 215.5"],
+            MarketKind::OverUnder { line_x10: 2155 },
+            None,
+        );
+
+        for (account, height) in [(accounts(2), 0), (accounts(3), RESOLUTION_LOCK_BLOCKS)] {
+            register(&mut contract, account.clone());
+            testing_env!(context(account)
+                .block_timestamp(RESOLUTION)
+                .block_height(height)
+                .attached_deposit(NearToken::from_yoctonear(OUTLAYER_MIN_DEPOSIT))
+                .build());
+            contract.request_resolution(id).detach();
+        }
+
+        // Ответ на первый, уже просроченный запрос: вызов OutLayer упал
+        callback_context(RESOLUTION_LOCK_BLOCKS + 1, PromiseResult::Failed);
+        assert_eq!(
+            contract.on_resolution_result(id, accounts(2), 0),
+            "Stale resolution request"
+        );
+        assert_eq!(
+            contract.get_balance(accounts(2), None).0,
+            OUTLAYER_MIN_DEPOSIT
+        );
+
+        callback_context(RESOLUTION_LOCK_BLOCKS + 1, PromiseResult::Failed);
+        assert_eq!(
+            contract.on_resolution_result(id, accounts(3), 1),
+            "OutLayer call failed"
+        );
+        assert_eq!(
+            contract.get_balance(accounts(3), None).0,
+            OUTLAYER_MIN_DEPOSIT
+        );
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Closed);
+    }
+
+    #[test]
+    #[should_panic(expected = "Окно для оспаривания ещё открыто")]
+    fn open_challenge_window_blocks_voiding_at_the_deadline() {
+        let mut contract = setup();
+        let id = sports_market(
+            &mut contract,
+            &["Over 215.5", "Under 215.5"],
+            MarketKind::OverUnder { line_x10: 2155 },
+            None,
+        );
+        let deadline = RESOLUTION + DEFAULT_RESOLUTION_GRACE;
+        oracle_score_at(&mut contract, id, 120, 110, deadline - HOUR);
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Proposed);
+
+        testing_env!(context(accounts(5)).block_timestamp(deadline).build());
+        contract.void_expired_market(id);
+    }
+
+    /// Ответ TLS Oracle get_attestation для события "401"
+    fn tls_attestation(response_data: serde_json::Value) -> PromiseResult {
+        PromiseResult::Successful(
+            serde_json::to_vec(&serde_json::json!({
+                "sourceUrl": "https://site.api.espn.com/apis/site/v2/sports/soccer/eng.1/summary?event=401",
+                "serverName": "site.api.espn.com",
+                "responseData": response_data.to_string(),
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn tls_vote(
+        contract: &mut NearCast,
+        market_id: u64,
+        response_data: serde_json::Value,
+    ) -> String {
+        tls_request(contract, market_id);
+        tls_callback(contract, market_id, response_data)
+    }
+
+    /// Бесплатный вызов resolve_with_tls_attestation от accounts(2)
+    fn tls_request(contract: &mut NearCast, market_id: u64) {
+        testing_env!(context(accounts(2)).block_timestamp(RESOLUTION).build());
+        contract
+            .resolve_with_tls_attestation(
+                market_id,
+                7,
+                2,
+                1,
+                "Home".to_string(),
+                "Away".to_string(),
+                "final".to_string(),
+            )
+            .detach();
+    }
+
+    fn tls_callback(
+        contract: &mut NearCast,
+        market_id: u64,
+        response_data: serde_json::Value,
+    ) -> String {
+        callback_context(0, tls_attestation(response_data));
+        contract.on_tls_attestation_result(
+            market_id,
+            accounts(2),
+            7,
+            2,
+            1,
+            "Home".to_string(),
+            "Away".to_string(),
+            "final".to_string(),
+        )
+    }
+
+    #[test]
+    fn tls_vote_settles_soccer_on_the_attested_regulation_score() {
+        let mut contract = setup();
+        let id = sport_market(
+            &mut contract,
+            "soccer",
+            &["Home", "Draw", "Away"],
+            MarketKind::Winner { draw: true },
+            None,
+        );
+        register(&mut contract, accounts(2));
+        let final_score = serde_json::json!({
+            "ht": "Home", "at": "Away", "hs": 2, "as": 1, "st": "final", "eid": "401",
+        });
+        assert_eq!(
+            tls_vote(&mut contract, id, final_score),
+            "Cannot judge: Extra time not reported, regulation score unknown"
+        );
+        assert!(contract.get_score_votes(id).is_empty());
+
+        // Победный гол в овертайме: по основному времени ничья 1:1
+        let with_extra_time = serde_json::json!({
+            "ht": "Home", "at": "Away", "hs": 2, "as": 1, "st": "final", "eid": "401",
+            "ot": true, "so": false, "rhs": 1, "ras": 1,
+        });
+        tls_vote(&mut contract, id, with_extra_time);
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Proposed);
+        assert_eq!(
+            contract.proposals.get(&id).unwrap().settlement,
+            Some(vec![
+                OutcomeSettlement::LOSE,
+                OutcomeSettlement::WIN,
+                OutcomeSettlement::LOSE
+            ])
+        );
+    }
+
+    #[test]
+    fn source_outside_the_policy_cannot_reschedule() {
+        let mut contract = setup();
+        let id = sports_market(
+            &mut contract,
+            &["Over 215.5", "Under 215.5"],
+            MarketKind::OverUnder { line_x10: 2155 },
+            Some(ResolutionPolicy {
+                sources: vec![OracleSource::Outlayer, OracleSource::DesignatedOracle],
+                required: 2,
+            }),
+        );
+        assert_eq!(
+            oracle_score(&mut contract, id, 120, 110),
+            "Vote recorded: 1/2"
+        );
+
+        let postponed = serde_json::json!({
+            "home_team": "Home",
+            "away_team": "Away",
+            "home_score": 0,
+            "away_score": 0,
+            "event_status": "postponed",
+            "error": "",
+            "start_time": RESOLUTION / 1_000_000_000 + 7 * 24 * 3600,
+        });
+        assert_eq!(
+            source_result(&mut contract, id, OracleSource::TlsOracle, postponed),
+            "Source tls_oracle is not allowed"
+        );
+        assert_eq!(
+            contract.markets.get(&id).unwrap().resolution_date,
+            RESOLUTION
+        );
+        assert_eq!(contract.get_score_votes(id).len(), 1);
+    }
+
+    #[test]
+    fn tls_attestation_neither_takes_nor_releases_the_lock() {
+        let mut contract = setup();
+        contract.set_outlayer_config("github.com/nearcast/worker".to_string(), None, None);
+        let id = sport_market(
+            &mut contract,
+            "soccer",
+            &["Home", "Draw", "Away"],
+            MarketKind::Winner { draw: true },
+            Some(ResolutionPolicy {
+                sources: vec![OracleSource::Outlayer, OracleSource::TlsOracle],
+                required: 2,
+            }),
+        );
+
+        register(&mut contract, accounts(2));
+        register(&mut contract, accounts(3));
+
+        // Бесплатный TLS-вызов не мешает платному запросу OutLayer
+        tls_request(&mut contract, id);
+        testing_env!(context(accounts(3))
+            .block_timestamp(RESOLUTION)
+            .attached_deposit(NearToken::from_yoctonear(OUTLAYER_MIN_DEPOSIT))
+            .build());
+        contract.request_resolution(id).detach();
+
+        let attested = serde_json::json!({
+            "ht": "Home", "at": "Away", "hs": 2, "as": 1, "st": "final", "eid": "401",
+            "ot": false, "so": false,
+        });
+        assert_eq!(
+            tls_callback(&mut contract, id, attested),
+            "Vote recorded: 1/2"
+        );
+        // Запрос OutLayer всё ещё в полёте — блокировка за ним
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::ResolutionPending);
+        assert_eq!(contract.resolution_requests.get(&id).unwrap().len(), 1);
+    }
+
+    /// Неспортивный рынок — разрешается оптимистичным предложением
+    fn question_market(contract: &mut NearCast) -> u64 {
+        testing_env!(context(accounts(0)).build());
+        contract.create_market(
+            "Выйдет ли релиз до конца года?".to_string(),
+            String::new(),
+            vec!["Да".to_string(), "Нет".to_string()],
+            "tech".to_string(),
+            (NOW + HOUR).to_string(),
+            RESOLUTION.to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        )
+    }
+
+    fn propose(
+        contract: &mut NearCast,
+        proposer: AccountId,
+        market_id: u64,
+        outcome: Option<u32>,
+        bond: u128,
+    ) {
+        testing_env!(context(proposer)
+            .block_timestamp(RESOLUTION)
+            .attached_deposit(NearToken::from_yoctonear(bond))
+            .build());
+        contract.propose_outcome(market_id, outcome);
+    }
+
+    #[test]
+    fn proposals_and_withdrawals_pay_for_their_storage_until_released() {
+        let mut contract = setup();
+        let id = question_market(&mut contract);
+        register(&mut contract, accounts(2));
+        let available = |contract: &NearCast, account: AccountId| {
+            contract.storage_balance_of(account).unwrap().available.0
+        };
+        let before = available(&contract, accounts(2));
+
+        // Предложение оплачивает предложивший, после расчёта storage возвращается
+        propose(
+            &mut contract,
+            accounts(2),
+            id,
+            Some(0),
+            DEFAULT_PROPOSAL_BOND,
+        );
+        let proposed = available(&contract, accounts(2));
+        assert!(proposed < before);
+
+        testing_env!(context(accounts(5))
+            .block_timestamp(RESOLUTION + DEFAULT_DISPUTE_WINDOW)
+            .build());
+        contract.finalize_proposal(id);
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Resolved);
+        assert!(contract.proposals.get(&id).is_none());
+        let settled = available(&contract, accounts(2));
+        assert!(settled > proposed);
+
+        // Незавершённый вывод тоже занимает storage до callback
+        testing_env!(context(accounts(2)).build());
+        contract
+            .withdraw(U128(DEFAULT_PROPOSAL_BOND), None)
+            .detach();
+        assert!(available(&contract, accounts(2)) < settled);
+
+        callback_context(0, PromiseResult::Failed);
+        assert!(!contract.on_withdraw_complete(accounts(2), U128(DEFAULT_PROPOSAL_BOND), None));
+        assert!(contract.get_pending_withdrawal(accounts(2)).is_none());
+        assert_eq!(available(&contract, accounts(2)), settled);
+    }
+
+    // ── Определение исхода ───────────────────────────────────────
+
+    fn espn(result: serde_json::Value) -> EspnResult {
+        serde_json::from_value(result).unwrap()
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    /// Итог матча: хозяева "Boston Celtics" (id 2), гости "LA Lakers" (id 13)
+    fn celtics_lakers(home: i32, away: i32) -> EspnResult {
+        espn(serde_json::json!({
+            "home_team": "Boston Celtics", "away_team": "LA Lakers",
+            "home_team_id": "2", "away_team_id": "13",
+            "home_score": home, "away_score": away, "event_status": "final", "error": "",
+        }))
+    }
+
+    #[test]
+    fn winner_is_matched_by_team_id_not_by_position() {
+        // Гости первым исходом, названия не совпадают с ESPN
+        let outcomes = strings(&["Lakers", "Celtics"]);
+        let team_ids = strings(&["13", "2"]);
+
+        let (resolution, summary) =
+            resolve_winner(&outcomes, &team_ids, false, &celtics_lakers(110, 104));
+        assert_eq!(resolution, Resolution::Winner(1));
+        assert_eq!(summary, "Celtics wins 110:104");
+
+        let (resolution, _) = resolve_winner(&outcomes, &team_ids, false, &celtics_lakers(98, 101));
+        assert_eq!(resolution, Resolution::Winner(0));
+
+        // Ничья в 2-way рынке
+        let (resolution, _) =
+            resolve_winner(&outcomes, &team_ids, false, &celtics_lakers(100, 100));
+        assert_eq!(resolution, Resolution::Push);
+    }
+
+    #[test]
+    fn unknown_or_missing_team_id_voids_the_winner_market() {
+        let outcomes = strings(&["Celtics", "Knicks"]);
+        let team_ids = strings(&["2", "18"]);
+
+        let (resolution, summary) =
+            resolve_winner(&outcomes, &team_ids, false, &celtics_lakers(110, 104));
+        assert_eq!(resolution, Resolution::Void);
+        assert!(summary.starts_with("Teams not matched to outcomes"));
+
+        // С привязкой к id совпадение названий не спасает
+        let mut by_name = celtics_lakers(110, 104);
+        by_name.away_team = "Knicks".to_string();
+        by_name.away_team_id = String::new();
+        let (resolution, _) = resolve_winner(&outcomes, &team_ids, false, &by_name);
+        assert_eq!(resolution, Resolution::Void);
+    }
+
+    #[test]
+    fn draw_outcome_is_the_one_without_a_team_id() {
+        let outcomes = strings(&["Arsenal", "Ничья", "Chelsea"]);
+        let team_ids = strings(&["359", "", "363"]);
+        let derby = |home: i32, away: i32| {
+            espn(serde_json::json!({
+                "home_team": "Chelsea", "away_team": "Arsenal",
+                "home_team_id": "363", "away_team_id": "359",
+                "home_score": home, "away_score": away, "event_status": "final", "error": "",
+            }))
+        };
+
+        let (resolution, summary) = resolve_winner(&outcomes, &team_ids, true, &derby(1, 1));
+        assert_eq!(resolution, Resolution::Winner(1));
+        assert_eq!(summary, "Draw 1:1");
+        let (resolution, _) = resolve_winner(&outcomes, &team_ids, true, &derby(0, 2));
+        assert_eq!(resolution, Resolution::Winner(0));
+    }
+
+    #[test]
+    fn legacy_market_without_team_ids_matches_names() {
+        let outcomes = strings(&["Celtics", "Lakers"]);
+        let (resolution, _) = resolve_winner(&outcomes, &[], false, &celtics_lakers(110, 104));
+        assert_eq!(resolution, Resolution::Winner(0));
+    }
+
+    #[test]
+    fn vote_without_team_ids_is_skipped_on_a_bound_market() {
+        let mut contract = setup();
+        let id = three_source_market(&mut contract, Some(vec!["10".into(), "20".into()]));
+        testing_env!(context(accounts(5)).block_timestamp(RESOLUTION).build());
+        contract.close_market(id);
+
+        assert_eq!(
+            source_result(
+                &mut contract,
+                id,
+                OracleSource::Outlayer,
+                team_result("10", "")
+            ),
+            "Cannot judge: team ids not reported"
+        );
+        assert!(contract.get_score_votes(id).is_empty());
+
+        // Без привязки к id тот же голос принимается
+        let legacy = three_source_market(&mut contract, None);
+        testing_env!(context(accounts(5)).block_timestamp(RESOLUTION).build());
+        contract.close_market(legacy);
+        assert_eq!(
+            source_result(
+                &mut contract,
+                legacy,
+                OracleSource::Outlayer,
+                team_result("10", "")
+            ),
+            "Vote recorded: 1/2"
+        );
+    }
+
+    #[test]
+    fn market_kinds_accept_well_formed_outcomes() {
+        MarketKind::Winner { draw: true }.validate(&strings(&["Arsenal", "Draw", "Chelsea"]), &[]);
+        MarketKind::OverUnder { line_x10: 30 }.validate(&strings(&["Больше 3", "Меньше 3"]), &[]);
+        MarketKind::BothScore.validate(&strings(&["Yes", "No"]), &[]);
+        MarketKind::CorrectScore.validate(&strings(&["1-0", "2:1", "Any other"]), &[]);
+        MarketKind::Range.validate(&strings(&["0-1 goals", "2-3 goals", "4+ goals"]), &[]);
+        MarketKind::PeriodWinner {
+            from_period: 1,
+            to_period: 2,
+            draw: false,
+        }
+        .validate(&strings(&["Celtics", "Lakers"]), &strings(&["2", "13"]));
+    }
+
+    #[test]
+    fn legacy_market_type_reads_the_line_from_the_first_outcome() {
+        let kind = MarketKind::from_legacy("over-under", &strings(&["Over 2.5", "Under 2.5"]));
+        assert!(kind == MarketKind::OverUnder { line_x10: 25 });
+        let kind = MarketKind::from_legacy("winner", &strings(&["Arsenal", "Draw", "Chelsea"]));
+        assert!(kind == MarketKind::Winner { draw: true });
+    }
+
+    #[test]
+    #[should_panic(expected = "Winner с ничьёй: нужен исход")]
+    fn three_way_winner_needs_a_draw_outcome() {
+        MarketKind::Winner { draw: true }.validate(&strings(&["Arsenal", "Chelsea", "Spurs"]), &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Тотал: исход 0")]
+    fn over_under_outcomes_must_be_over_then_under() {
+        MarketKind::OverUnder { line_x10: 25 }.validate(&strings(&["Under 2.5", "Over 2.5"]), &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Линия тотала: целая")]
+    fn over_under_line_is_whole_or_half() {
+        MarketKind::OverUnder { line_x10: 23 }.validate(&strings(&["Over 2.3", "Under 2.3"]), &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Точный счёт: счёт \"2:1\" повторяется")]
+    fn correct_score_outcomes_are_unique() {
+        MarketKind::CorrectScore.validate(&strings(&["2-1", "2:1", "Any other"]), &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Диапазон: корзины пересекаются")]
+    fn range_buckets_must_not_overlap() {
+        MarketKind::Range.validate(&strings(&["0-2", "2-3", "4+"]), &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Периоды: 1 <= from_period <= to_period")]
+    fn period_range_starts_from_the_first_period() {
+        MarketKind::PeriodWinner {
+            from_period: 3,
+            to_period: 2,
+            draw: false,
+        }
+        .validate(&strings(&["Celtics", "Lakers"]), &[]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Тип рынка (kind) задаётся только для спортивных рынков")]
+    fn kind_is_rejected_on_a_non_sports_market() {
+        let mut contract = setup();
+        contract.create_market(
+            "Выйдет ли релиз до конца года?".to_string(),
+            String::new(),
+            strings(&["Да", "Нет"]),
+            "tech".to_string(),
+            (NOW + HOUR).to_string(),
+            RESOLUTION.to_string(),
+            None,
+            None,
+            None,
+            None,
+            Some(MarketKind::BothScore),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+    }
+
+    #[test]
+    fn spread_applies_the_signed_home_handicap() {
+        let team_ids = strings(&["2", "13"]);
+
+        // Фаворит -5.5: нужно выиграть 6+ очками
+        let (resolution, summary) = resolve_spread(-55, &team_ids, &celtics_lakers(110, 104));
+        assert_eq!(resolution, Resolution::Winner(0));
+        assert_eq!(summary, "Boston Celtics -5.5 covers (110:104)");
+        let (resolution, _) = resolve_spread(-55, &team_ids, &celtics_lakers(110, 105));
+        assert_eq!(resolution, Resolution::Winner(1));
+
+        // Андердог +3.5: поражение до 3 очков включительно — фора сыграла
+        let (resolution, _) = resolve_spread(35, &team_ids, &celtics_lakers(100, 103));
+        assert_eq!(resolution, Resolution::Winner(0));
+        let (resolution, summary) = resolve_spread(35, &team_ids, &celtics_lakers(100, 104));
+        assert_eq!(resolution, Resolution::Winner(1));
+        assert_eq!(summary, "LA Lakers covers vs +3.5 (100:104)");
+    }
+
+    #[test]
+    fn whole_spread_on_the_margin_is_a_push() {
+        let (resolution, summary) = resolve_spread(-50, &[], &celtics_lakers(110, 105));
+        assert_eq!(resolution, Resolution::Push);
+        assert_eq!(summary, "Boston Celtics -5.0 ties (110:105) — push");
+        let (resolution, _) = resolve_spread(30, &[], &celtics_lakers(100, 103));
+        assert_eq!(resolution, Resolution::Push);
+    }
+
+    #[test]
+    fn spread_is_void_when_home_team_differs_from_the_first_outcome() {
+        // Рынок создан на Lakers с форой, а ESPN показывает их гостями
+        let (resolution, _) =
+            resolve_spread(-55, &strings(&["13", "2"]), &celtics_lakers(110, 104));
+        assert_eq!(resolution, Resolution::Void);
+    }
+
+    #[test]
+    fn legacy_spread_line_is_the_trailing_signed_word() {
+        let kind = MarketKind::from_legacy("spread", &strings(&["Schalke 04 +1.5", "Köln -1.5"]));
+        assert!(kind == MarketKind::Spread { handicap_x10: 15 });
+        let kind = MarketKind::from_legacy("spread", &strings(&["Lakers -5.5", "Celtics +5.5"]));
+        assert!(kind == MarketKind::Spread { handicap_x10: -55 });
+    }
+
+    #[test]
+    #[should_panic(expected = "Укажите фору в kind или в конце названия исхода со знаком")]
+    fn legacy_spread_without_a_signed_line_is_rejected() {
+        // "04" в названии команды — не линия
+        MarketKind::from_legacy("spread", &strings(&["Schalke 04", "Köln"]));
+    }
+
+    #[test]
+    fn correct_score_falls_back_to_any_other() {
+        let outcomes = strings(&["1-0", "2-1", "1:1", "Any other"]);
+        let (resolution, summary) = resolve_correct_score(&outcomes, 1, 1);
+        assert_eq!(resolution, Resolution::Winner(2));
+        assert_eq!(summary, "Correct score 1:1");
+        // Счёт "хозяева-гости": 0:1 — не 1-0
+        let (resolution, _) = resolve_correct_score(&outcomes, 0, 1);
+        assert_eq!(resolution, Resolution::Winner(3));
+
+        let (resolution, summary) = resolve_correct_score(&outcomes[..3], 3, 3);
+        assert_eq!(resolution, Resolution::Void);
+        assert_eq!(summary, "Score 3:3 not listed and no \"Any other\" outcome");
+    }
+
+    #[test]
+    fn range_bucket_bounds_are_inclusive() {
+        let outcomes = strings(&["0-1 goals", "2-3 goals", "4+ goals"]);
+        let bucket = |home: i32, away: i32| resolve_range(&outcomes, home, away).0;
+        assert_eq!(bucket(0, 0), Resolution::Winner(0));
+        assert_eq!(bucket(1, 0), Resolution::Winner(0));
+        assert_eq!(bucket(1, 1), Resolution::Winner(1));
+        assert_eq!(bucket(2, 1), Resolution::Winner(1));
+        assert_eq!(bucket(2, 2), Resolution::Winner(2));
+        assert_eq!(bucket(7, 3), Resolution::Winner(2));
+
+        // Дыра между корзинами — аннулирование
+        let (resolution, summary) = resolve_range(&strings(&["0-1", "3+"]), 1, 1);
+        assert_eq!(resolution, Resolution::Void);
+        assert_eq!(summary, "Total 2 outside all buckets (1:1)");
+    }
+
+    #[test]
+    fn correct_score_settles_on_the_regulation_score() {
+        let mut contract = setup();
+        let id = sport_market(
+            &mut contract,
+            "soccer",
+            &["1-0", "1-1", "2-1", "Any other"],
+            MarketKind::CorrectScore,
+            None,
+        );
+        let extra_time = |overtime: Option<bool>| {
+            let mut result = serde_json::json!({
+                "home_team": "Home", "away_team": "Away",
+                "home_score": 2, "away_score": 1, "event_status": "final", "error": "",
+                "regulation_home_score": 1, "regulation_away_score": 1,
+            });
+            if let Some(overtime) = overtime {
+                result["overtime"] = overtime.into();
+                result["shootout"] = false.into();
+            }
+            result
+        };
+        testing_env!(context(accounts(5)).block_timestamp(RESOLUTION).build());
+        contract.close_market(id);
+
+        // Без данных об овертайме 2:1 может быть и счётом основного времени
+        assert_eq!(
+            source_result(&mut contract, id, OracleSource::Outlayer, extra_time(None)),
+            "Cannot judge: Extra time not reported, regulation score unknown"
+        );
+        assert!(contract.get_score_votes(id).is_empty());
+
+        source_result(
+            &mut contract,
+            id,
+            OracleSource::Outlayer,
+            extra_time(Some(true)),
+        );
+        assert_eq!(
+            contract.proposals.get(&id).unwrap().settlement,
+            Some(vec![
+                OutcomeSettlement::LOSE,
+                OutcomeSettlement::WIN,
+                OutcomeSettlement::LOSE,
+                OutcomeSettlement::LOSE
+            ])
+        );
+    }
 
-                log!(
-                    "TLS Oracle: рынок #{} — исход={}, счёт={}:{}, {}",
-                    market_id,
-                    winning_outcome,
-                    espn_result.home_score,
-                    espn_result.away_score,
-                    reasoning
-                );
+    fn competitors(places: &[(&str, bool, u32)]) -> Vec<CompetitorResult> {
+        places
+            .iter()
+            .map(|&(id, winner, position)| CompetitorResult {
+                id: id.to_string(),
+                winner,
+                position,
+            })
+            .collect()
+    }
 
-                format!(
-                    "Resolved via TLS Oracle: outcome={}, score={}:{}, {}",
-                    winning_outcome,
-                    espn_result.home_score,
-                    espn_result.away_score,
-                    reasoning
-                )
-            }
-            Err(_) => {
-                log!(
-                    "TLS Oracle: view call не удался для аттестации #{}",
-                    attestation_id
-                );
-                "TLS Oracle view call failed".to_string()
-            }
-        }
+    /// Закрытый рынок с исходами по ESPN id — готов к голосам источников
+    fn bound_market(
+        contract: &mut NearCast,
+        sport: &str,
+        outcomes: &[&str],
+        team_ids: &[&str],
+        kind: MarketKind,
+    ) -> u64 {
+        testing_env!(context(accounts(0)).build());
+        let id = contract.create_market(
+            "Матч".to_string(),
+            String::new(),
+            strings(outcomes),
+            "sports".to_string(),
+            (NOW + HOUR).to_string(),
+            RESOLUTION.to_string(),
+            Some("401".to_string()),
+            Some(sport.to_string()),
+            None,
+            None,
+            Some(kind),
+            None,
+            None,
+            None,
+            None,
+            Some(strings(team_ids)),
+            None,
+        );
+        testing_env!(context(accounts(5)).block_timestamp(RESOLUTION).build());
+        contract.close_market(id);
+        id
     }
 
-    // ══════════════════════════════════════════════════════════════
-    // ПОЛУЧЕНИЕ ВЫИГРЫША / ВОЗВРАТ
-    // ══════════════════════════════════════════════════════════════
+    #[test]
+    fn head_to_head_follows_the_winner_flag() {
+        let outcomes = strings(&["Alcaraz", "Sinner"]);
+        let athlete_ids = strings(&["3782", "3623"]);
 
-    pub fn claim_winnings(&mut self, market_id: u64) {
-        let sender = env::predecessor_account_id();
+        let (resolution, summary) = resolve_head_to_head(
+            &outcomes,
+            &athlete_ids,
+            false,
+            &competitors(&[("3623", false, 2), ("3782", true, 1)]),
+        );
+        assert_eq!(resolution, Resolution::Winner(0));
+        assert_eq!(summary, "Alcaraz wins");
+
+        // Без победителя: 2-way — push, флаг у обоих — аннулирование
+        let (resolution, _) = resolve_head_to_head(
+            &outcomes,
+            &athlete_ids,
+            false,
+            &competitors(&[("3782", false, 0), ("3623", false, 0)]),
+        );
+        assert_eq!(resolution, Resolution::Push);
+        let (resolution, _) = resolve_head_to_head(
+            &outcomes,
+            &athlete_ids,
+            false,
+            &competitors(&[("3782", true, 1), ("3623", true, 1)]),
+        );
+        assert_eq!(resolution, Resolution::Void);
+    }
 
-        let market = self.markets.get(&market_id).expect("Рынок не найден").clone();
-        assert!(
-            market.status == "resolved" || market.status == "voided",
-            "Рынок ещё не разрешён"
+    #[test]
+    fn drawn_fight_settles_on_the_outcome_without_an_athlete() {
+        let (resolution, summary) = resolve_head_to_head(
+            &strings(&["Makhachev", "Draw", "Poirier"]),
+            &strings(&["3332412", "", "2504169"]),
+            true,
+            &competitors(&[("3332412", false, 0), ("2504169", false, 0)]),
         );
+        assert_eq!(resolution, Resolution::Winner(1));
+        assert_eq!(summary, "No winner — draw");
+    }
 
-        let mut bets = self.market_bets.get(&market_id).cloned().unwrap_or_default();
-        let mut payout: u128 = 0;
-        let mut has_claimed = false;
-        let mut bet_indices: Vec<usize> = Vec::new();
-
-        if market.status == "voided" {
-            for (i, bet) in bets.iter().enumerate() {
-                if bet.user == sender {
-                    if bet.claimed {
-                        has_claimed = true;
-                        break;
-                    }
-                    let amt: u128 = bet.amount.into();
-                    payout += amt;
-                    bet_indices.push(i);
-                }
-            }
-        } else {
-            for (i, bet) in bets.iter().enumerate() {
-                if bet.user == sender && bet.outcome == market.resolved_outcome as u32 {
-                    if bet.claimed {
-                        has_claimed = true;
-                        break;
-                    }
-                    let amt: u128 = bet.amount.into();
-                    payout += amt;
-                    bet_indices.push(i);
-                }
-            }
+    #[test]
+    fn top_n_includes_the_last_qualifying_place() {
+        let field = competitors(&[
+            ("1", true, 1),
+            ("44", false, 3),
+            ("16", false, 4),
+            ("4", false, 0),
+        ]);
 
-            if payout > 0 {
-                let total_pool: u128 = market.total_pool.into();
-                let winning_pool: u128 =
-                    market.outcome_pools[market.resolved_outcome as usize].into();
-                if winning_pool > 0 {
-                    // Безопасный расчёт без overflow u128:
-                    // payout * total_pool может переполнить (yoctoNEAR ~ 10^24)
-                    // Делим через промежуточный масштаб
-                    payout = safe_mul_div(payout, total_pool, winning_pool);
-                }
-            }
-        }
+        let (resolution, summary) = resolve_top_n(&strings(&["44", "16", "4"]), 3, &field);
+        assert_eq!(
+            resolution,
+            Resolution::Split(vec![
+                OutcomeSettlement::WIN,
+                OutcomeSettlement::LOSE,
+                OutcomeSettlement::LOSE
+            ])
+        );
+        assert_eq!(summary, "Top 3: 44#3, 16#4, 4#0");
+
+        // Никто из исходов не в топе — аннулирование
+        let (resolution, _) = resolve_top_n(&strings(&["16", "4"]), 3, &field);
+        assert_eq!(resolution, Resolution::Void);
+        let (resolution, summary) =
+            resolve_top_n(&strings(&["44"]), 3, &competitors(&[("44", false, 0)]));
+        assert_eq!(resolution, Resolution::Void);
+        assert_eq!(summary, "No finishing positions reported");
+    }
 
-        assert!(!has_claimed, "Выигрыш уже получен");
-        assert!(payout > 0, "Нет ставок для получения");
+    #[test]
+    fn vote_without_an_outcome_athlete_is_skipped() {
+        let mut contract = setup();
+        let id = bound_market(
+            &mut contract,
+            "racing",
+            &["Hamilton", "Leclerc"],
+            &["44", "16"],
+            MarketKind::TopN { n: 3 },
+        );
+        let race = |field: serde_json::Value| {
+            serde_json::json!({
+                "home_team": "", "away_team": "", "home_score": 0, "away_score": 0,
+                "event_status": "final", "error": "", "competitors": field,
+            })
+        };
 
-        for &idx in &bet_indices {
-            bets[idx].claimed = true;
-        }
-        self.market_bets.set(market_id, Some(bets));
+        // Протокол обрезан до первых мест — Leclerc в нём нет
+        let top = race(serde_json::json!([
+            { "id": "1", "winner": true, "position": 1 },
+            { "id": "44", "winner": false, "position": 2 },
+        ]));
+        assert_eq!(
+            source_result(&mut contract, id, OracleSource::Outlayer, top),
+            "Cannot judge: athlete 16 not reported"
+        );
+        assert!(contract.get_score_votes(id).is_empty());
+
+        let full = race(serde_json::json!([
+            { "id": "1", "winner": true, "position": 1 },
+            { "id": "44", "winner": false, "position": 2 },
+            { "id": "16", "winner": false, "position": 0 },
+        ]));
+        source_result(&mut contract, id, OracleSource::Outlayer, full);
+        assert_eq!(
+            contract.proposals.get(&id).unwrap().settlement,
+            Some(vec![OutcomeSettlement::WIN, OutcomeSettlement::LOSE])
+        );
+    }
 
-        let mut user_bets = self.user_bets.get(&sender).cloned().unwrap_or_default();
-        for ub in user_bets.iter_mut() {
-            if ub.market_id == market_id {
-                if market.status == "voided" || ub.outcome == market.resolved_outcome as u32 {
-                    ub.claimed = true;
-                }
-            }
-        }
-        self.user_bets.set(sender.clone(), Some(user_bets));
+    #[test]
+    fn player_prop_compares_the_stat_with_the_line() {
+        let points = |value_x10: Option<i64>| {
+            vec![PlayerStat {
+                athlete_id: "1966".to_string(),
+                stat: "PTS".to_string(),
+                value_x10,
+            }]
+        };
 
-        let current = self.balances.get(&sender).copied().unwrap_or(0);
-        self.balances.set(sender.clone(), Some(current + payout));
+        let (resolution, summary) = resolve_player_prop("1966", "pts", 255, &points(Some(260)));
+        assert_eq!(resolution, Resolution::Winner(0));
+        assert_eq!(summary, "1966 pts=26.0 > 25.5");
+        let (resolution, _) = resolve_player_prop("1966", "pts", 255, &points(Some(250)));
+        assert_eq!(resolution, Resolution::Winner(1));
+        let (resolution, _) = resolve_player_prop("1966", "pts", 250, &points(Some(250)));
+        assert_eq!(resolution, Resolution::Push);
+
+        // Игрок не вышел на площадку
+        let (resolution, summary) = resolve_player_prop("1966", "pts", 255, &points(None));
+        assert_eq!(resolution, Resolution::Void);
+        assert_eq!(summary, "No pts for athlete 1966 (did not play?)");
+    }
 
-        let action = if market.status == "voided" {
-            "Возврат"
-        } else {
-            "Выигрыш"
+    #[test]
+    fn player_prop_vote_needs_the_requested_stat() {
+        let mut contract = setup();
+        let id = sports_market(
+            &mut contract,
+            &["Over 25.5", "Under 25.5"],
+            MarketKind::PlayerProp {
+                athlete_id: "1966".to_string(),
+                stat: "points".to_string(),
+                line_x10: 255,
+            },
+            None,
+        );
+        testing_env!(context(accounts(5)).block_timestamp(RESOLUTION).build());
+        contract.close_market(id);
+        let boxscore = |player_stats: serde_json::Value| {
+            serde_json::json!({
+                "home_team": "Lakers", "away_team": "Celtics",
+                "home_score": 112, "away_score": 108, "event_status": "final", "error": "",
+                "player_stats": player_stats,
+            })
         };
-        log!(
-            "{}: {} получил {} yoctoNEAR с рынка #{}",
-            action,
-            sender,
-            payout,
-            market_id
+
+        // Показатель другого игрока — голос не учитывается
+        let other = boxscore(serde_json::json!([
+            { "athlete_id": "3112335", "stat": "points", "value_x10": 310 },
+        ]));
+        assert_eq!(
+            source_result(&mut contract, id, OracleSource::Outlayer, other),
+            "Cannot judge: points for athlete 1966 not reported"
+        );
+        assert!(contract.get_score_votes(id).is_empty());
+
+        // Вывод worker — поля в snake_case
+        let scored = boxscore(serde_json::json!([
+            { "athlete_id": "1966", "stat": "points", "value_x10": 280 },
+        ]));
+        source_result(&mut contract, id, OracleSource::Outlayer, scored);
+        assert_eq!(
+            contract.proposals.get(&id).unwrap().settlement,
+            Some(vec![OutcomeSettlement::WIN, OutcomeSettlement::LOSE])
         );
     }
 
-    // ══════════════════════════════════════════════════════════════
-    // АДМИНИСТРАТИВНЫЕ МЕТОДЫ
-    // ══════════════════════════════════════════════════════════════
+    #[test]
+    fn outright_pays_the_winner_or_any_other_team() {
+        let outcomes = strings(&["Arsenal", "Liverpool", "Любая другая"]);
+        let team_ids = strings(&["359", "364", ""]);
+
+        let (resolution, summary) = resolve_outright(&outcomes, &team_ids, "364");
+        assert_eq!(resolution, Resolution::Winner(1));
+        assert_eq!(summary, "Liverpool (364) wins");
+        let (resolution, _) = resolve_outright(&outcomes, &team_ids, "382");
+        assert_eq!(resolution, Resolution::Winner(2));
+
+        // Без "любой другой" чемпион вне списка аннулирует рынок
+        let (resolution, _) = resolve_outright(&outcomes[..2], &team_ids[..2], "382");
+        assert_eq!(resolution, Resolution::Void);
+        let (resolution, _) = resolve_outright(&outcomes, &team_ids, "");
+        assert_eq!(resolution, Resolution::Void);
+    }
 
-    /// Настройка OutLayer — GitHub repo с WASM Worker
-    pub fn set_outlayer_config(
-        &mut self,
-        source_repo: String,
-        source_commit: Option<String>,
-        outlayer_contract: Option<AccountId>,
-    ) {
-        assert!(
-            env::predecessor_account_id() == self.owner,
-            "Только владелец"
+    #[test]
+    fn outright_vote_without_a_winner_is_skipped() {
+        let mut contract = setup();
+        let id = bound_market(
+            &mut contract,
+            "soccer",
+            &["Arsenal", "Liverpool", "Любая другая"],
+            &["359", "364", ""],
+            MarketKind::Outright {
+                source: OutrightSource::Standings,
+            },
         );
-        self.outlayer_source_repo = source_repo.clone();
-        if let Some(commit) = source_commit {
-            self.outlayer_source_commit = commit;
-        }
-        if let Some(contract) = outlayer_contract {
-            self.outlayer_contract = contract;
-        }
-        log!(
-            "OutLayer настроен: repo={}, commit={}, contract={}",
-            source_repo,
-            self.outlayer_source_commit,
-            self.outlayer_contract
+        let standings = |winner_id: &str| {
+            serde_json::json!({
+                "home_team": "", "away_team": "", "home_score": 0, "away_score": 0,
+                "event_status": "final", "error": "", "winner_id": winner_id,
+            })
+        };
+
+        assert_eq!(
+            source_result(&mut contract, id, OracleSource::Outlayer, standings("")),
+            "Cannot judge: winner not reported"
         );
-    }
+        assert!(contract.get_score_votes(id).is_empty());
 
-    /// Настройка TLS Oracle контракта
-    pub fn set_tls_oracle_config(&mut self, tls_oracle_contract: AccountId) {
-        assert!(
-            env::predecessor_account_id() == self.owner,
-            "Только владелец"
+        source_result(&mut contract, id, OracleSource::Outlayer, standings("382"));
+        assert_eq!(
+            contract.proposals.get(&id).unwrap().settlement,
+            Some(vec![
+                OutcomeSettlement::LOSE,
+                OutcomeSettlement::LOSE,
+                OutcomeSettlement::WIN
+            ])
         );
-        self.tls_oracle_contract = tls_oracle_contract.clone();
-        log!("TLS Oracle настроен: {}", tls_oracle_contract);
     }
 
-    // ══════════════════════════════════════════════════════════════
-    // VIEW МЕТОДЫ
-    // ══════════════════════════════════════════════════════════════
+    // ── Вывод средств ────────────────────────────────────────────
+
+    #[test]
+    fn failed_withdrawal_returns_the_amount_to_the_balance() {
+        let mut contract = setup();
+        fund(&mut contract, accounts(2), 5);
+        testing_env!(context(accounts(2)).build());
+        contract.withdraw(U128(3 * ONE_NEAR), None).detach();
+        assert_eq!(contract.get_balance(accounts(2), None).0, 2 * ONE_NEAR);
+        assert!(contract.get_pending_withdrawal(accounts(2)).is_some());
+
+        callback_context(0, PromiseResult::Failed);
+        assert!(!contract.on_withdraw_complete(accounts(2), U128(3 * ONE_NEAR), None));
+        assert_eq!(contract.get_balance(accounts(2), None).0, 5 * ONE_NEAR);
+        assert!(contract.get_pending_withdrawal(accounts(2)).is_none());
+    }
 
-    pub fn get_balance(&self, account_id: AccountId) -> U128 {
-        U128(self.balances.get(&account_id).copied().unwrap_or(0))
+    #[test]
+    fn completed_withdrawal_releases_the_pending_entry() {
+        let mut contract = setup();
+        fund(&mut contract, accounts(2), 5);
+        testing_env!(context(accounts(2)).build());
+        contract.withdraw(U128(5 * ONE_NEAR), None).detach();
+
+        callback_context(0, PromiseResult::Successful(vec![]));
+        assert!(contract.on_withdraw_complete(accounts(2), U128(5 * ONE_NEAR), None));
+        assert_eq!(contract.get_balance(accounts(2), None).0, 0);
+        assert!(contract.get_pending_withdrawal(accounts(2)).is_none());
     }
 
-    pub fn get_market(&self, market_id: u64) -> Option<Market> {
-        let mut market = self.markets.get(&market_id)?.clone();
-        let now = env::block_timestamp();
-        if market.status == "active" && now >= market.bets_end_date {
-            market.status = "closed".to_string();
-        }
-        Some(market)
+    #[test]
+    #[should_panic(expected = "Предыдущий вывод ещё не завершён")]
+    fn second_withdrawal_waits_for_the_first_callback() {
+        let mut contract = setup();
+        fund(&mut contract, accounts(2), 5);
+        testing_env!(context(accounts(2)).build());
+        contract.withdraw(U128(ONE_NEAR), None).detach();
+        contract.withdraw(U128(ONE_NEAR), None).detach();
     }
 
-    pub fn get_markets(
-        &self,
-        from_index: Option<u64>,
-        limit: Option<u64>,
-        category: Option<String>,
-        status: Option<String>,
-    ) -> Vec<Market> {
-        let skip = from_index.unwrap_or(0);
-        let max = limit.unwrap_or(50) as usize;
-        let now = env::block_timestamp();
-        let mut results: Vec<Market> = Vec::new();
-        let mut skipped: u64 = 0;
+    // ── NEP-141 залог ────────────────────────────────────────────
 
-        let mut id = self.market_count;
-        while id > 0 && results.len() < max {
-            id -= 1;
-            let mut market = match self.markets.get(&id) {
-                Some(m) => m.clone(),
-                None => continue,
-            };
+    /// USDC с 6 знаками, минимальная ставка 1 USDC
+    const USDC: u128 = 1_000_000;
 
-            if market.status == "active" && now >= market.bets_end_date {
-                market.status = "closed".to_string();
-            }
+    fn usdc() -> AccountId {
+        "usdc.testnet".parse().unwrap()
+    }
 
-            if let Some(ref cat) = category {
-                if cat != "все" && market.category != *cat {
-                    continue;
-                }
-            }
-            if let Some(ref st) = status {
-                if st != "все" && market.status != *st {
-                    continue;
-                }
-            }
+    fn ft_deposit(contract: &mut NearCast, account: AccountId, amount: u128) {
+        testing_env!(context(usdc()).build());
+        let unused = contract.ft_on_transfer(account, U128(amount), String::new());
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+    }
 
-            if skipped < skip {
-                skipped += 1;
-                continue;
-            }
+    fn usdc_market(contract: &mut NearCast) -> u64 {
+        testing_env!(context(accounts(0)).build());
+        contract.add_collateral_token(usdc(), U128(USDC));
+        contract.create_market(
+            "Курс выше $1.01 к концу года?".to_string(),
+            String::new(),
+            strings(&["Да", "Нет"]),
+            "crypto".to_string(),
+            (NOW + HOUR).to_string(),
+            RESOLUTION.to_string(),
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(usdc()),
+            None,
+            None,
+            None,
+            None,
+        )
+    }
 
-            results.push(market);
+    #[test]
+    fn token_deposit_funds_bets_in_that_token_only() {
+        let mut contract = setup();
+        let id = usdc_market(&mut contract);
+        fund(&mut contract, accounts(2), 10);
+        ft_deposit(&mut contract, accounts(2), 50 * USDC);
+        assert_eq!(contract.get_balance(accounts(2), Some(usdc())).0, 50 * USDC);
+
+        testing_env!(context(accounts(2)).build());
+        contract.place_bet(id, 0, U128(20 * USDC));
+        assert_eq!(contract.get_balance(accounts(2), Some(usdc())).0, 30 * USDC);
+        assert_eq!(contract.get_balance(accounts(2), None).0, 10 * ONE_NEAR);
+        assert_eq!(contract.markets.get(&id).unwrap().total_pool.0, 20 * USDC);
+    }
+
+    #[test]
+    #[should_panic(expected = "Минимальная ставка: 1000000 usdc.testnet")]
+    fn token_bet_below_the_token_minimum_is_rejected() {
+        let mut contract = setup();
+        let id = usdc_market(&mut contract);
+        register(&mut contract, accounts(2));
+        ft_deposit(&mut contract, accounts(2), 50 * USDC);
+        testing_env!(context(accounts(2)).build());
+        contract.place_bet(id, 0, U128(USDC / 2));
+    }
+
+    #[test]
+    #[should_panic(expected = "Токен dai.testnet не принимается в качестве залога")]
+    fn transfer_of_an_unlisted_token_is_rejected() {
+        let mut contract = setup();
+        register(&mut contract, accounts(2));
+        testing_env!(context("dai.testnet".parse().unwrap()).build());
+        contract
+            .ft_on_transfer(accounts(2), U128(50 * USDC), String::new())
+            .detach();
+    }
+
+    #[test]
+    fn failed_token_withdrawal_returns_tokens() {
+        let mut contract = setup();
+        usdc_market(&mut contract);
+        register(&mut contract, accounts(2));
+        ft_deposit(&mut contract, accounts(2), 50 * USDC);
+
+        testing_env!(context(accounts(2)).build());
+        contract.withdraw(U128(50 * USDC), Some(usdc())).detach();
+        assert_eq!(contract.get_balance(accounts(2), Some(usdc())).0, 0);
+
+        callback_context(0, PromiseResult::Failed);
+        contract.on_withdraw_complete(accounts(2), U128(50 * USDC), Some(usdc()));
+        assert_eq!(contract.get_balance(accounts(2), Some(usdc())).0, 50 * USDC);
+    }
+
+    // ── Оптимистичное разрешение ─────────────────────────────────
+
+    fn dispute(contract: &mut NearCast, disputer: AccountId, market_id: u64, bond: u128) {
+        testing_env!(context(disputer)
+            .block_timestamp(RESOLUTION + HOUR)
+            .attached_deposit(NearToken::from_yoctonear(bond))
+            .build());
+        contract.dispute(market_id);
+    }
+
+    fn arbitrate(contract: &mut NearCast, market_id: u64, outcome: Option<u32>) {
+        testing_env!(context(accounts(1))
+            .block_timestamp(RESOLUTION + HOUR)
+            .build());
+        contract.arbitrate(market_id, outcome, None, None);
+    }
+
+    #[test]
+    fn upheld_dispute_pays_both_bonds_to_the_disputer() {
+        let mut contract = setup();
+        let id = question_market(&mut contract);
+        for account in [accounts(2), accounts(3), accounts(4)] {
+            fund(&mut contract, account, 10);
         }
+        bet(&mut contract, accounts(4), id, 1, 2);
 
-        results
+        propose(
+            &mut contract,
+            accounts(2),
+            id,
+            Some(0),
+            DEFAULT_PROPOSAL_BOND,
+        );
+        dispute(&mut contract, accounts(3), id, 2 * DEFAULT_PROPOSAL_BOND);
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Disputed);
+
+        arbitrate(&mut contract, id, Some(1));
+        let market = contract.markets.get(&id).unwrap();
+        assert!(market.status == MarketStatus::Resolved);
+        assert_eq!(market.resolved_outcome, Some(1));
+        assert!(contract.proposals.get(&id).is_none());
+        assert_eq!(contract.get_balance(accounts(2), None).0, 10 * ONE_NEAR);
+        assert_eq!(
+            contract.get_balance(accounts(3), None).0,
+            10 * ONE_NEAR + 3 * DEFAULT_PROPOSAL_BOND
+        );
+        assert_eq!(claim(&mut contract, accounts(4), id), 2 * ONE_NEAR);
     }
 
-    pub fn get_odds(&self, market_id: u64) -> Option<serde_json::Value> {
-        let market = self.markets.get(&market_id)?;
-        let total_pool: u128 = market.total_pool.into();
+    #[test]
+    fn rejected_dispute_pays_both_bonds_to_the_proposer() {
+        let mut contract = setup();
+        let id = question_market(&mut contract);
+        for account in [accounts(2), accounts(3)] {
+            register(&mut contract, account);
+        }
 
-        let odds: Vec<f64> = market
-            .outcome_pools
-            .iter()
-            .map(|pool| {
-                let p: u128 = (*pool).into();
-                if p == 0 || total_pool == 0 {
-                    0.0
-                } else {
-                    (total_pool as f64 * 1000.0 / p as f64).round() / 1000.0
-                }
-            })
-            .collect();
+        propose(
+            &mut contract,
+            accounts(2),
+            id,
+            Some(0),
+            DEFAULT_PROPOSAL_BOND,
+        );
+        dispute(&mut contract, accounts(3), id, DEFAULT_PROPOSAL_BOND);
+        arbitrate(&mut contract, id, Some(0));
 
-        Some(serde_json::json!({
-            "outcomes": market.outcomes,
-            "odds": odds,
-            "pools": market.outcome_pools,
-            "totalPool": market.total_pool,
-        }))
+        assert_eq!(contract.markets.get(&id).unwrap().resolved_outcome, Some(0));
+        assert_eq!(
+            contract.get_balance(accounts(2), None).0,
+            2 * DEFAULT_PROPOSAL_BOND
+        );
+        assert_eq!(contract.get_balance(accounts(3), None).0, 0);
     }
 
-    pub fn get_market_bets(&self, market_id: u64) -> Vec<Bet> {
-        self.market_bets.get(&market_id).cloned().unwrap_or_default()
+    #[test]
+    #[should_panic(expected = "Залог за спор")]
+    fn dispute_bond_must_match_the_proposal_bond() {
+        let mut contract = setup();
+        let id = question_market(&mut contract);
+        for account in [accounts(2), accounts(3)] {
+            register(&mut contract, account);
+        }
+        propose(
+            &mut contract,
+            accounts(2),
+            id,
+            Some(0),
+            2 * DEFAULT_PROPOSAL_BOND,
+        );
+        dispute(&mut contract, accounts(3), id, DEFAULT_PROPOSAL_BOND);
     }
 
-    pub fn get_user_bets(&self, account_id: AccountId) -> Vec<Bet> {
-        self.user_bets.get(&account_id).cloned().unwrap_or_default()
+    #[test]
+    #[should_panic(expected = "Спортивные рынки разрешаются через ESPN oracle")]
+    fn sports_market_cannot_be_proposed() {
+        let mut contract = setup();
+        let id = sports_market(
+            &mut contract,
+            &["Lakers", "Celtics"],
+            MarketKind::Winner { draw: false },
+            None,
+        );
+        register(&mut contract, accounts(2));
+        propose(
+            &mut contract,
+            accounts(2),
+            id,
+            Some(0),
+            DEFAULT_PROPOSAL_BOND,
+        );
     }
 
-    pub fn get_stats(&self) -> serde_json::Value {
-        serde_json::json!({
-            "totalMarkets": self.market_count,
-            "totalVolume": U128(self.total_volume),
-            "owner": self.owner,
-            "oracle": self.oracle,
-            "outlayerSourceRepo": self.outlayer_source_repo,
-            "outlayerContract": self.outlayer_contract,
-            "tlsOracleContract": self.tls_oracle_contract,
-        })
+    // ── Период оспаривания результата oracle ─────────────────────
+
+    /// Хоккейный матч с предварительным итогом "Rangers 4:2"
+    fn provisional_result(contract: &mut NearCast) -> u64 {
+        let id = sport_market(
+            contract,
+            "hockey",
+            &["Rangers", "Bruins"],
+            MarketKind::Winner { draw: false },
+            None,
+        );
+        testing_env!(context(accounts(5)).block_timestamp(RESOLUTION).build());
+        contract.close_market(id);
+        let summary = source_result(
+            contract,
+            id,
+            OracleSource::Outlayer,
+            serde_json::json!({
+                "home_team": "Rangers", "away_team": "Bruins",
+                "home_score": 4, "away_score": 2, "event_status": "final", "error": "",
+                "overtime": false, "shootout": false,
+            }),
+        );
+        assert!(summary.starts_with("Provisionally resolved: Winner(0)"));
+        id
     }
 
-    pub fn get_outlayer_config(&self) -> serde_json::Value {
-        serde_json::json!({
-            "source_repo": self.outlayer_source_repo,
-            "source_commit": self.outlayer_source_commit,
-            "outlayer_contract": self.outlayer_contract,
-        })
+    #[test]
+    fn oracle_result_waits_for_the_challenge_window() {
+        let mut contract = setup();
+        let id = provisional_result(&mut contract);
+        let proposal = contract.proposals.get(&id).unwrap();
+        assert_eq!(proposal.bond.0, 0);
+        assert_eq!(proposal.dispute_deadline, FINALIZE);
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Proposed);
+
+        finalize(&mut contract, id);
+        assert_eq!(contract.markets.get(&id).unwrap().resolved_outcome, Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Окно для оспаривания ещё открыто")]
+    fn oracle_result_cannot_be_finalized_inside_the_window() {
+        let mut contract = setup();
+        let id = provisional_result(&mut contract);
+        testing_env!(context(accounts(5)).block_timestamp(FINALIZE - 1).build());
+        contract.finalize_proposal(id);
+    }
+
+    #[test]
+    fn disputed_oracle_result_goes_to_arbitration() {
+        let mut contract = setup();
+        let id = provisional_result(&mut contract);
+        register(&mut contract, accounts(3));
+
+        // У результата oracle залога нет — спор стоит proposal_bond
+        dispute(&mut contract, accounts(3), id, DEFAULT_PROPOSAL_BOND);
+        assert!(contract.markets.get(&id).unwrap().status == MarketStatus::Disputed);
+
+        arbitrate(&mut contract, id, Some(1));
+        assert_eq!(contract.markets.get(&id).unwrap().resolved_outcome, Some(1));
+        assert_eq!(
+            contract.get_balance(accounts(3), None).0,
+            DEFAULT_PROPOSAL_BOND
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Окно для оспаривания закрыто")]
+    fn oracle_result_cannot_be_disputed_after_the_window() {
+        let mut contract = setup();
+        let id = provisional_result(&mut contract);
+        register(&mut contract, accounts(3));
+        testing_env!(context(accounts(3))
+            .block_timestamp(FINALIZE)
+            .attached_deposit(NearToken::from_yoctonear(DEFAULT_PROPOSAL_BOND))
+            .build());
+        contract.dispute(id);
+    }
+
+    #[test]
+    fn challenge_window_follows_the_resolution_config() {
+        let mut contract = setup();
+        contract.set_resolution_config(
+            U128(DEFAULT_PROPOSAL_BOND),
+            DEFAULT_DISPUTE_WINDOW,
+            HOUR,
+            0,
+        );
+        let id = provisional_result(&mut contract);
+        assert_eq!(
+            contract.proposals.get(&id).unwrap().dispute_deadline,
+            RESOLUTION + HOUR
+        );
     }
 }
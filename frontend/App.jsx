@@ -22,7 +22,7 @@ import "@near-wallet-selector/modal-ui/styles.css";
 const TRANSLATIONS = {
   en: {
     nav: { markets: "Markets", create: "+ Create", resolved: "Reporting", portfolio: "Portfolio", connect: "Connect" },
-    status: { active: "Active", closed: "In-play", resolution: "Resolution", awaitingTee: "Awaiting TEE", resolved: "Resolved", voided: "Voided", resolvedWon: (w) => `Resolved: ${w} won` },
+    status: { active: "Active", closed: "In-play", resolution: "Resolution", awaitingTee: "Awaiting TEE", proposed: "Proposed", disputed: "Disputed", resolved: "Resolved", voided: "Voided", finalized: "Finalized", resolvedWon: (w) => `Resolved: ${w} won` },
     stats: { markets: "Markets", volume: "Volume (NEAR)" },
     filters: { all: "All", active: "Active", inPlay: "In-play", needsResolution: "Needs Resolution", awaitingResolution: "Awaiting Resolution", resolved: "Resolved", voided: "Voided" },
     sort: { label: "Sort", endDate: "End Date", volume: "Volume", newest: "Newest" },
@@ -132,11 +132,15 @@ function getStyles(th) {
   };
 }
 
-const STATUS_COLORS = { active: "#22c55e", closed: "#f59e0b", resolved: "#3b82f6", voided: "#ef4444" };
+const STATUS_COLORS = {
+  active: "#22c55e", closed: "#f59e0b", resolution_pending: "#f59e0b", proposed: "#a855f7", disputed: "#f97316",
+  resolved: "#3b82f6", voided: "#ef4444", finalized: "#64748b",
+};
 const getStatusColor = (m) => {
   if (m.pendingResolution && isReadyToResolve(m)) return "#f59e0b";
-  if (m.status === "closed" && isReadyToResolve(m)) return "#a855f7";
-  return STATUS_COLORS[m.status] || "#94a3b8";
+  const s = displayStatus(m);
+  if (s === "closed" && isReadyToResolve(m)) return "#a855f7";
+  return STATUS_COLORS[s] || "#94a3b8";
 };
 
 // ══════════════════════════════════════════════════════════════
@@ -157,21 +161,48 @@ function formatDate(nanoTimestamp) {
   return new Date(ms).toLocaleString(undefined, { day: "numeric", month: "short", year: "numeric", hour: "2-digit", minute: "2-digit" });
 }
 
+// Контракт переводит рынок в closed только через close_market или при
+// следующем действии — после betsEndDate показываем его закрытым сразу
+const displayStatus = (m) => {
+  if (m.status !== "active") return m.status;
+  const endMs = Number(BigInt(m.betsEndDate || 0) / BigInt(1_000_000));
+  return endMs > 0 && Date.now() >= endMs ? "closed" : "active";
+};
+
 function formatMatchDate(iso) {
   try { return new Date(iso).toLocaleString(undefined, { day: "numeric", month: "short", hour: "2-digit", minute: "2-digit" }); }
   catch { return iso; }
 }
 
+// Ставка на исход участвует в выплате: выигрыш или возврат (push)
+function isPayableOutcome(market, outcome) {
+  if (market.status === "voided") return true;
+  if (market.status !== "resolved") return false;
+  const s = market.settlement?.[outcome];
+  return s ? s.winBps > 0 || s.pushBps > 0 : market.resolvedOutcome === outcome;
+}
+
+// Ставка на исход возвращается целиком (push или аннулирование)
+function isPushOutcome(market, outcome) {
+  if (market.status === "voided") return true;
+  const s = market.settlement?.[outcome];
+  return market.status === "resolved" && !!s && s.winBps === 0 && s.pushBps > 0;
+}
+
 function getStatusLabel(market, t) {
   if (!market) return "—";
-  const s = market.status;
+  const s = displayStatus(market);
   if (s === "active") return t.status.active;
   if (s === "closed" && market.pendingResolution && isReadyToResolve(market)) return t.status.awaitingTee;
   if (s === "closed") return isReadyToResolve(market) ? t.status.resolution : t.status.closed;
-  if (s === "resolved") {
+  if (s === "resolution_pending") return t.status.awaitingTee;
+  if (s === "proposed") return t.status.proposed;
+  if (s === "disputed") return t.status.disputed;
+  if (s === "resolved" || s === "finalized") {
     const idx = market.resolvedOutcome;
     const winner = idx != null && market.outcomes?.[idx];
-    return winner ? t.status.resolvedWon(winner) : t.status.resolved;
+    if (winner) return t.status.resolvedWon(winner);
+    return s === "resolved" ? t.status.resolved : t.status.finalized;
   }
   if (s === "voided") return t.status.voided;
   return s;
@@ -179,9 +210,16 @@ function getStatusLabel(market, t) {
 
 const isErrorMsg = (msg) => msg && (msg.includes("Ошибка") || msg.includes("Error"));
 
-/** Рынок готов к резолвингу: closed/active + resolutionDate прошла + есть ESPN Oracle */
+/** Рассчитанный рынок: выплаты открыты или уже получены */
+const isSettled = (m) => m.status === "resolved" || m.status === "voided" || m.status === "finalized";
+
+/**
+ * Рынок ждёт разрешения: closed/active + resolutionDate прошла + есть ESPN Oracle,
+ * либо разрешение уже идёт (запрос в полёте, окно оспаривания, спор)
+ */
 const isReadyToResolve = (m) => {
   if (!m || !m.resolutionDate || m.resolutionDate === "0") return false;
+  if (m.status === "resolution_pending" || m.status === "proposed" || m.status === "disputed") return true;
   if (m.status !== "closed" && m.status !== "active") return false;
   const resMs = Number(BigInt(m.resolutionDate) / BigInt(1_000_000));
   return Date.now() >= resMs;
@@ -290,11 +328,12 @@ export default function App() {
     try {
       const params = new URLSearchParams();
       params.set("limit", "100000");
-      if (statusFilter !== "all") params.set("status", statusFilter);
       if (categoryFilter !== "all") params.set("category", categoryFilter);
       const res = await fetch(`/api/markets?${params}`);
       const data = await res.json();
-      setMarkets(Array.isArray(data) ? data : []);
+      // Статус фильтруется здесь: рынок после betsEndDate on-chain ещё active
+      const all = Array.isArray(data) ? data : [];
+      setMarkets(statusFilter === "all" ? all : all.filter((m) => displayStatus(m) === statusFilter));
     } catch (err) {
       console.error("Markets load error:", err);
     }
@@ -511,8 +550,8 @@ function MarketBrowser({ markets, stats, statusFilter, setStatusFilter, sortBy,
     return b.id - a.id;
   });
 
-  // Убираем resolved/voided/needs-resolution из основного списка (они в Reporting)
-  const statusFiltered = sorted.filter((m) => m.status !== "resolved" && m.status !== "voided" && !isReadyToResolve(m));
+  // Убираем рассчитанные и ждущие разрешения из основного списка (они в Reporting)
+  const statusFiltered = sorted.filter((m) => !isSettled(m) && !isReadyToResolve(m));
 
   // Фильтрация по поисковому запросу (имя или ID)
   const searchFiltered = searchQuery.trim() === "" ? statusFiltered : statusFiltered.filter((m) => {
@@ -629,7 +668,7 @@ function MarketDetail({ market, account, balance, userBets, prevPage, onBack, on
 
   // При загрузке — проверяем сервер на pending resolution
   useEffect(() => {
-    if (market.status === "resolved" || market.status === "voided") {
+    if (isSettled(market)) {
       setResolutionPending(null);
       return;
     }
@@ -641,7 +680,7 @@ function MarketDetail({ market, account, balance, userBets, prevPage, onBack, on
 
   // Очищаем pending на сервере если рынок зарезолвлен
   useEffect(() => {
-    if (resolutionPending && (market.status === "resolved" || market.status === "voided")) {
+    if (resolutionPending && isSettled(market)) {
       fetch(`/api/markets/${market.id}/pending-resolution`, { method: "DELETE" }).catch(() => {});
       setResolutionPending(null);
     }
@@ -667,7 +706,7 @@ function MarketDetail({ market, account, balance, userBets, prevPage, onBack, on
 
   // Проверяем, есть ли у пользователя невостребованные выигрышные ставки или ставки для возврата
   const myBets = (userBets || []).filter((b) => b.marketId === market.id);
-  const hasUnclaimedWin = market.status === "resolved" && myBets.some((b) => b.outcome === market.resolvedOutcome && !b.claimed);
+  const hasUnclaimedWin = market.status === "resolved" && myBets.some((b) => isPayableOutcome(market, b.outcome) && !b.claimed);
   const hasUnclaimedRefund = market.status === "voided" && myBets.some((b) => !b.claimed);
   const canClaim = hasUnclaimedWin || hasUnclaimedRefund;
 
@@ -717,7 +756,7 @@ function MarketDetail({ market, account, balance, userBets, prevPage, onBack, on
           const odds = totalPool > 0n && pool > 0n ? (Number(totalPool) / Number(pool)).toFixed(2) : "—";
           const isWinner = market.resolvedOutcome === i;
           return (
-            <div key={i} style={S.outcomeBar(pct, isWinner)} onClick={() => market.status === "active" && setSelectedOutcome(i)}>
+            <div key={i} style={S.outcomeBar(pct, isWinner)} onClick={() => displayStatus(market) === "active" && setSelectedOutcome(i)}>
               <div>
                 <span style={{ fontWeight: selectedOutcome === i ? 700 : 400 }}>
                   {selectedOutcome === i && "● "}{outcome}{isWinner && " ✓"}
@@ -730,7 +769,7 @@ function MarketDetail({ market, account, balance, userBets, prevPage, onBack, on
           );
         })}
 
-        {market.status === "active" && (
+        {displayStatus(market) === "active" && (
           <div style={{ marginTop: 20 }}>
             {account && (
               <div style={{ fontSize: 13, color: th.muted, marginBottom: 8 }}>
@@ -1144,7 +1183,7 @@ function CreateMarket({ account, onCreated }) {
     setLoading(true); setMessage("");
     try {
       const res = await fetch("/api/generate-market", { method: "POST", headers: { "Content-Type": "application/json" },
-        body: JSON.stringify({ sport, country, league, teamA: selectedMatch.teamA, teamB: selectedMatch.teamB, matchDate: selectedMatch.date, marketType, espnEventId: selectedMatch.id, lang }) });
+        body: JSON.stringify({ sport, country, league, teamA: selectedMatch.teamA, teamB: selectedMatch.teamB, teamAId: selectedMatch.teamAId, teamBId: selectedMatch.teamBId, matchDate: selectedMatch.date, marketType, espnEventId: selectedMatch.id, lang }) });
       const data = await res.json();
       if (data.error) { setMessage(data.error); setLoading(false); return; }
       setAiResult(data); setStep("confirm");
@@ -1163,6 +1202,7 @@ function CreateMarket({ account, onCreated }) {
         question: aiResult.question, description: aiResult.description || "", outcomes: aiResult.outcomes, category: sport,
         betsEndDate: msToNano(betsEnd), resolutionDate: msToNano(resolution),
         espnEventId: aiResult.espnEventId || "", sport: aiResult.sport || "", league: aiResult.league || "", marketType: aiResult.marketType || marketType,
+        outcomeTeamIds: aiResult.outcomeTeamIds, kind: aiResult.kind,
       });
       setMessage(t.create.marketCreated);
       // Seed liquidity — бэкенд ставит 1 NEAR на каждый исход
@@ -1359,7 +1399,7 @@ function ResolvedMarkets({ onOpen }) {
         const res = await fetch("/api/markets?limit=100000");
         const data = await res.json();
         const all = Array.isArray(data) ? data : [];
-        setMarkets(all.filter((m) => m.status === "resolved" || m.status === "closed" || m.status === "voided"));
+        setMarkets(all.filter((m) => isSettled(m) || displayStatus(m) === "closed" || isReadyToResolve(m)));
       } catch (err) { console.error("Load error:", err); }
       setLoading(false);
     }
@@ -1473,9 +1513,24 @@ function Portfolio({ account, userBets, markets: externalMarkets, balance, onRef
   // Расчёт PnL для каждой ставки
   const getBetPnl = (bet, market) => {
     if (!market) return { status: "pending", pnl: 0 };
+    if (isPushOutcome(market, bet.outcome)) return { status: "voided", pnl: 0 };
     if (market.status === "resolved") {
       const won = market.resolvedOutcome === bet.outcome;
       const amountNear = Number(BigInt(bet.amount)) / ONE_NEAR;
+      const split = market.resolvedOutcome == null && market.settlement?.[bet.outcome];
+      if (split) {
+        // Частичный итог (четвертная линия): доля выигрыша делит проигранные доли
+        let winPool = 0, lostPool = 0;
+        market.settlement.forEach((s, i) => {
+          const pool = Number(BigInt(market.outcomePools?.[i] || "0")) / ONE_NEAR;
+          winPool += pool * s.winBps / 10000;
+          lostPool += pool * (10000 - s.winBps - s.pushBps) / 10000;
+        });
+        const winPart = amountNear * split.winBps / 10000;
+        const lostPart = amountNear * (10000 - split.winBps - split.pushBps) / 10000;
+        const pnl = (winPool > 0 ? winPart * lostPool / winPool : 0) - lostPart;
+        return { status: split.winBps > 0 ? "won" : "lost", pnl };
+      }
       if (won) {
         const totalPool = Number(BigInt(market.totalPool || "0")) / ONE_NEAR;
         const winPool = Number(BigInt(market.outcomePools?.[bet.outcome] || "0")) / ONE_NEAR;
@@ -1500,7 +1555,7 @@ function Portfolio({ account, userBets, markets: externalMarkets, balance, onRef
     const market = markets.find((m) => m.id === mid);
     const bets = betsByMarket[mid];
     if (!market) return false;
-    if (market.status === "resolved") return bets.some((b) => b.outcome === market.resolvedOutcome && !b.claimed);
+    if (market.status === "resolved") return bets.some((b) => isPayableOutcome(market, b.outcome) && !b.claimed);
     if (market.status === "voided") return bets.some((b) => !b.claimed);
     return false;
   });
@@ -1511,7 +1566,7 @@ function Portfolio({ account, userBets, markets: externalMarkets, balance, onRef
     const bets = betsByMarket[mid];
     if (!market) return false;
     if (market.status === "resolved") {
-      const hasUnclaimedWin = bets.some((b) => b.outcome === market.resolvedOutcome && !b.claimed);
+      const hasUnclaimedWin = bets.some((b) => isPayableOutcome(market, b.outcome) && !b.claimed);
       return !hasUnclaimedWin; // проигранные ИЛИ уже заклеймленные
     }
     if (market.status === "voided") {
@@ -123,7 +123,8 @@ export async function withdraw(amountNear) {
 
 // ── Вызовы контракта (автоподпись, без попапа) ────────────────
 
-// Создать рынок (Rust-контракт — snake_case поля)
+// Создать рынок (Rust-контракт — snake_case поля).
+// Залог creation_bond контракт списывает с внутреннего баланса NEAR
 export async function createMarket({
   question,
   description,
@@ -135,6 +136,8 @@ export async function createMarket({
   sport,
   league,
   marketType,
+  kind,
+  outcomeTeamIds,
 }) {
   const wallet = await selector.wallet();
   const args = {
@@ -150,6 +153,9 @@ export async function createMarket({
   if (sport) args.sport = sport;
   if (league) args.league = league;
   if (marketType) args.market_type = marketType;
+  // Явный тип с параметрами (рынки на периоды); иначе контракт выводит его из market_type
+  if (kind) args.kind = kind;
+  if (outcomeTeamIds) args.outcome_team_ids = outcomeTeamIds;
 
   return wallet.signAndSendTransaction({
     receiverId: contractId,
@@ -158,7 +164,7 @@ export async function createMarket({
         "create_market",
         args,
         30_000_000_000_000n, // 30 TGas
-        0n // без deposit — автоподпись
+        0n // без deposit — автоподпись, залог списывается с внутреннего баланса
       ),
     ],
   });
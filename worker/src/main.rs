@@ -2,17 +2,29 @@
 ///
 /// Запускается внутри Intel TDX (Trusted Execution Environment).
 /// Получает ESPN event ID, делает HTTP-запрос к ESPN API,
-/// возвращает СЫРЫЕ данные (имена команд, счёт, статус).
+/// возвращает СЫРЫЕ данные (имена и ESPN id команд, счёт по периодам, статус).
 ///
 /// Логика определения победителя — в смарт-контракте (on-chain).
 ///
-/// Вход (stdin): JSON с ESPN event ID, sport, league
+/// Вход (stdin): JSON с ESPN event ID, sport, league и режимом:
+/// "event" (матч), "championship" (финал — id победителя),
+/// "standings" (таблица сезона — id чемпиона; espn_event_id = сезон)
 /// Выход (stdout): JSON с сырыми данными ESPN (<=900 байт)
 
 use serde::{Deserialize, Serialize};
 use std::io::{self, Read, Write};
 use wasi_http_client::Client;
 
+/// Лимит размера stdout для OutLayer
+const MAX_OUTPUT_BYTES: usize = 900;
+/// Не больше периодов в выводе (иннинги бейсбола с экстра-иннингами)
+const MAX_PERIODS: usize = 20;
+/// Не больше участников в выводе (пелотон гонки); лишние — с конца протокола.
+/// Рынок передаёт athlete_ids исходов (до 10) — тогда выводятся только они
+const MAX_COMPETITORS: usize = 20;
+/// Не больше запрошенных показателей игроков
+const MAX_PLAYER_STATS: usize = 10;
+
 // ── Входные данные (из stdin) ────────────────────────────────────
 
 #[derive(Deserialize)]
@@ -20,6 +32,21 @@ struct Input {
     espn_event_id: String,
     sport: String,
     league: String,
+    // Статистика игроков для player-prop рынков
+    #[serde(default)]
+    player_stats: Vec<StatRequest>,
+    // Спортсмены исходов рынка: выводятся только они; пусто — все участники
+    #[serde(default)]
+    athlete_ids: Vec<String>,
+    // "event" | "championship" | "standings"; нет — "event"
+    #[serde(default)]
+    mode: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StatRequest {
+    athlete_id: String,
+    stat: String, // ключ ESPN boxscore ("points") или метка ("PTS")
 }
 
 // ── Выходные данные (stdout, <=900 байт) ─────────────────────────
@@ -29,10 +56,49 @@ struct Input {
 struct Output {
     home_team: String,
     away_team: String,
+    home_team_id: String, // ESPN team.id — контракт сопоставляет исходы по нему
+    away_team_id: String,
+    home_abbr: String, // ESPN team.abbreviation
+    away_abbr: String,
     home_score: i32,
     away_score: i32,
-    event_status: String, // "final" | "pre" | "in" | "error"
-    error: String,        // пустая строка если всё ОК
+    // "final" | "pre" | "in" | "postponed" | "canceled" | "abandoned" | "suspended" | "error"
+    event_status: String,
+    error: String,   // пустая строка если всё ОК
+    start_time: u64, // начало матча (unix, секунды), 0 — неизвестно
+    // Очки по периодам (тайм / четверть / период / иннинг), включая овертаймы;
+    // пусто — ESPN не вернул linescores или вывод не влез в лимит
+    home_periods: Vec<i32>,
+    away_periods: Vec<i32>,
+    overtime: bool, // был овертайм / дополнительное время
+    shootout: bool, // была серия буллитов / пенальти
+    // Счёт основного времени; null — неизвестен (нет linescores)
+    regulation_home_score: Option<i32>,
+    regulation_away_score: Option<i32>,
+    // Счёт серии пенальти (футбол); null — серии не было или ESPN не сообщил
+    home_shootout_score: Option<i32>,
+    away_shootout_score: Option<i32>,
+    // Индивидуальные виды (теннис, MMA, гольф, гонки) — без home/away:
+    // участники по занятому месту, победитель первым
+    competitors: Vec<CompetitorResult>,
+    // Запрошенные показатели игроков из boxscore
+    player_stats: Vec<PlayerStat>,
+    // Outright-режимы: ESPN id победителя финала / чемпиона таблицы
+    winner_id: String,
+}
+
+#[derive(Serialize)]
+struct PlayerStat {
+    athlete_id: String,
+    stat: String,
+    value_x10: Option<i64>, // значение ×10 (255 = 25.5); null — игрок не играл / нет показателя
+}
+
+#[derive(Serialize)]
+struct CompetitorResult {
+    id: String, // ESPN athlete.id (или competitor.id)
+    winner: bool,
+    position: u32, // занятое место, 0 — неизвестно
 }
 
 impl Output {
@@ -40,21 +106,69 @@ impl Output {
         Output {
             home_team: String::new(),
             away_team: String::new(),
+            home_team_id: String::new(),
+            away_team_id: String::new(),
+            home_abbr: String::new(),
+            away_abbr: String::new(),
             home_score: -1,
             away_score: -1,
             event_status: "error".to_string(),
             error: msg.to_string(),
+            start_time: 0,
+            home_periods: Vec::new(),
+            away_periods: Vec::new(),
+            overtime: false,
+            shootout: false,
+            regulation_home_score: None,
+            regulation_away_score: None,
+            home_shootout_score: None,
+            away_shootout_score: None,
+            competitors: Vec::new(),
+            player_stats: Vec::new(),
+            winner_id: String::new(),
         }
     }
 
-    fn not_finished(state: &str) -> Self {
+    /// Итог outright-режима: только id победителя
+    fn outright(winner_id: String, start_time: u64) -> Self {
+        Output {
+            winner_id,
+            ..Output::not_finished("final", start_time)
+        }
+    }
+
+    /// Завершённое событие без home/away: только список участников
+    fn individual(competitors: Vec<CompetitorResult>, start_time: u64) -> Self {
+        Output {
+            competitors,
+            ..Output::not_finished("final", start_time)
+        }
+    }
+
+    fn not_finished(state: &str, start_time: u64) -> Self {
         Output {
             home_team: String::new(),
             away_team: String::new(),
+            home_team_id: String::new(),
+            away_team_id: String::new(),
+            home_abbr: String::new(),
+            away_abbr: String::new(),
             home_score: -1,
             away_score: -1,
             event_status: state.to_string(),
             error: String::new(),
+            start_time,
+            home_periods: Vec::new(),
+            away_periods: Vec::new(),
+            overtime: false,
+            shootout: false,
+            regulation_home_score: None,
+            regulation_away_score: None,
+            home_shootout_score: None,
+            away_shootout_score: None,
+            competitors: Vec::new(),
+            player_stats: Vec::new(),
+            winner_id: String::new(),
         }
     }
 }
@@ -64,6 +178,31 @@ impl Output {
 #[derive(Deserialize)]
 struct ESPNResponse {
     header: Option<Header>,
+    boxscore: Option<Boxscore>,
+}
+
+#[derive(Deserialize)]
+struct Boxscore {
+    players: Option<Vec<TeamPlayers>>,
+}
+
+#[derive(Deserialize)]
+struct TeamPlayers {
+    statistics: Option<Vec<StatGroup>>,
+}
+
+/// Группа статистики: keys / labels — колонки, у игроков stats по тем же индексам
+#[derive(Deserialize)]
+struct StatGroup {
+    keys: Option<Vec<String>>,
+    labels: Option<Vec<String>>,
+    athletes: Option<Vec<AthleteStats>>,
+}
+
+#[derive(Deserialize)]
+struct AthleteStats {
+    athlete: Option<Athlete>,
+    stats: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
@@ -73,6 +212,7 @@ struct Header {
 
 #[derive(Deserialize)]
 struct Competition {
+    date: Option<String>, // "2024-03-15T19:00Z"
     status: Option<Status>,
     competitors: Option<Vec<Competitor>>,
 }
@@ -87,22 +227,73 @@ struct Status {
 struct StatusType {
     completed: Option<bool>,
     state: Option<String>, // "pre" | "in" | "post"
+    name: Option<String>,  // "STATUS_FINAL" | "STATUS_POSTPONED" | ...
+    detail: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct Competitor {
+    id: Option<String>,
     #[serde(rename = "homeAway")]
     home_away: Option<String>,
+    winner: Option<bool>,
+    order: Option<u32>, // место в протоколе гонки / турнира
     score: Option<String>,
     team: Option<Team>,
+    linescores: Option<Vec<Linescore>>,
+    #[serde(rename = "shootoutScore")]
+    shootout_score: Option<serde_json::Value>, // число или строка
+    athlete: Option<Athlete>,
+}
+
+#[derive(Deserialize)]
+struct Athlete {
+    id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Linescore {
+    #[serde(rename = "displayValue")]
+    display_value: Option<String>,
+    value: Option<f64>,
 }
 
 #[derive(Deserialize)]
 struct Team {
+    id: Option<String>,
+    abbreviation: Option<String>,
     #[serde(rename = "displayName")]
     display_name: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct StandingsResponse {
+    children: Option<Vec<StandingsGroup>>, // конференции / группы
+    standings: Option<StandingsTable>,     // единая таблица
+}
+
+#[derive(Deserialize)]
+struct StandingsGroup {
+    standings: Option<StandingsTable>,
+}
+
+#[derive(Deserialize)]
+struct StandingsTable {
+    entries: Option<Vec<StandingsEntry>>,
+}
+
+#[derive(Deserialize)]
+struct StandingsEntry {
+    team: Option<Team>,
+    stats: Option<Vec<StandingsStat>>,
+}
+
+#[derive(Deserialize)]
+struct StandingsStat {
+    name: Option<String>, // "rank" | "points" | "wins" | ...
+    value: Option<f64>,
+}
+
 // ── Основная логика ──────────────────────────────────────────────
 
 fn main() {
@@ -113,16 +304,53 @@ fn main() {
     };
 
     // Выводим результат в stdout (OutLayer читает stdout)
-    print!("{}", serde_json::to_string(&output).unwrap_or_default());
+    print!("{}", encode_output(output));
     let _ = io::stdout().flush();
 }
 
+/// JSON вывода в пределах MAX_OUTPUT_BYTES.
+/// Не влезли в лимит — жертвуем периодами (итоговый счёт важнее), затем
+/// хвостом участников и показателями игроков с конца: первым запрошен
+/// показатель рынка. Обрезанное контракт не считает проигрышем — голос
+/// без нужных данных просто не учитывается
+fn encode_output(mut output: Output) -> String {
+    let encode = |o: &Output| serde_json::to_string(o).unwrap_or_default();
+    let mut json = encode(&output);
+    if json.len() > MAX_OUTPUT_BYTES {
+        output.home_periods.clear();
+        output.away_periods.clear();
+        json = encode(&output);
+    }
+    while json.len() > MAX_OUTPUT_BYTES && output.competitors.pop().is_some() {
+        json = encode(&output);
+    }
+    while json.len() > MAX_OUTPUT_BYTES && output.player_stats.pop().is_some() {
+        json = encode(&output);
+    }
+    // Остались длинные строки ESPN (названия команд, id) — голоса не будет
+    if json.len() > MAX_OUTPUT_BYTES {
+        json = encode(&Output::error("Output exceeds size limit"));
+    }
+    assert!(
+        json.len() <= MAX_OUTPUT_BYTES,
+        "Вывод больше лимита OutLayer"
+    );
+    json
+}
+
 fn run() -> Result<Output, Box<dyn std::error::Error>> {
     // Читаем JSON из stdin
     let mut input_raw = String::new();
     io::stdin().read_to_string(&mut input_raw)?;
     let input: Input = serde_json::from_str(&input_raw)?;
 
+    let championship = match input.mode.as_deref().unwrap_or("event") {
+        "event" => false,
+        "championship" => true,
+        "standings" => return run_standings(&input),
+        other => return Ok(Output::error(&format!("Unknown mode: {}", other))),
+    };
+
     // Формируем URL ESPN summary API
     let url = format!(
         "https://site.api.espn.com/apis/site/v2/sports/{}/{}/summary?event={}",
@@ -143,6 +371,7 @@ fn run() -> Result<Output, Box<dyn std::error::Error>> {
         .map_err(|e| format!("ESPN JSON parse: {}", e))?;
 
     // Извлекаем данные о матче
+    let boxscore = espn.boxscore;
     let comp = espn
         .header
         .and_then(|h| h.competitions)
@@ -164,8 +393,40 @@ fn run() -> Result<Output, Box<dyn std::error::Error>> {
         .and_then(|t| t.completed)
         .unwrap_or(false);
 
+    let start_time = comp.date.as_deref().and_then(parse_espn_date).unwrap_or(0);
+
+    // Перенос / отмена матча — отдельные статусы, счёта нет
+    if let Some(special) = comp
+        .status
+        .as_ref()
+        .and_then(|s| s.status_type.as_ref())
+        .and_then(special_status)
+    {
+        return Ok(Output::not_finished(special, start_time));
+    }
+
     if !completed || state != "post" {
-        return Ok(Output::not_finished(state));
+        return Ok(Output::not_finished(state, start_time));
+    }
+
+    // Финал турнира / серии: победитель по флагу ESPN (учитывает пенальти)
+    if championship {
+        let winner = comp
+            .competitors
+            .iter()
+            .flatten()
+            .find(|c| c.winner == Some(true))
+            .and_then(|c| {
+                c.team
+                    .as_ref()
+                    .and_then(|t| t.id.clone())
+                    .or_else(|| c.athlete.as_ref().and_then(|a| a.id.clone()))
+                    .or_else(|| c.id.clone())
+            });
+        return Ok(match winner {
+            Some(id) => Output::outright(id, start_time),
+            None => Output::error("No winner flagged in championship event"),
+        });
     }
 
     // Парсим счёт и имена команд
@@ -177,6 +438,15 @@ fn run() -> Result<Output, Box<dyn std::error::Error>> {
         .iter()
         .find(|c| c.home_away.as_deref() == Some("away"));
 
+    // Индивидуальный вид спорта: ESPN отдаёт winner и место вместо home/away
+    if home_comp.is_none() || away_comp.is_none() {
+        let results = competitor_results(&competitors, &input.sport, &input.athlete_ids);
+        if results.is_empty() {
+            return Ok(Output::error("Could not parse competitors from ESPN"));
+        }
+        return Ok(Output::individual(results, start_time));
+    }
+
     let home_score: i32 = home_comp
         .and_then(|c| c.score.as_ref())
         .and_then(|s| s.parse().ok())
@@ -186,27 +456,631 @@ fn run() -> Result<Output, Box<dyn std::error::Error>> {
         .and_then(|s| s.parse().ok())
         .unwrap_or(-1);
 
-    let home_team = home_comp
-        .and_then(|c| c.team.as_ref())
-        .and_then(|t| t.display_name.as_deref())
-        .unwrap_or("")
-        .to_string();
-    let away_team = away_comp
-        .and_then(|c| c.team.as_ref())
-        .and_then(|t| t.display_name.as_deref())
-        .unwrap_or("")
-        .to_string();
+    let home = home_comp.and_then(|c| c.team.as_ref());
+    let away = away_comp.and_then(|c| c.team.as_ref());
+    let field = |team: Option<&Team>, get: fn(&Team) -> Option<&String>| {
+        team.and_then(get).cloned().unwrap_or_default()
+    };
 
     if home_score < 0 || away_score < 0 {
         return Ok(Output::error("Could not parse scores from ESPN"));
     }
 
+    let home_periods = home_comp.map(periods).unwrap_or_default();
+    let away_periods = away_comp.map(periods).unwrap_or_default();
+
+    // Овертайм / серия: по статусу ESPN или по лишним периодам в linescores
+    let regulation = regulation_periods(&input.sport, &input.league);
+    let (status_ot, shootout) = comp
+        .status
+        .as_ref()
+        .and_then(|s| s.status_type.as_ref())
+        .map(extra_time_status)
+        .unwrap_or((false, false));
+    let overtime = status_ot || shootout || regulation.is_some_and(|n| home_periods.len() > n);
+
+    // Счёт основного времени: сумма регламентных периодов, без овертайма — итоговый
+    let regulation_score = |scores: &[i32], total: i32| match regulation {
+        Some(n) if scores.len() >= n => Some(scores[..n].iter().sum()),
+        _ if !overtime => Some(total),
+        _ => None,
+    };
+    let shootout_score = |c: Option<&Competitor>| {
+        let value = c.and_then(|c| c.shootout_score.as_ref())?;
+        value
+            .as_i64()
+            .map(|v| v as i32)
+            .or_else(|| value.as_str().and_then(|v| v.parse().ok()))
+    };
+
     Ok(Output {
-        home_team,
-        away_team,
+        home_team: field(home, |t| t.display_name.as_ref()),
+        away_team: field(away, |t| t.display_name.as_ref()),
+        home_team_id: field(home, |t| t.id.as_ref()),
+        away_team_id: field(away, |t| t.id.as_ref()),
+        home_abbr: field(home, |t| t.abbreviation.as_ref()),
+        away_abbr: field(away, |t| t.abbreviation.as_ref()),
         home_score,
         away_score,
         event_status: "final".to_string(),
         error: String::new(),
+        start_time,
+        regulation_home_score: regulation_score(&home_periods, home_score),
+        regulation_away_score: regulation_score(&away_periods, away_score),
+        home_periods,
+        away_periods,
+        overtime,
+        shootout,
+        home_shootout_score: if shootout { shootout_score(home_comp) } else { None },
+        away_shootout_score: if shootout { shootout_score(away_comp) } else { None },
+        competitors: Vec::new(),
+        winner_id: String::new(),
+        player_stats: input
+            .player_stats
+            .iter()
+            .take(MAX_PLAYER_STATS)
+            .map(|r| PlayerStat {
+                athlete_id: r.athlete_id.clone(),
+                stat: r.stat.clone(),
+                value_x10: boxscore
+                    .as_ref()
+                    .and_then(|b| player_stat(b, &r.athlete_id, &r.stat)),
+            })
+            .collect(),
     })
 }
+
+/// Показатель игрока из boxscore ×10. Колонка ищется по ключу ("points"),
+/// затем по метке ("PTS"). "8-15" (реализовано-попыток) → реализовано,
+/// "-3" (плюс-минус) — как есть
+fn player_stat(boxscore: &Boxscore, athlete_id: &str, stat: &str) -> Option<i64> {
+    let groups = boxscore
+        .players
+        .iter()
+        .flatten()
+        .flat_map(|t| t.statistics.iter().flatten());
+
+    for group in groups {
+        let column = |names: &Option<Vec<String>>| {
+            names
+                .as_ref()?
+                .iter()
+                .position(|n| n.eq_ignore_ascii_case(stat))
+        };
+        let Some(col) = column(&group.keys).or_else(|| column(&group.labels)) else {
+            continue;
+        };
+        let Some(row) = group
+            .athletes
+            .iter()
+            .flatten()
+            .find(|a| a.athlete.as_ref().and_then(|x| x.id.as_deref()) == Some(athlete_id))
+        else {
+            continue;
+        };
+        let raw = row.stats.as_ref()?.get(col)?;
+        let value: f64 = raw
+            .trim()
+            .parse()
+            .ok()
+            .or_else(|| raw.split(['-', '/']).next()?.trim().parse().ok())?;
+        return Some((value * 10.0).round() as i64);
+    }
+    None
+}
+
+/// Режим standings: чемпион — команда на 1-м месте единой таблицы сезона.
+/// Таблица с конференциями / группами неоднозначна — нужен режим championship
+fn run_standings(input: &Input) -> Result<Output, Box<dyn std::error::Error>> {
+    let mut url = format!(
+        "https://site.api.espn.com/apis/v2/sports/{}/{}/standings",
+        input.sport, input.league
+    );
+    if !input.espn_event_id.is_empty() {
+        url.push_str(&format!("?season={}", input.espn_event_id));
+    }
+
+    let response = Client::new().get(&url).send()?;
+    if response.status() != 200 {
+        return Ok(Output::error(&format!("ESPN HTTP {}", response.status())));
+    }
+
+    let body = String::from_utf8_lossy(&response.body()?).to_string();
+    let standings: StandingsResponse =
+        serde_json::from_str(&body).map_err(|e| format!("ESPN standings JSON parse: {}", e))?;
+    Ok(standings_champion(standings))
+}
+
+/// Чемпион из ответа ESPN standings: единственная команда с rank 1
+fn standings_champion(standings: StandingsResponse) -> Output {
+    let mut tables: Vec<StandingsTable> = standings
+        .children
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|g| g.standings)
+        .collect();
+    tables.extend(standings.standings);
+    let table = match tables.len() {
+        1 => tables.remove(0),
+        0 => return Output::error("No standings in ESPN response"),
+        n => {
+            return Output::error(&format!(
+                "Standings have {} groups, use championship mode",
+                n
+            ))
+        }
+    };
+
+    let leaders: Vec<String> = table
+        .entries
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|e| {
+            e.stats
+                .iter()
+                .flatten()
+                .any(|s| s.name.as_deref() == Some("rank") && s.value == Some(1.0))
+        })
+        .filter_map(|e| e.team.and_then(|t| t.id))
+        .collect();
+    match leaders.as_slice() {
+        [id] => Output::outright(id.clone(), 0),
+        _ => Output::error(&format!(
+            "Expected one leader in standings, found {}",
+            leaders.len()
+        )),
+    }
+}
+
+/// Периодов в основном времени: тайм / четверть / период / иннинг.
+/// None — неизвестный вид спорта, основное время по linescores не считаем
+fn regulation_periods(sport: &str, league: &str) -> Option<usize> {
+    match sport {
+        "soccer" => Some(2),
+        "basketball" if league.contains("college") => Some(2),
+        "basketball" | "football" => Some(4),
+        "hockey" => Some(3),
+        "baseball" => Some(9),
+        _ => None,
+    }
+}
+
+/// (овертайм, серия) по статусу ESPN: STATUS_FINAL_OT / _AET / _SO / _PEN
+/// или detail ("Final/OT", "Final/SO", "FT-Pens", "AET")
+fn extra_time_status(status_type: &StatusType) -> (bool, bool) {
+    let name = status_type.name.as_deref().unwrap_or("");
+    let detail = status_type
+        .detail
+        .as_deref()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+
+    let shootout = matches!(
+        name,
+        "STATUS_FINAL_SO" | "STATUS_FINAL_PEN" | "STATUS_FINAL_PENS"
+    ) || detail.ends_with("/so")
+        || detail.contains("pens")
+        || detail.contains("penalties");
+    // "Final/OT", "Final/2OT"
+    let overtime = matches!(name, "STATUS_FINAL_OT" | "STATUS_FINAL_AET")
+        || detail
+            .split_once('/')
+            .is_some_and(|(_, p)| p.ends_with("ot"))
+        || detail.contains("aet");
+    (overtime || shootout, shootout)
+}
+
+/// Участники индивидуального события по занятому месту.
+/// Гонки и гольф — место из order; в остальных (теннис, MMA) order — номер
+/// угла / сетки, место выводится из флага winner.
+/// Непустой athlete_ids оставляет только спортсменов исходов рынка
+fn competitor_results(
+    competitors: &[Competitor],
+    sport: &str,
+    athlete_ids: &[String],
+) -> Vec<CompetitorResult> {
+    let ranked = matches!(sport, "racing" | "golf");
+    let has_winner = competitors.iter().any(|c| c.winner == Some(true));
+
+    let mut results: Vec<CompetitorResult> = competitors
+        .iter()
+        .filter_map(|c| {
+            let id = c
+                .athlete
+                .as_ref()
+                .and_then(|a| a.id.clone())
+                .or_else(|| c.id.clone())?;
+            let winner = c.winner.unwrap_or(false);
+            let position = match (ranked, winner, has_winner) {
+                (true, _, _) => c.order.unwrap_or(0),
+                (false, true, _) => 1,
+                (false, false, true) => 2,
+                (false, false, false) => 0,
+            };
+            Some(CompetitorResult {
+                id,
+                winner,
+                position,
+            })
+        })
+        .filter(|r| athlete_ids.is_empty() || athlete_ids.contains(&r.id))
+        .collect();
+    results.sort_by_key(|r| (!r.winner, r.position == 0, r.position));
+    results.truncate(MAX_COMPETITORS);
+    results
+}
+
+/// Очки команды по периодам из ESPN linescores.
+/// Непарсящийся период обнуляет весь список — частичные данные хуже пустых
+fn periods(competitor: &Competitor) -> Vec<i32> {
+    let parsed: Option<Vec<i32>> = competitor
+        .linescores
+        .iter()
+        .flatten()
+        .take(MAX_PERIODS)
+        .map(|l| {
+            l.display_value
+                .as_deref()
+                .and_then(|v| v.parse().ok())
+                .or_else(|| l.value.map(|v| v as i32))
+        })
+        .collect();
+    parsed.unwrap_or_default()
+}
+
+/// STATUS_POSTPONED / STATUS_CANCELED / ... → event_status для контракта.
+/// ESPN не всегда заполняет name — тогда смотрим detail ("Postponed", "Canceled")
+fn special_status(status_type: &StatusType) -> Option<&'static str> {
+    let name = status_type.name.as_deref().unwrap_or("");
+    let detail = status_type
+        .detail
+        .as_deref()
+        .unwrap_or("")
+        .to_ascii_lowercase();
+
+    match name {
+        "STATUS_POSTPONED" => Some("postponed"),
+        "STATUS_CANCELED" | "STATUS_CANCELLED" => Some("canceled"),
+        "STATUS_ABANDONED" => Some("abandoned"),
+        "STATUS_SUSPENDED" => Some("suspended"),
+        _ if detail.starts_with("postponed") => Some("postponed"),
+        _ if detail.starts_with("canceled") || detail.starts_with("cancelled") => Some("canceled"),
+        _ if detail.starts_with("abandoned") => Some("abandoned"),
+        _ if detail.starts_with("suspended") => Some("suspended"),
+        _ => None,
+    }
+}
+
+/// "2024-03-15T19:00Z" / "2024-03-15T19:00:00Z" → unix секунды (UTC)
+fn parse_espn_date(date: &str) -> Option<u64> {
+    let (day, time) = date.trim_end_matches('Z').split_once('T')?;
+
+    let mut d = day.split('-').map(|p| p.parse::<i64>());
+    let (y, m, dd) = (d.next()?.ok()?, d.next()?.ok()?, d.next()?.ok()?);
+    let mut t = time.split(':').map(|p| p.parse::<i64>());
+    let (hh, mm) = (t.next()?.ok()?, t.next()?.ok()?);
+    let ss = t.next().and_then(|r| r.ok()).unwrap_or(0);
+
+    // Дни от 1970-01-01 по григорианскому календарю (алгоритм days_from_civil)
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + dd - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let secs = days * 86_400 + hh * 3_600 + mm * 60 + ss;
+    u64::try_from(secs).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Первое соревнование из сохранённого ответа ESPN summary
+    fn competition(fixture: &str) -> Competition {
+        let espn: ESPNResponse = serde_json::from_str(fixture).unwrap();
+        espn.header
+            .and_then(|h| h.competitions)
+            .and_then(|c| c.into_iter().next())
+            .unwrap()
+    }
+
+    fn status_type(comp: &Competition) -> &StatusType {
+        comp.status.as_ref().unwrap().status_type.as_ref().unwrap()
+    }
+
+    fn final_output() -> Output {
+        Output {
+            home_team: "Los Angeles Lakers".to_string(),
+            away_team: "Boston Celtics".to_string(),
+            home_team_id: "13".to_string(),
+            away_team_id: "2".to_string(),
+            home_abbr: "LAL".to_string(),
+            away_abbr: "BOS".to_string(),
+            home_score: 112,
+            away_score: 108,
+            ..Output::not_finished("final", 1_710_529_200)
+        }
+    }
+
+    #[test]
+    fn oversized_output_keeps_the_first_requested_player_stat() {
+        let mut output = final_output();
+        output.home_periods = vec![28; MAX_PERIODS];
+        output.away_periods = vec![27; MAX_PERIODS];
+        output.player_stats = (0..MAX_PLAYER_STATS)
+            .map(|i| PlayerStat {
+                athlete_id: format!("{}", 1_966_000 + i),
+                stat: "threePointFieldGoalsMade-threePointFieldGoalsAttempted".to_string(),
+                value_x10: Some(40),
+            })
+            .collect();
+
+        let json = encode_output(output);
+        assert!(json.len() <= MAX_OUTPUT_BYTES);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["home_score"], 112);
+        assert_eq!(value["home_periods"], serde_json::json!([]));
+        let stats = value["player_stats"].as_array().unwrap();
+        assert!(!stats.is_empty() && stats.len() < MAX_PLAYER_STATS);
+        assert_eq!(stats[0]["athlete_id"], "1966000");
+    }
+
+    #[test]
+    fn parse_espn_date_reads_both_espn_formats() {
+        let comp = competition(include_str!("../fixtures/nba_postponed.json"));
+        assert_eq!(
+            comp.date.as_deref().and_then(parse_espn_date),
+            Some(1_710_529_200)
+        );
+        assert_eq!(parse_espn_date("2024-03-15T19:00:00Z"), Some(1_710_529_200));
+        // Високосный день и секунды
+        assert_eq!(parse_espn_date("2000-02-29T23:59:59Z"), Some(951_868_799));
+        assert_eq!(parse_espn_date("2024-03-15"), None);
+        assert_eq!(parse_espn_date("TBD"), None);
+    }
+
+    #[test]
+    fn special_status_by_name_or_detail() {
+        let postponed = competition(include_str!("../fixtures/nba_postponed.json"));
+        assert_eq!(special_status(status_type(&postponed)), Some("postponed"));
+
+        // ESPN без name: статус только в detail
+        let canceled = competition(include_str!("../fixtures/epl_canceled.json"));
+        assert!(status_type(&canceled).name.is_none());
+        assert_eq!(special_status(status_type(&canceled)), Some("canceled"));
+        assert_eq!(
+            canceled.date.as_deref().and_then(parse_espn_date),
+            Some(1_733_583_600)
+        );
+
+        let final_status = StatusType {
+            completed: Some(true),
+            state: Some("post".to_string()),
+            name: Some("STATUS_FINAL".to_string()),
+            detail: Some("Final".to_string()),
+        };
+        assert_eq!(special_status(&final_status), None);
+    }
+
+    fn side<'a>(comp: &'a Competition, home_away: &str) -> &'a Competitor {
+        comp.competitors
+            .iter()
+            .flatten()
+            .find(|c| c.home_away.as_deref() == Some(home_away))
+            .unwrap()
+    }
+
+    #[test]
+    fn periods_include_overtime_linescores() {
+        let comp = competition(include_str!("../fixtures/nba_final_ot.json"));
+        assert_eq!(periods(side(&comp, "home")), vec![28, 30, 25, 27, 12]);
+        assert_eq!(periods(side(&comp, "away")), vec![30, 26, 27, 27, 8]);
+    }
+
+    #[test]
+    fn unparsable_period_drops_all_periods() {
+        let competitor: Competitor = serde_json::from_value(serde_json::json!({
+            "homeAway": "home",
+            "score": "3",
+            "linescores": [
+                { "displayValue": "1", "value": 1.0 },
+                { "value": 2.0 },
+                { "displayValue": "-" }
+            ]
+        }))
+        .unwrap();
+        assert!(periods(&competitor).is_empty());
+
+        let competitor: Competitor = serde_json::from_value(serde_json::json!({
+            "homeAway": "home",
+            "score": "3",
+            "linescores": [{ "displayValue": "1" }, { "value": 2.0 }]
+        }))
+        .unwrap();
+        assert_eq!(periods(&competitor), vec![1, 2]);
+    }
+
+    #[test]
+    fn extra_time_status_from_espn_finals() {
+        let nba = competition(include_str!("../fixtures/nba_final_ot.json"));
+        assert_eq!(extra_time_status(status_type(&nba)), (true, false));
+        let regulation = regulation_periods("basketball", "nba").unwrap();
+        assert!(periods(side(&nba, "home")).len() > regulation);
+
+        let nhl = competition(include_str!("../fixtures/nhl_final_so.json"));
+        assert_eq!(extra_time_status(status_type(&nhl)), (true, true));
+
+        let cup = competition(include_str!("../fixtures/fa_cup_final_pens.json"));
+        assert_eq!(extra_time_status(status_type(&cup)), (true, true));
+        // shootoutScore приходит и числом, и строкой
+        assert_eq!(
+            side(&cup, "home").shootout_score,
+            Some(serde_json::json!(3))
+        );
+        assert_eq!(
+            side(&cup, "away").shootout_score,
+            Some(serde_json::json!("4"))
+        );
+
+        let status = |name: &str, detail: &str| StatusType {
+            completed: Some(true),
+            state: Some("post".to_string()),
+            name: Some(name.to_string()),
+            detail: Some(detail.to_string()),
+        };
+        assert_eq!(
+            extra_time_status(&status("STATUS_FINAL", "Final/2OT")),
+            (true, false)
+        );
+        assert_eq!(
+            extra_time_status(&status("STATUS_FINAL_AET", "AET")),
+            (true, false)
+        );
+        assert_eq!(
+            extra_time_status(&status("STATUS_FULL_TIME", "FT")),
+            (false, false)
+        );
+        assert_eq!(
+            extra_time_status(&status("STATUS_FINAL", "Final")),
+            (false, false)
+        );
+    }
+
+    #[test]
+    fn regulation_periods_by_sport_and_league() {
+        assert_eq!(regulation_periods("soccer", "eng.1"), Some(2));
+        assert_eq!(
+            regulation_periods("basketball", "mens-college-basketball"),
+            Some(2)
+        );
+        assert_eq!(regulation_periods("basketball", "wnba"), Some(4));
+        assert_eq!(regulation_periods("football", "nfl"), Some(4));
+        assert_eq!(regulation_periods("hockey", "nhl"), Some(3));
+        assert_eq!(regulation_periods("baseball", "mlb"), Some(9));
+        assert_eq!(regulation_periods("tennis", "atp"), None);
+    }
+
+    fn summary(result: &[CompetitorResult]) -> Vec<(&str, bool, u32)> {
+        result
+            .iter()
+            .map(|r| (r.id.as_str(), r.winner, r.position))
+            .collect()
+    }
+
+    #[test]
+    fn tennis_places_come_from_the_winner_flag() {
+        let comp = competition(include_str!("../fixtures/atp_match.json"));
+        let competitors = comp.competitors.unwrap();
+        // order у тенниса — позиция в сетке, а не место
+        assert_eq!(
+            summary(&competitor_results(&competitors, "tennis", &[])),
+            vec![("4686", true, 1), ("3782", false, 2)]
+        );
+    }
+
+    #[test]
+    fn race_places_come_from_order_and_unclassified_go_last() {
+        let comp = competition(include_str!("../fixtures/f1_race.json"));
+        let competitors = comp.competitors.unwrap();
+        assert_eq!(
+            summary(&competitor_results(&competitors, "racing", &[])),
+            vec![
+                ("868", true, 1),
+                ("4665", false, 2),
+                ("5579", false, 3),
+                ("5498", false, 0)
+            ]
+        );
+
+        // Рынок передал спортсменов исходов — остальные не выводятся
+        let athlete_ids = vec!["5498".to_string(), "4665".to_string()];
+        assert_eq!(
+            summary(&competitor_results(&competitors, "racing", &athlete_ids)),
+            vec![("4665", false, 2), ("5498", false, 0)]
+        );
+    }
+
+    #[test]
+    fn long_fields_are_cut_to_the_top_places() {
+        let field: Vec<Competitor> = (1..=30)
+            .rev()
+            .map(|place| {
+                serde_json::from_value(serde_json::json!({
+                    "id": format!("{}", 9000 + place),
+                    "order": place,
+                    "winner": place == 1,
+                }))
+                .unwrap()
+            })
+            .collect();
+        let results = competitor_results(&field, "golf", &[]);
+        assert_eq!(results.len(), MAX_COMPETITORS);
+        assert_eq!(results[0].id, "9001");
+        assert_eq!(results.last().unwrap().position, MAX_COMPETITORS as u32);
+    }
+
+    #[test]
+    fn player_stat_from_espn_boxscore() {
+        let espn: ESPNResponse =
+            serde_json::from_str(include_str!("../fixtures/nba_final_ot.json")).unwrap();
+        let boxscore = espn.boxscore.unwrap();
+
+        // Колонка по ключу ESPN или по метке
+        assert_eq!(player_stat(&boxscore, "1966", "points"), Some(310));
+        assert_eq!(player_stat(&boxscore, "1966", "PTS"), Some(310));
+        assert_eq!(player_stat(&boxscore, "1966", "ast"), Some(110));
+        // "13-22" — реализовано из попыток
+        assert_eq!(player_stat(&boxscore, "1966", "FG"), Some(130));
+        // Плюс-минус со знаком
+        assert_eq!(player_stat(&boxscore, "1966", "+/-"), Some(50));
+        assert_eq!(player_stat(&boxscore, "4065648", "plusMinus"), Some(-30));
+        // Игрок второй команды
+        assert_eq!(player_stat(&boxscore, "4065648", "REB"), Some(110));
+
+        // Не играл (DNP) — пустые stats
+        assert_eq!(player_stat(&boxscore, "3136776", "points"), None);
+        // Нет в протоколе / нет такого показателя
+        assert_eq!(player_stat(&boxscore, "999999", "points"), None);
+        assert_eq!(player_stat(&boxscore, "1966", "touchdowns"), None);
+    }
+
+    fn standings(fixture: &str) -> Output {
+        standings_champion(serde_json::from_str(fixture).unwrap())
+    }
+
+    #[test]
+    fn standings_champion_is_the_single_first_place_team() {
+        let champion = standings(include_str!("../fixtures/epl_standings.json"));
+        assert_eq!(champion.event_status, "final");
+        assert_eq!(champion.winner_id, "382");
+        assert!(champion.error.is_empty());
+    }
+
+    #[test]
+    fn conference_standings_need_championship_mode() {
+        let result = standings(include_str!("../fixtures/nba_standings.json"));
+        assert_eq!(result.event_status, "error");
+        assert_eq!(
+            result.error,
+            "Standings have 2 groups, use championship mode"
+        );
+        assert!(result.winner_id.is_empty());
+    }
+
+    #[test]
+    fn standings_without_a_single_leader_are_an_error() {
+        let tied = standings(
+            r#"{"standings": {"entries": [
+                {"team": {"id": "1"}, "stats": [{"name": "rank", "value": 1.0}]},
+                {"team": {"id": "2"}, "stats": [{"name": "rank", "value": 1.0}]}
+            ]}}"#,
+        );
+        assert_eq!(tied.error, "Expected one leader in standings, found 2");
+
+        let empty = standings("{}");
+        assert_eq!(empty.error, "No standings in ESPN response");
+    }
+}